@@ -0,0 +1,203 @@
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use crossbeam_channel::{Receiver, Sender};
+use engine_core::engine::Engine;
+use protocol::types::{
+    CancelOrder, Event, Order, OrderCommand, OrderId, OrderType, Price, Quantity, Side,
+};
+use std::hint::black_box;
+
+const SYMBOL: &str = "BENCH";
+const MID_PRICE: Price = 10_000;
+/// Commands in each run of the mixed flow
+const FLOW_LENGTH: usize = 10_000;
+
+fn limit(
+    order_id: OrderId,
+    user_id: u64,
+    side: Side,
+    quantity: Quantity,
+    price: Price,
+) -> OrderCommand {
+    OrderCommand::PlaceOrder(Order::new(
+        order_id,
+        user_id,
+        SYMBOL.to_string(),
+        side,
+        OrderType::Limit,
+        quantity,
+        Some(price),
+    ))
+}
+
+fn market(order_id: OrderId, user_id: u64, side: Side, quantity: Quantity) -> OrderCommand {
+    OrderCommand::PlaceOrder(Order::new(
+        order_id,
+        user_id,
+        SYMBOL.to_string(),
+        side,
+        OrderType::Market,
+        quantity,
+        None,
+    ))
+}
+
+fn cancel(order_id: OrderId, user_id: u64) -> OrderCommand {
+    OrderCommand::CancelOrder(CancelOrder::new(order_id, user_id, SYMBOL.to_string()))
+}
+
+/// An engine with 50 levels a side of 4 orders of 10 each, and the channel
+/// its events go to.
+fn seeded_engine() -> (Engine, Sender<Event>, Receiver<Event>) {
+    let mut engine = Engine::new(SYMBOL);
+    let (event_tx, event_rx) = crossbeam_channel::unbounded();
+
+    let mut order_id = 0;
+    for level in 0..50 {
+        for user_id in 1..=4 {
+            order_id += 1;
+            engine.execute(
+                limit(order_id, user_id, Side::Sell, 10, MID_PRICE + level),
+                &event_tx,
+            );
+            order_id += 1;
+            engine.execute(
+                limit(order_id, user_id, Side::Buy, 10, MID_PRICE - 1 - level),
+                &event_tx,
+            );
+        }
+    }
+    event_rx.try_iter().for_each(drop);
+
+    (engine, event_tx, event_rx)
+}
+
+/// Small deterministic generator so every run sees the same flow
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 33
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+/// Roughly what a venue sees: mostly passive orders around the touch, a
+/// third as many cancels of live orders and some aggressive orders.
+fn mixed_flow(first_order_id: OrderId, length: usize) -> Vec<OrderCommand> {
+    let mut rng = Lcg(42);
+    let mut live: Vec<(OrderId, u64)> = Vec::new();
+    let mut order_id = first_order_id;
+    let mut commands = Vec::with_capacity(length);
+
+    while commands.len() < length {
+        let user_id = 10 + rng.below(50);
+        let side = if rng.below(2) == 0 {
+            Side::Buy
+        } else {
+            Side::Sell
+        };
+
+        match rng.below(100) {
+            0..60 => {
+                order_id += 1;
+                let offset = 1 + rng.below(20);
+                let price = match side {
+                    Side::Buy => MID_PRICE - offset,
+                    Side::Sell => MID_PRICE + offset,
+                };
+                live.push((order_id, user_id));
+                commands.push(limit(order_id, user_id, side, 1 + rng.below(20), price));
+            }
+            60..90 if !live.is_empty() => {
+                let (order_id, user_id) = live.swap_remove(rng.below(live.len() as u64) as usize);
+                commands.push(cancel(order_id, user_id));
+            }
+            90..95 => {
+                order_id += 1;
+                let price = match side {
+                    Side::Buy => MID_PRICE + rng.below(5),
+                    Side::Sell => MID_PRICE - 1 - rng.below(5),
+                };
+                commands.push(limit(order_id, user_id, side, 1 + rng.below(30), price));
+            }
+            _ => {
+                order_id += 1;
+                commands.push(market(order_id, user_id, side, 1 + rng.below(30)));
+            }
+        }
+    }
+
+    commands
+}
+
+fn bench_commands(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine");
+    let next_order_id = 1_000_000;
+
+    let mut run_one = |name: &str, command: fn(OrderId) -> OrderCommand| {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    let (engine, event_tx, event_rx) = seeded_engine();
+                    (engine, event_tx, event_rx, command(next_order_id))
+                },
+                |(mut engine, event_tx, event_rx, command)| {
+                    black_box(engine.execute(command, &event_tx));
+                    (engine, event_rx)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    };
+
+    run_one("place_passive", |order_id| {
+        limit(order_id, 99, Side::Buy, 10, MID_PRICE - 25)
+    });
+    run_one("place_aggressive", |order_id| {
+        limit(order_id, 99, Side::Buy, 5, MID_PRICE)
+    });
+    run_one("market_sweep_10_levels", |order_id| {
+        market(order_id, 99, Side::Buy, 400)
+    });
+    run_one("cancel", |_| cancel(101, 3));
+
+    group.finish();
+}
+
+fn bench_mixed_flow(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine");
+    group.throughput(Throughput::Elements(FLOW_LENGTH as u64));
+
+    group.bench_function("mixed_flow", |b| {
+        b.iter_batched(
+            || {
+                let (engine, event_tx, event_rx) = seeded_engine();
+                (
+                    engine,
+                    event_tx,
+                    event_rx,
+                    mixed_flow(1_000_000, FLOW_LENGTH),
+                )
+            },
+            |(mut engine, event_tx, event_rx, flow)| {
+                for command in flow {
+                    black_box(engine.execute(command, &event_tx));
+                }
+                (engine, event_rx)
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_commands, bench_mixed_flow);
+criterion_main!(benches);
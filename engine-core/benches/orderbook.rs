@@ -0,0 +1,160 @@
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use engine_core::orderbook::orderbook::{OrderBook, OrderEntry};
+use protocol::types::{InstrumentSpec, OrderId, Price, Quantity, Side, TimeInForce};
+use std::hint::black_box;
+
+/// Best ask of the seeded books; bids start one tick below
+const MID_PRICE: Price = 10_000;
+const MAKER: u64 = 1;
+const TAKER: u64 = 2;
+
+fn order(
+    order_id: OrderId,
+    user_id: u64,
+    side: Side,
+    price: Price,
+    quantity: Quantity,
+) -> OrderEntry {
+    OrderEntry::new(
+        order_id,
+        user_id,
+        side,
+        price,
+        quantity,
+        TimeInForce::GTC,
+        None,
+    )
+}
+
+/// A book with `levels` ask and bid levels of `orders_per_level` orders of
+/// 10 each, on trees or on ladders.
+fn seeded_book(levels: u64, orders_per_level: u64, ladder: bool) -> OrderBook {
+    let spec = InstrumentSpec::new("BENCH").with_price_rules(1, 1, 2 * MID_PRICE);
+    let mut book = OrderBook::new(spec);
+    if ladder {
+        book.use_price_ladder();
+    }
+
+    let mut order_id = 0;
+    for level in 0..levels {
+        for _ in 0..orders_per_level {
+            order_id += 1;
+            let mut ask = order(order_id, MAKER, Side::Sell, MID_PRICE + level, 10);
+            book.match_limit_order(&mut ask, 0).unwrap();
+
+            order_id += 1;
+            let mut bid = order(order_id, MAKER, Side::Buy, MID_PRICE - 1 - level, 10);
+            book.match_limit_order(&mut bid, 0).unwrap();
+        }
+    }
+
+    book
+}
+
+fn layouts() -> [(&'static str, bool); 2] {
+    [("tree", false), ("ladder", true)]
+}
+
+fn bench_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("orderbook/add");
+
+    for (layout, ladder) in layouts() {
+        let book = seeded_book(100, 10, ladder);
+
+        // Joins the back of a level inside the book without crossing
+        group.bench_function(BenchmarkId::new("passive", layout), |b| {
+            b.iter_batched(
+                || book.clone(),
+                |mut book| {
+                    let mut bid = order(1_000_000, TAKER, Side::Buy, MID_PRICE - 50, 10);
+                    black_box(book.match_limit_order(&mut bid, 0).unwrap());
+                    book
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_cancel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("orderbook/cancel");
+
+    for (layout, ladder) in layouts() {
+        let book = seeded_book(100, 10, ladder);
+
+        // An order from the middle of a queue in the middle of the book
+        let middle = 2 * (50 * 10 + 5) + 1;
+        group.bench_function(BenchmarkId::new("middle_of_queue", layout), |b| {
+            b.iter_batched(
+                || book.clone(),
+                |mut book| {
+                    black_box(book.cancel_order(middle, MAKER).unwrap());
+                    book
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        // The only order of the best level, emptying it
+        let thin = seeded_book(100, 1, ladder);
+        group.bench_function(BenchmarkId::new("best_level", layout), |b| {
+            b.iter_batched(
+                || thin.clone(),
+                |mut book| {
+                    black_box(book.cancel_order(1, MAKER).unwrap());
+                    book
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_match(c: &mut Criterion) {
+    let mut group = c.benchmark_group("orderbook/match");
+
+    for (layout, ladder) in layouts() {
+        let book = seeded_book(100, 10, ladder);
+
+        // Takes part of the first order at the best ask
+        group.bench_function(BenchmarkId::new("aggressive_limit", layout), |b| {
+            b.iter_batched(
+                || book.clone(),
+                |mut book| {
+                    let mut buy = order(1_000_000, TAKER, Side::Buy, MID_PRICE, 5);
+                    black_box(book.match_limit_order(&mut buy, 0).unwrap());
+                    book
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        // Market orders clearing 1, 10 and 100 levels of 10 orders
+        for swept_levels in [1, 10, 100] {
+            group.bench_function(
+                BenchmarkId::new(format!("sweep_{}_levels", swept_levels), layout),
+                |b| {
+                    b.iter_batched(
+                        || book.clone(),
+                        |mut book| {
+                            let quantity = swept_levels * 10 * 10;
+                            let mut buy = order(1_000_000, TAKER, Side::Buy, 0, quantity);
+                            black_box(book.match_market_order(&mut buy, 0).unwrap());
+                            book
+                        },
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_add, bench_cancel, bench_match);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::Utc;
+
+/// Source of engine time, in unix millis.
+///
+/// The engine reads the clock once for every command it takes off the queue
+/// and once per expiry tick, and stamps everything that command produces
+/// with that single value. Nothing below the engine loop reads the wall
+/// clock, so the same commands at the same times always produce the same
+/// events.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> i64;
+}
+
+/// Wall clock time, the default for a live engine.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// A clock that only moves when told to, for tests and replays. Clones share
+/// the same time, so a handle kept outside the engine thread can drive it.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicI64>,
+}
+
+impl ManualClock {
+    pub fn new(now: i64) -> Self {
+        Self {
+            now: Arc::new(AtomicI64::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: i64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, millis: i64) {
+        self.now.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> i64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
@@ -1,14 +1,38 @@
-use crossbeam_channel::{Receiver, Sender};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveTime, TimeDelta};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use oneshot;
 use protocol::types::{
-    CancelOrder, CancelReason, Event, Order, OrderAck, OrderCancelled, OrderCommand, OrderReject,
-    OrderType, RejectReason,
+    AmendOrder, CancelOrder, CancelReason, Deposit, Event, GetDepth, IndicativePrice,
+    InstrumentSpec, MassCancel, Order, OrderAck, OrderAmended, OrderCancelled, OrderCommand,
+    OrderId, OrderReject, OrderType, Price, Quantity, RejectReason, SelfTradePrevention,
+    SessionState, SessionStateChanged, SetSessionState, Side, Symbol, TimeInForce, TrailingOffset,
+    UserId,
 };
 
-use crate::orderbook::orderbook::{OrderBook, OrderEntry};
+use crate::clock::{Clock, SystemClock};
+use crate::error::OrderBookError;
+use crate::journal::{Journal, JournalEntry};
+use crate::ledger::Ledger;
+use crate::orderbook::{
+    orderbook::{OrderBook, OrderEntry},
+    types::{CACHE_LIMIT, FeeSchedule, MatchResult, MatchingAlgorithm, PriceBand},
+};
+use crate::sequencer::EventSender;
+use crate::snapshot::{Snapshot, SnapshotInterval, SnapshotRef, SnapshotSchedule};
 use net::http::models::orders::{
-    CancelOrderResponse, CommandResponse, DepthResponse, OrderResponse,
+    AmendOrderResponse, CancelOrderResponse, CommandResponse, DepositResponse, DepthResponse,
+    ExchangeInfoResponse, MassCancelResponse, OrderResponse, RejectResponse, SessionStateResponse,
 };
+use runtime::latency::LatencyRecorder;
+
+/// How often the engine loop checks for expired orders
+pub const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_millis(100);
 
 /// synchronous matching engine
 /// runs in a dedicated thread, no async, deteministic, locks free
@@ -20,17 +44,292 @@ use net::http::models::orders::{
 /// - Handling errors
 /// - Logging
 /// - Metrics
+///
+/// Each traded symbol gets its own `OrderBook`; commands are routed to the
+/// book named by their `symbol` and rejected with `SymbolNotFound` otherwise.
+///
+/// Every book has a session state changed by `SetSessionState`. New orders
+/// and amends are rejected with `MarketClosed` unless the symbol is trading
+/// continuously; cancels are always accepted. During an auction call resting
+/// limit orders are collected without matching, and the book is uncrossed
+/// whenever it turns continuous.
+///
+/// GTD and DAY orders are expired from the engine loop on a fixed tick, so
+/// expiries are ordered with respect to commands on the engine thread.
+///
+/// Time comes from the engine's `Clock`, read once as each command is taken
+/// off the queue. Orders, trades and events produced by that command all
+/// carry that timestamp, so a command log replayed against a `ManualClock`
+/// reproduces the same events.
+///
+/// With a `Journal` every state-changing command is appended to it before it
+/// is processed, and `replay` rebuilds the books from it after a restart.
+/// Periodic snapshots keep that replay short: `restore` the latest one and
+/// replay only the journal entries written after it.
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Engine {
-    orderbook: OrderBook,
+    orderbooks: BTreeMap<Symbol, OrderBook>,
+    // UTC time of day at which DAY orders expire
+    session_close: NaiveTime,
+    // Balances checked and locked for every order, None trades without funds
+    ledger: Option<Ledger>,
+    clock: Arc<dyn Clock>,
+    // Write-ahead log of state-changing commands, None keeps nothing
+    journal: Option<Journal>,
+    snapshots: Option<SnapshotSchedule>,
+    // Highest order id placed, for id generation to carry on after a restart
+    last_order_id: OrderId,
+    // Sequence of the next event sent, global across symbols
+    next_sequence: u64,
+    // Time from taking each command off the queue to its events being sent
+    latency: LatencyRecorder,
 }
 
 impl Engine {
     pub fn new(symbol: &str) -> Self {
-        Self {
-            orderbook: OrderBook::new(symbol),
+        Self::with_symbols([symbol])
+    }
+
+    pub fn with_symbols<I, S>(symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::with_instruments(
+            symbols
+                .into_iter()
+                .map(|symbol| InstrumentSpec::new(symbol.as_ref())),
+        )
+    }
+
+    /// Creates a book per spec; orders on each symbol are validated against
+    /// its tick and lot grid and bounds.
+    pub fn with_instruments<I>(specs: I) -> Self
+    where
+        I: IntoIterator<Item = InstrumentSpec>,
+    {
+        let mut engine = Self {
+            orderbooks: BTreeMap::new(),
+            session_close: NaiveTime::MIN,
+            ledger: None,
+            clock: Arc::new(SystemClock),
+            journal: None,
+            snapshots: None,
+            last_order_id: 0,
+            next_sequence: 1,
+            latency: LatencyRecorder::new(),
+        };
+
+        for spec in specs {
+            engine.add_instrument(spec);
+        }
+
+        engine
+    }
+
+    pub fn add_symbol(&mut self, symbol: &str) {
+        self.add_instrument(InstrumentSpec::new(symbol));
+    }
+
+    pub fn add_instrument(&mut self, spec: InstrumentSpec) {
+        self.orderbooks
+            .entry(spec.symbol.clone())
+            .or_insert_with(|| OrderBook::new(spec));
+    }
+
+    pub fn has_symbol(&self, symbol: &str) -> bool {
+        self.orderbooks.contains_key(symbol)
+    }
+
+    /// Sets the self-trade prevention mode used for orders on `symbol` that
+    /// do not specify their own.
+    pub fn with_self_trade_prevention(mut self, symbol: &str, mode: SelfTradePrevention) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol) {
+            orderbook.set_self_trade_prevention(mode);
         }
+        self
+    }
+
+    /// Sets how the quantity trading at a price level of `symbol` is shared
+    /// among its resting orders, price-time FIFO by default.
+    pub fn with_matching_algorithm(mut self, symbol: &str, algorithm: MatchingAlgorithm) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol) {
+            orderbook.set_matching_algorithm(algorithm);
+        }
+        self
+    }
+
+    /// Charges the fills of `symbol` maker and taker fees, none by default.
+    /// Fees are taken out of what each fill receives, and a negative maker
+    /// fee credits a rebate instead.
+    pub fn with_fee_schedule(mut self, symbol: &str, fee_schedule: FeeSchedule) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol) {
+            orderbook.set_fee_schedule(fee_schedule);
+        }
+        self
+    }
+
+    /// Keeps the price levels of `symbol` in dense ladders indexed by tick
+    /// instead of trees. The ladders cover the whole price range of the
+    /// symbol's spec, so only symbols whose range spans fewer than 2^20 ticks
+    /// get them; others keep their trees.
+    pub fn with_price_ladder(mut self, symbol: &str) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol)
+            && !orderbook.use_price_ladder()
+        {
+            eprintln!("[Engine] Levels of {} do not fit a price ladder", symbol);
+        }
+        self
+    }
+
+    /// Sets the default protection band for market orders on `symbol`: they
+    /// stop matching `max_slippage_bps` away from the best price on arrival.
+    pub fn with_max_slippage(mut self, symbol: &str, max_slippage_bps: u64) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol) {
+            orderbook.set_max_slippage_bps(Some(max_slippage_bps));
+        }
+        self
+    }
+
+    /// Sets the session state `symbol` starts in, continuous by default.
+    pub fn with_session_state(mut self, symbol: &str, state: SessionState) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol) {
+            orderbook.set_session_state(state);
+        }
+        self
+    }
+
+    /// Sets a volatility band on `symbol`: limit orders priced more than
+    /// `width_bps` away from the mean trade price of the last `window` are
+    /// rejected, and matching that would trade outside the band pauses the
+    /// symbol in an auction call for `interruption`.
+    pub fn with_price_band(
+        mut self,
+        symbol: &str,
+        width_bps: u64,
+        window: Duration,
+        interruption: Duration,
+    ) -> Self {
+        if let Some(orderbook) = self.orderbooks.get_mut(symbol) {
+            orderbook.set_price_band(Some(PriceBand {
+                width_bps,
+                window_ms: window.as_millis() as i64,
+                interruption_ms: interruption.as_millis() as i64,
+            }));
+        }
+        self
+    }
+
+    /// Enforces balances from `ledger`: orders that cannot lock their funds
+    /// are rejected with `InsufficientBalance`.
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    pub fn ledger(&self) -> Option<&Ledger> {
+        self.ledger.as_ref()
+    }
+
+    /// Sets the UTC time of day at which DAY orders expire (midnight by default).
+    pub fn with_session_close(mut self, session_close: NaiveTime) -> Self {
+        self.session_close = session_close;
+        self
+    }
+
+    /// Replaces the wall clock, e.g. with a `ManualClock` for tests and replays.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Appends every state-changing command to `journal` before it is
+    /// processed. Replay what is already in it with `replay` first.
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Writes a snapshot of every book and the ledger to `path` at `interval`.
+    /// Each snapshot records how much of the journal it covers.
+    pub fn with_snapshots(mut self, path: impl Into<PathBuf>, interval: SnapshotInterval) -> Self {
+        self.snapshots = Some(SnapshotSchedule::new(path.into(), interval));
+        self
+    }
+
+    /// Highest order id placed so far, including replayed orders.
+    pub fn last_order_id(&self) -> OrderId {
+        self.last_order_id
+    }
+
+    /// Sequence number the next event will carry. Replay regenerates the
+    /// events it drops, so the numbering carries on where it left off.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Time each command took from being taken off the queue to its reply
+    /// and events being sent, by command. Printed when `run` ends.
+    pub fn latency(&self) -> &LatencyRecorder {
+        &self.latency
+    }
+
+    /// Loads the resting orders of every book and the ledger of `snapshot`;
+    /// replay the journal entries after `snapshot.journal_entries()` next.
+    /// Each book keeps the spec, fees and other settings this engine was
+    /// built with. Books for symbols this engine does not trade are dropped,
+    /// and the ledger is only restored into an engine that keeps one.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        for (symbol, orderbook) in snapshot.orderbooks {
+            match self.orderbooks.get_mut(&symbol) {
+                Some(current) => current.restore(orderbook),
+                None => eprintln!("[Engine] Dropping snapshot of unknown symbol {}", symbol),
+            }
+        }
+
+        if let (Some(ledger), Some(restored)) = (&mut self.ledger, snapshot.ledger) {
+            *ledger = restored;
+        }
+
+        self.last_order_id = self.last_order_id.max(snapshot.last_order_id);
+        self.next_sequence = self.next_sequence.max(snapshot.next_sequence);
+
+        println!(
+            "[Engine] Restored snapshot at journal entry {}",
+            snapshot.journal_entries
+        );
+    }
+
+    /// Rebuilds the books by running journaled entries through the engine at
+    /// the time they were recorded, so resting orders, price levels and trade
+    /// ids come back as they were. Must start from the configuration the
+    /// journal was written with. Replies and events are dropped and nothing
+    /// is journaled again.
+    pub fn replay<I>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = JournalEntry>,
+    {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let event_tx = EventSender::new(event_tx, self.next_sequence);
+        let mut replayed = 0;
+
+        for entry in entries {
+            match entry {
+                JournalEntry::Command { timestamp, command } => {
+                    let (reply_tx, _reply_rx) = oneshot::channel();
+                    self.handle_command(command, timestamp, reply_tx, &event_tx);
+                }
+                JournalEntry::Tick { timestamp } => self.handle_tick(timestamp, &event_tx),
+            }
+
+            event_rx.try_iter().for_each(drop);
+            replayed += 1;
+        }
+
+        self.next_sequence = event_tx.next_sequence();
+
+        println!("[Engine] Replayed {} journal entries", replayed);
     }
 
     pub fn run(
@@ -40,101 +339,528 @@ impl Engine {
     ) {
         println!("[Engine] Starting matching engine...");
 
+        let event_tx = EventSender::new(event_tx, self.next_sequence);
+
+        let mut next_expiry_check = Instant::now() + EXPIRY_CHECK_INTERVAL;
+
         loop {
-            match order_rx.recv() {
-                Ok((order_command, reply_tx)) => match order_command {
-                    OrderCommand::PlaceOrder(order) => {
-                        println!("[Engine] Placing order: {order:?}");
-                        self.handle_place_order(order, reply_tx, &event_tx);
-                    }
-                    OrderCommand::CancelOrder(cancel_order) => {
-                        println!("[Engine] Cancelling order: {cancel_order:?}");
-                        self.handle_cancel_order(cancel_order, reply_tx, &event_tx);
-                    }
-                    OrderCommand::GetDepth => {
-                        println!("[Engine] Getting depth");
-                        let depth = self.orderbook.get_depth(20);
-                        if let Err(e) = reply_tx.send(CommandResponse::Depth(DepthResponse {
-                            bids: depth.bids,
-                            asks: depth.asks,
-                        })) {
-                            eprintln!("[Engine] Failed to send depth response: {}", e);
-                        };
-                    }
-                },
+            let timeout = next_expiry_check.saturating_duration_since(Instant::now());
+
+            match order_rx.recv_timeout(timeout) {
+                Ok((order_command, reply_tx)) => {
+                    self.process_command(order_command, reply_tx, &event_tx);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
                 Err(e) => {
                     println!("[Engine] Error receiving order command: {e}");
                     break;
                 }
             }
+
+            if Instant::now() >= next_expiry_check {
+                let now = self.clock.now();
+
+                // Ticks that change nothing are left out of the journal
+                if self.has_due_timers(now) {
+                    match self.journal.as_mut().map_or(Ok(()), |j| j.append_tick(now)) {
+                        Ok(()) => {
+                            self.handle_tick(now, &event_tx);
+                            self.next_sequence = event_tx.next_sequence();
+                            self.record_state_change();
+                        }
+                        Err(e) => eprintln!("[Engine] Failed to journal tick: {}", e),
+                    }
+                }
+
+                self.snapshot_if_due();
+                next_expiry_check = Instant::now() + EXPIRY_CHECK_INTERVAL;
+            }
         }
 
+        if !self.latency.is_empty() {
+            print!("[Engine] Command latency:\n{}", self.latency);
+        }
         println!("[Engine] Engine shutting down");
     }
 
+    /// Runs one command on the calling thread as `run` would and returns its
+    /// reply, with its events sent to `event_tx`. Expiries and other timers
+    /// are only checked by `run`.
+    pub fn execute(
+        &mut self,
+        order_command: OrderCommand,
+        event_tx: &Sender<Event>,
+    ) -> CommandResponse {
+        let event_tx = EventSender::new(event_tx.clone(), self.next_sequence);
+        let (reply_tx, reply_rx) = oneshot::channel();
+
+        self.process_command(order_command, reply_tx, &event_tx);
+
+        reply_rx.recv().unwrap_or_else(|e| {
+            CommandResponse::Reject(RejectResponse {
+                reason: RejectReason::InternalError,
+                message: format!("Command was not replied to: {}", e),
+            })
+        })
+    }
+
+    /// Journals and handles a command taken off the queue, timing it from
+    /// now until its reply and events are sent.
+    fn process_command(
+        &mut self,
+        order_command: OrderCommand,
+        reply_tx: oneshot::Sender<CommandResponse>,
+        event_tx: &EventSender,
+    ) {
+        let received_at = Instant::now();
+        let now = self.clock.now();
+
+        let changes_state = changes_state(&order_command);
+        let command_name = command_name(&order_command);
+
+        match self.journal_command(now, &order_command) {
+            Ok(()) => {
+                self.handle_command(order_command, now, reply_tx, event_tx);
+                self.latency.record(command_name, received_at.elapsed());
+                self.next_sequence = event_tx.next_sequence();
+                if changes_state {
+                    self.record_state_change();
+                }
+            }
+            Err(e) => {
+                eprintln!("[Engine] Failed to journal command: {}", e);
+                let response = CommandResponse::Reject(RejectResponse {
+                    reason: RejectReason::InternalError,
+                    message: format!("Failed to journal command: {}", e),
+                });
+                if let Err(e) = reply_tx.send(response) {
+                    eprintln!("[Engine] Failed to send event: {}", e);
+                }
+            }
+        }
+    }
+
+    fn handle_command(
+        &mut self,
+        order_command: OrderCommand,
+        now: i64,
+        reply_tx: oneshot::Sender<CommandResponse>,
+        event_tx: &EventSender,
+    ) {
+        match order_command {
+            OrderCommand::PlaceOrder(order) => {
+                println!("[Engine] Placing order: {order:?}");
+                self.last_order_id = self.last_order_id.max(order.order_id);
+                self.handle_place_order(order, now, reply_tx, event_tx);
+            }
+            OrderCommand::CancelOrder(cancel_order) => {
+                println!("[Engine] Cancelling order: {cancel_order:?}");
+                self.handle_cancel_order(cancel_order, now, reply_tx, event_tx);
+            }
+            OrderCommand::AmendOrder(amend_order) => {
+                println!("[Engine] Amending order: {amend_order:?}");
+                self.handle_amend_order(amend_order, now, reply_tx, event_tx);
+            }
+            OrderCommand::MassCancel(mass_cancel) => {
+                println!("[Engine] Mass cancelling orders: {mass_cancel:?}");
+                self.handle_mass_cancel(mass_cancel, now, reply_tx, event_tx);
+            }
+            OrderCommand::GetDepth(get_depth) => {
+                println!("[Engine] Getting depth: {get_depth:?}");
+                self.handle_get_depth(get_depth, reply_tx);
+            }
+            OrderCommand::GetExchangeInfo => {
+                self.handle_get_exchange_info(reply_tx);
+            }
+            OrderCommand::SetSessionState(set_session_state) => {
+                println!("[Engine] Setting session state: {set_session_state:?}");
+                self.handle_set_session_state(set_session_state, now, reply_tx, event_tx);
+            }
+            OrderCommand::Deposit(deposit) => {
+                println!("[Engine] Depositing: {deposit:?}");
+                self.handle_deposit(deposit, reply_tx);
+            }
+        }
+    }
+
+    /// Writes `order_command` ahead to the journal unless it only reads.
+    fn journal_command(&mut self, now: i64, order_command: &OrderCommand) -> io::Result<()> {
+        let Some(journal) = &mut self.journal else {
+            return Ok(());
+        };
+
+        if changes_state(order_command) {
+            journal.append_command(now, order_command)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Counts a journaled command or tick towards the next snapshot and
+    /// writes one if it is due.
+    fn record_state_change(&mut self) {
+        if let Some(schedule) = &mut self.snapshots {
+            schedule.record_command();
+        }
+
+        self.snapshot_if_due();
+    }
+
+    fn snapshot_if_due(&mut self) {
+        let Some(schedule) = &mut self.snapshots else {
+            return;
+        };
+
+        if !schedule.is_due() {
+            return;
+        }
+
+        let snapshot = SnapshotRef {
+            journal_entries: self.journal.as_ref().map_or(0, Journal::entry_count),
+            last_order_id: self.last_order_id,
+            next_sequence: self.next_sequence,
+            orderbooks: &self.orderbooks,
+            ledger: &self.ledger,
+        };
+
+        match snapshot.write(&schedule.path) {
+            Ok(()) => println!(
+                "[Engine] Wrote snapshot at journal entry {}",
+                snapshot.journal_entries
+            ),
+            Err(e) => eprintln!("[Engine] Failed to write snapshot: {}", e),
+        }
+
+        schedule.reset();
+    }
+
+    /// Whether a tick at `now` has orders to expire or interruptions to end.
+    fn has_due_timers(&self, now: i64) -> bool {
+        self.orderbooks.values().any(|orderbook| {
+            orderbook
+                .next_expiry()
+                .is_some_and(|expire_at| expire_at <= now)
+                || orderbook.interruption_end().is_some_and(|end| end <= now)
+        })
+    }
+
+    fn handle_tick(&mut self, now: i64, event_tx: &EventSender) {
+        self.expire_orders(now, event_tx);
+        self.end_interruptions(now, event_tx);
+    }
+
     fn handle_place_order(
         &mut self,
         order: Order,
+        now: i64,
         reply_tx: oneshot::Sender<CommandResponse>,
-        event_tx: &Sender<Event>,
+        event_tx: &EventSender,
     ) {
         println!(
             "[Engine] Processing order: {} from user {}",
             order.order_id, order.user_id
         );
 
-        if order.quantity <= 0 {
-            let reject = Event::OrderReject(OrderReject {
-                order_id: order.order_id,
-                user_id: order.user_id,
-                reason: RejectReason::InvalidQuantity,
-                message: "Quantity must be greater than 0".to_string(),
-                symbol: order.symbol.clone(),
-            });
+        let Some(orderbook) = self.orderbooks.get_mut(&order.symbol) else {
+            let message = format!("Symbol not found: {}", order.symbol);
+            reject_order(
+                &order,
+                RejectReason::SymbolNotFound,
+                message,
+                reply_tx,
+                event_tx,
+            );
+            return;
+        };
 
-            if let Err(e) = reply_tx.send(CommandResponse::PlaceOrder(OrderResponse::Reject {
-                order_id: order.order_id,
-                reason: RejectReason::InvalidQuantity,
-                message: "Quantity must be greater than 0".to_string(),
-                symbol: order.symbol.clone(),
-            })) {
-                eprintln!("[Engine] Failed to send event: {}", e);
-            };
+        let accepted = match orderbook.session_state() {
+            SessionState::Continuous => true,
+            SessionState::Auction => {
+                order.order_type == OrderType::Limit
+                    && order.post_only.is_none()
+                    && !matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK)
+            }
+            SessionState::PreOpen | SessionState::Halted | SessionState::Closed => false,
+        };
 
-            if let Err(e) = event_tx.send(reject) {
-                eprintln!("[Engine] Failed to send event: {}", e);
+        if !accepted {
+            let message = match orderbook.session_state() {
+                SessionState::Auction => format!(
+                    "Only resting limit orders are accepted during the {} auction call",
+                    order.symbol
+                ),
+                state => format!("{} is not trading: {}", order.symbol, state),
             };
+            reject_order(
+                &order,
+                RejectReason::MarketClosed,
+                message,
+                reply_tx,
+                event_tx,
+            );
             return;
         }
 
-        if order.order_type == OrderType::Limit && order.price.unwrap_or(0) == 0 {
-            let reject = Event::OrderReject(OrderReject {
-                order_id: order.order_id,
-                user_id: order.user_id,
-                reason: RejectReason::InvalidOrder,
-                message: "Price is required for limit orders".to_string(),
-                symbol: order.symbol.clone(),
-            });
+        if let Some(quote_quantity) = order.quote_quantity
+            && (quote_quantity == 0
+                || order.quantity != 0
+                || order.order_type != OrderType::Market
+                || order.side != Side::Buy)
+        {
+            reject_order(
+                &order,
+                RejectReason::InvalidQuantity,
+                "Quote quantity must be positive and replaces quantity on market buys".to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
 
-            if let Err(e) = reply_tx.send(CommandResponse::PlaceOrder(OrderResponse::Reject {
-                order_id: order.order_id,
-                reason: RejectReason::InvalidOrder,
-                message: "Price is required for limit orders".to_string(),
-                symbol: order.symbol.clone(),
-            })) {
-                eprintln!("[Engine] Failed to send event: {}", e);
-            };
+        if order.quantity <= 0 && order.quote_quantity.is_none() {
+            reject_order(
+                &order,
+                RejectReason::InvalidQuantity,
+                "Quantity must be greater than 0".to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
 
-            if let Err(e) = event_tx.send(reject) {
-                eprintln!("[Engine] Failed to send event: {}", e);
+        if matches!(order.order_type, OrderType::Limit | OrderType::StopLimit)
+            && order.price.unwrap_or(0) == 0
+        {
+            reject_order(
+                &order,
+                RejectReason::InvalidOrder,
+                "Price is required for limit orders".to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        if let Err(e) = check_instrument(orderbook, &order) {
+            reject_order(&order, e.reject_reason(), e.to_string(), reply_tx, event_tx);
+            return;
+        }
+
+        if order.order_type == OrderType::Limit
+            && let Some(price) = order.price
+            && let Err(e) = orderbook.check_band(price, now)
+        {
+            reject_order(&order, e.reject_reason(), e.to_string(), reply_tx, event_tx);
+            return;
+        }
+
+        if let Some(display_quantity) = order.display_quantity
+            && (display_quantity == 0
+                || display_quantity > order.quantity
+                || !matches!(order.order_type, OrderType::Limit | OrderType::StopLimit))
+        {
+            reject_order(
+                &order,
+                RejectReason::InvalidQuantity,
+                "Display quantity must be between 1 and the order quantity on a limit order"
+                    .to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        if let Some(trailing_offset) = order.trailing_offset {
+            let valid = match trailing_offset {
+                TrailingOffset::Absolute(amount) => amount > 0,
+                TrailingOffset::Percentage(bps) => bps > 0 && bps < 10_000,
             };
+
+            if !valid
+                || !matches!(
+                    order.order_type,
+                    OrderType::StopMarket | OrderType::StopLimit
+                )
+            {
+                reject_order(
+                    &order,
+                    RejectReason::InvalidOrder,
+                    "Trailing offset must be positive and set on a stop order".to_string(),
+                    reply_tx,
+                    event_tx,
+                );
+                return;
+            }
+        }
+
+        // Trailing stops without a stop price start from the best price
+        let stop_price = match order.order_type {
+            OrderType::StopMarket | OrderType::StopLimit => {
+                let stop_price = order.stop_price.filter(|&p| p > 0).or_else(|| {
+                    order.trailing_offset.and_then(|offset| {
+                        orderbook.initial_trailing_stop_price(&order.side, offset)
+                    })
+                });
+
+                let Some(stop_price) = stop_price else {
+                    reject_order(
+                        &order,
+                        RejectReason::InvalidOrder,
+                        "Stop price is required for stop orders".to_string(),
+                        reply_tx,
+                        event_tx,
+                    );
+                    return;
+                };
+
+                Some(stop_price)
+            }
+            OrderType::Limit | OrderType::Market => None,
+        };
+
+        if let Some(max_slippage_bps) = order.max_slippage_bps
+            && (max_slippage_bps == 0
+                || max_slippage_bps >= 10_000
+                || !matches!(order.order_type, OrderType::Market | OrderType::StopMarket))
+        {
+            reject_order(
+                &order,
+                RejectReason::InvalidOrder,
+                "Max slippage must be between 1 and 9999 bps on a market order".to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        if order.post_only.is_some()
+            && (order.order_type != OrderType::Limit
+                || matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK))
+        {
+            reject_order(
+                &order,
+                RejectReason::InvalidOrder,
+                "Post-only is only supported for resting limit orders".to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        let expire_at = match order.time_in_force {
+            TimeInForce::GTD => match order.expire_at {
+                Some(expire_at) if expire_at > now => Some(expire_at),
+                Some(_) => {
+                    reject_order(
+                        &order,
+                        RejectReason::InvalidOrder,
+                        "Expiry time must be in the future".to_string(),
+                        reply_tx,
+                        event_tx,
+                    );
+                    return;
+                }
+                None => {
+                    reject_order(
+                        &order,
+                        RejectReason::InvalidOrder,
+                        "Expiry time is required for GTD orders".to_string(),
+                        reply_tx,
+                        event_tx,
+                    );
+                    return;
+                }
+            },
+            TimeInForce::DAY => Some(next_session_close(self.session_close, now)),
+            TimeInForce::GTC | TimeInForce::IOC | TimeInForce::FOK => None,
+        };
+
+        // Quote-sized buys are turned into the base quantity the budget buys
+        // at the current asks, within the protection band
+        let quantity = match order.quote_quantity {
+            Some(quote_quantity) => {
+                let limit_price = orderbook.market_price_limit(&order.side, order.max_slippage_bps);
+                let quantity = orderbook.quote_to_base_quantity(quote_quantity, limit_price);
+
+                if quantity == 0 {
+                    reject_order(
+                        &order,
+                        RejectReason::InsufficientLiquidity,
+                        format!(
+                            "Quote quantity {} does not buy any {} at the current asks",
+                            quote_quantity, order.symbol
+                        ),
+                        reply_tx,
+                        event_tx,
+                    );
+                    return;
+                }
+
+                if let Err(e) = orderbook.check_quantity(quantity) {
+                    reject_order(&order, e.reject_reason(), e.to_string(), reply_tx, event_tx);
+                    return;
+                }
+
+                quantity
+            }
+            None => order.quantity,
+        };
+
+        let mut order_entry = OrderEntry::new(
+            order.order_id,
+            order.user_id,
+            order.side.clone(),
+            order.price.unwrap_or(0),
+            quantity,
+            order.time_in_force,
+            expire_at,
+        )
+        .with_display_quantity(order.display_quantity)
+        .with_post_only(order.post_only)
+        .with_self_trade_prevention(order.self_trade_prevention)
+        .with_max_slippage_bps(order.max_slippage_bps)
+        .with_timestamp(now);
+
+        // Stop orders are checked against the book when they trigger
+        let limit_price = match order.order_type {
+            OrderType::Market => {
+                Some(orderbook.market_price_limit(&order.side, order.max_slippage_bps))
+            }
+            OrderType::Limit => Some(order.price),
+            OrderType::StopMarket | OrderType::StopLimit => None,
+        };
+
+        if let Some(limit_price) = limit_price
+            && let Err(e) =
+                orderbook.pre_trade_check(&mut order_entry, &order.order_type, limit_price, now)
+        {
+            reject_order(&order, e.reject_reason(), e.to_string(), reply_tx, event_tx);
             return;
         }
 
+        if let Some(ledger) = &mut self.ledger {
+            let (asset, amount) =
+                orderbook.required_funds(&order_entry, &order.order_type, stop_price);
+
+            if let Err(e) = ledger.reserve(order.order_id, order.user_id, asset, amount) {
+                reject_order(&order, e.reject_reason(), e.to_string(), reply_tx, event_tx);
+                return;
+            }
+
+            // Market buys are only costed against the asks, they stop
+            // matching once what they locked is spent
+            if order.side == Side::Buy
+                && matches!(order.order_type, OrderType::Market | OrderType::StopMarket)
+            {
+                order_entry = order_entry.with_quote_budget(Some(amount));
+            }
+        }
+
         let ack = Event::OrderAck(OrderAck {
             order_id: order.order_id,
             user_id: order.user_id,
             symbol: order.symbol.clone(),
+            sequence: 0,
         });
 
         if let Err(e) = reply_tx.send(CommandResponse::PlaceOrder(OrderResponse::Ack {
@@ -151,28 +877,39 @@ impl Engine {
             return;
         }
 
-        let mut order_entry = OrderEntry::new(
-            order.order_id,
-            order.user_id,
-            order.side,
-            order.price.unwrap_or(0),
-            order.quantity,
-        );
-
-        let result = match order.order_type {
-            OrderType::Market => self.orderbook.match_market_order(&mut order_entry),
-            OrderType::Limit => self.orderbook.match_limit_order(&mut order_entry),
+        // Stop orders had their stop price resolved before the ack, parking
+        // them cannot fail
+        let result = match (order.order_type, stop_price) {
+            (order_type, Some(stop_price)) => {
+                orderbook.add_stop_order(
+                    order_entry,
+                    order_type,
+                    stop_price,
+                    order.trailing_offset,
+                );
+                Ok(MatchResult::default())
+            }
+            (OrderType::Limit, None) if orderbook.session_state() == SessionState::Auction => {
+                orderbook.add_auction_order(order_entry)
+            }
+            (OrderType::Market, None) => orderbook.match_market_order(&mut order_entry, now),
+            (_, None) => orderbook.match_limit_order(&mut order_entry, now),
         };
 
-        let result = match result {
+        let mut result = match result {
             Ok(r) => r,
             Err(e) => {
+                if let Some(ledger) = &mut self.ledger {
+                    ledger.release(order.order_id);
+                }
+
                 let reject = Event::OrderReject(OrderReject {
                     order_id: order.order_id,
                     user_id: order.user_id,
-                    reason: RejectReason::InvalidOrder,
+                    reason: e.reject_reason(),
                     message: e.to_string(),
                     symbol: order.symbol.clone(),
+                    sequence: 0,
                 });
 
                 if let Err(e) = event_tx.send(reject) {
@@ -182,28 +919,9 @@ impl Engine {
             }
         };
 
-        let symbol = self.orderbook.get_symbol();
-
-        for fill in result.fills {
-            let event = Event::Fill(fill.into_protocol(symbol));
-            if let Err(e) = event_tx.send(event) {
-                eprintln!("[Engine] Failed to send event: {}", e);
-            };
-        }
-
-        for trade in result.trades {
-            let event = Event::Trade(trade.into_protocol(symbol));
-            if let Err(e) = event_tx.send(event) {
-                eprintln!("[Engine] Failed to send event: {}", e);
-            };
-        }
-
-        if let Some(depth) = result.book_update {
-            let event = Event::BookUpdate(depth.into_protocol(symbol));
-            if let Err(e) = event_tx.send(event) {
-                eprintln!("[Engine] Failed to send event: {}", e);
-            };
-        }
+        orderbook.activate_stops(&mut result, now);
+        settle_and_emit(self.ledger.as_mut(), orderbook, result, now, event_tx);
+        emit_indicative_price(orderbook, now, event_tx);
 
         println!("[Engine] Order processed: {:?}", order.order_id);
         return;
@@ -212,60 +930,807 @@ impl Engine {
     fn handle_cancel_order(
         &mut self,
         cancel_order: CancelOrder,
+        now: i64,
         reply_tx: oneshot::Sender<CommandResponse>,
-        event_tx: &Sender<Event>,
+        event_tx: &EventSender,
     ) {
         println!(
             "[Engine] Cancelling order: {} from user {}",
             cancel_order.order_id, cancel_order.user_id
         );
 
+        let Some(orderbook) = self.orderbooks.get_mut(&cancel_order.symbol) else {
+            let message = format!("Symbol not found: {}", cancel_order.symbol);
+            reject_cancel(
+                &cancel_order,
+                RejectReason::SymbolNotFound,
+                message,
+                reply_tx,
+                event_tx,
+            );
+            return;
+        };
+
+        let cancelled_order =
+            match orderbook.cancel_order(cancel_order.order_id, cancel_order.user_id) {
+                Ok(order) => order,
+                Err(e) => {
+                    eprintln!("[Engine] Failed to cancel order: {}", e);
+                    reject_cancel(
+                        &cancel_order,
+                        e.reject_reason(),
+                        e.to_string(),
+                        reply_tx,
+                        event_tx,
+                    );
+                    return;
+                }
+            };
+
+        if let Some(ledger) = &mut self.ledger {
+            ledger.release(cancelled_order.order_id);
+        }
+
         if let Err(e) = reply_tx.send(CommandResponse::CancelOrder(CancelOrderResponse::Ack {
             order_id: cancel_order.order_id,
             user_id: cancel_order.user_id,
             symbol: cancel_order.symbol.clone(),
         })) {
             eprintln!("[Engine] Failed to send event: {}", e);
+        }
+
+        let cancelled = Event::OrderCancelled(OrderCancelled {
+            order_id: cancelled_order.order_id,
+            user_id: cancelled_order.user_id,
+            symbol: orderbook.get_symbol().to_string(),
+            reason: CancelReason::UserRequested,
+            sequence: 0,
+        });
+
+        if let Err(e) = event_tx.send(cancelled) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+
+        let depth = orderbook.get_depth(20);
+        let event = Event::BookUpdate(depth.into_protocol(orderbook.get_symbol()));
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+
+        emit_indicative_price(orderbook, now, event_tx);
+    }
+
+    /// Cancels a user's orders on every book, or only on `mass_cancel.symbol`
+    /// when one is given, and replies with how many were cancelled.
+    fn handle_mass_cancel(
+        &mut self,
+        mass_cancel: MassCancel,
+        now: i64,
+        reply_tx: oneshot::Sender<CommandResponse>,
+        event_tx: &EventSender,
+    ) {
+        if let Some(symbol) = &mass_cancel.symbol
+            && !self.orderbooks.contains_key(symbol)
+        {
+            let message = format!("Symbol not found: {}", symbol);
+            if let Err(e) = reply_tx.send(CommandResponse::MassCancel(MassCancelResponse::Reject {
+                user_id: mass_cancel.user_id,
+                reason: RejectReason::SymbolNotFound,
+                message,
+            })) {
+                eprintln!("[Engine] Failed to send event: {}", e);
+            }
             return;
         }
 
-        let cancelled_order = match self.orderbook.remove_order(cancel_order.order_id) {
-            Ok(order) => order,
-            Err(e) => {
-                eprintln!("[Engine] Failed to remove order: {}", e);
+        let mut cancelled = 0;
 
-                let reject = Event::OrderReject(OrderReject {
-                    order_id: cancel_order.order_id,
-                    user_id: cancel_order.user_id,
-                    reason: RejectReason::InvalidOrder,
-                    message: e.to_string(),
-                    symbol: cancel_order.symbol,
-                });
+        for orderbook in self.orderbooks.values_mut() {
+            if mass_cancel
+                .symbol
+                .as_ref()
+                .is_some_and(|symbol| symbol != orderbook.get_symbol())
+            {
+                continue;
+            }
 
-                if let Err(e) = event_tx.send(reject) {
-                    eprintln!("[Engine] Failed to send event: {}", e);
-                }
+            let result =
+                orderbook.cancel_user_orders(mass_cancel.user_id, mass_cancel.side.as_ref());
+            if result.cancels.is_empty() {
+                continue;
+            }
+
+            cancelled += result.cancels.len();
+            settle_and_emit(self.ledger.as_mut(), orderbook, result, now, event_tx);
+            emit_indicative_price(orderbook, now, event_tx);
+        }
+
+        println!(
+            "[Engine] Mass cancelled {} orders for user {}",
+            cancelled, mass_cancel.user_id
+        );
+
+        if let Err(e) = reply_tx.send(CommandResponse::MassCancel(MassCancelResponse::Ack {
+            user_id: mass_cancel.user_id,
+            symbol: mass_cancel.symbol,
+            side: mass_cancel.side,
+            cancelled,
+        })) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+    }
+
+    fn handle_amend_order(
+        &mut self,
+        amend_order: AmendOrder,
+        now: i64,
+        reply_tx: oneshot::Sender<CommandResponse>,
+        event_tx: &EventSender,
+    ) {
+        let Some(orderbook) = self.orderbooks.get_mut(&amend_order.symbol) else {
+            let message = format!("Symbol not found: {}", amend_order.symbol);
+            reject_amend(
+                &amend_order,
+                RejectReason::SymbolNotFound,
+                message,
+                reply_tx,
+                event_tx,
+            );
+            return;
+        };
+
+        if orderbook.session_state() != SessionState::Continuous {
+            let message = format!(
+                "{} is not trading: {}",
+                amend_order.symbol,
+                orderbook.session_state()
+            );
+            reject_amend(
+                &amend_order,
+                RejectReason::MarketClosed,
+                message,
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        let invalid = match (amend_order.price, amend_order.quantity) {
+            (None, None) => Some((RejectReason::InvalidOrder, "Nothing to amend")),
+            (Some(0), _) => Some((RejectReason::InvalidPrice, "Price must be greater than 0")),
+            (_, Some(0)) => Some((
+                RejectReason::InvalidQuantity,
+                "Quantity must be greater than 0",
+            )),
+            _ => None,
+        };
+
+        if let Some((reason, message)) = invalid {
+            reject_amend(
+                &amend_order,
+                reason,
+                message.to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        let spec_check = amend_order
+            .price
+            .map_or(Ok(()), |price| {
+                orderbook
+                    .check_price(price)
+                    .and_then(|_| orderbook.check_band(price, now))
+            })
+            .and_then(|_| {
+                amend_order
+                    .quantity
+                    .map_or(Ok(()), |quantity| orderbook.check_quantity(quantity))
+            });
+
+        if let Err(e) = spec_check {
+            reject_amend(
+                &amend_order,
+                e.reject_reason(),
+                e.to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        if let Some(order) = orderbook.get_order(amend_order.order_id)
+            && order.user_id != amend_order.user_id
+        {
+            reject_amend(
+                &amend_order,
+                RejectReason::NotOrderOwner,
+                format!("Order {} does not belong to user", amend_order.order_id),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        // What the order has to lock once amended at `price`. Checked at the
+        // requested price before the book changes; a post-only slide only
+        // ever moves the price away from the book, so it never needs more.
+        let resting = orderbook.get_order(amend_order.order_id).map(|order| {
+            let filled = order.quantity - order.remaining_quantity;
+            let remaining = amend_order
+                .quantity
+                .unwrap_or(order.quantity)
+                .saturating_sub(filled);
+
+            (order.side.clone(), order.price, remaining)
+        });
+        let funds = |side: &Side, price: Price, remaining: Quantity| match side {
+            Side::Buy => price.saturating_mul(remaining),
+            Side::Sell => remaining,
+        };
+
+        if let (Some(ledger), Some((side, price, remaining))) = (&self.ledger, &resting)
+            && let Err(e) = ledger.check_resize(
+                amend_order.order_id,
+                funds(side, amend_order.price.unwrap_or(*price), *remaining),
+            )
+        {
+            reject_amend(
+                &amend_order,
+                e.reject_reason(),
+                e.to_string(),
+                reply_tx,
+                event_tx,
+            );
+            return;
+        }
+
+        let (amended, mut result) = match orderbook.amend_order(
+            amend_order.order_id,
+            amend_order.price,
+            amend_order.quantity,
+            now,
+        ) {
+            Ok(amended) => amended,
+            Err(e) => {
+                reject_amend(
+                    &amend_order,
+                    e.reject_reason(),
+                    e.to_string(),
+                    reply_tx,
+                    event_tx,
+                );
                 return;
             }
         };
 
-        let cancelled = Event::OrderCancelled(OrderCancelled {
-            order_id: cancelled_order.order_id,
-            user_id: cancelled_order.user_id,
-            symbol: self.orderbook.get_symbol().to_string(),
-            reason: CancelReason::UserRequested,
+        // Locked at the price the order actually rests at, after any slide
+        if let (Some(ledger), Some((side, _, remaining))) = (&mut self.ledger, resting) {
+            ledger.resize(amended.order_id, funds(&side, amended.price, remaining));
+        }
+
+        if let Err(e) = reply_tx.send(CommandResponse::AmendOrder(AmendOrderResponse::Ack {
+            order_id: amended.order_id,
+            user_id: amended.user_id,
+            symbol: amend_order.symbol.clone(),
+            price: amended.price,
+            quantity: amended.quantity,
+        })) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+
+        let event = Event::OrderAmended(OrderAmended {
+            order_id: amended.order_id,
+            user_id: amended.user_id,
+            symbol: amend_order.symbol,
+            price: amended.price,
+            quantity: amended.quantity,
+            remaining_quantity: amended.remaining_quantity,
+            sequence: 0,
         });
 
-        if let Err(e) = event_tx.send(cancelled) {
+        if let Err(e) = event_tx.send(event) {
             eprintln!("[Engine] Failed to send event: {}", e);
         }
 
-        let depth = self.orderbook.get_depth(20);
-        let event = Event::BookUpdate(depth.into_protocol(self.orderbook.get_symbol()));
+        orderbook.activate_stops(&mut result, now);
+        settle_and_emit(self.ledger.as_mut(), orderbook, result, now, event_tx);
+        emit_indicative_price(orderbook, now, event_tx);
+    }
+
+    /// Cancels resting orders that expired at or before `now` across all books.
+    fn expire_orders(&mut self, now: i64, event_tx: &EventSender) {
+        for orderbook in self.orderbooks.values_mut() {
+            let result = orderbook.expire_orders(now);
+
+            if result.cancels.is_empty() {
+                continue;
+            }
+
+            println!(
+                "[Engine] Expired {} orders on {}",
+                result.cancels.len(),
+                orderbook.get_symbol()
+            );
+            settle_and_emit(self.ledger.as_mut(), orderbook, result, now, event_tx);
+            emit_indicative_price(orderbook, now, event_tx);
+        }
+    }
+
+    fn handle_set_session_state(
+        &mut self,
+        set_session_state: SetSessionState,
+        now: i64,
+        reply_tx: oneshot::Sender<CommandResponse>,
+        event_tx: &EventSender,
+    ) {
+        let SetSessionState { symbol, state } = set_session_state;
+
+        let response = match self.set_session_state(&symbol, state, now, event_tx) {
+            Ok(previous) => SessionStateResponse::Ack {
+                symbol,
+                previous,
+                state,
+            },
+            Err((reason, message)) => SessionStateResponse::Reject {
+                symbol,
+                reason,
+                message,
+            },
+        };
+
+        if let Err(e) = reply_tx.send(CommandResponse::SessionState(response)) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+    }
+
+    fn handle_deposit(&mut self, deposit: Deposit, reply_tx: oneshot::Sender<CommandResponse>) {
+        let Deposit {
+            user_id,
+            asset,
+            amount,
+        } = deposit;
+
+        let response = match self.deposit(user_id, &asset, amount) {
+            Ok(available) => DepositResponse::Ack {
+                user_id,
+                asset,
+                available,
+            },
+            Err((reason, message)) => DepositResponse::Reject {
+                user_id,
+                asset,
+                reason,
+                message,
+            },
+        };
+
+        if let Err(e) = reply_tx.send(CommandResponse::Deposit(response)) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+    }
+
+    /// Credits `amount` of `asset` to the user and returns their available
+    /// balance. Only an engine that enforces balances takes deposits, and
+    /// only of assets one of its symbols trades.
+    fn deposit(
+        &mut self,
+        user_id: UserId,
+        asset: &str,
+        amount: Quantity,
+    ) -> Result<Quantity, (RejectReason, String)> {
+        let Some(ledger) = &mut self.ledger else {
+            return Err((
+                RejectReason::InvalidOrder,
+                "Balances are not enforced by this engine".to_string(),
+            ));
+        };
+        if amount == 0 {
+            return Err((
+                RejectReason::InvalidQuantity,
+                "Deposit amount must be positive".to_string(),
+            ));
+        }
+        let known = self.orderbooks.values().any(|orderbook| {
+            let spec = orderbook.spec();
+            spec.base == asset || spec.quote == asset
+        });
+        if !known {
+            return Err((
+                RejectReason::InvalidOrder,
+                format!("Unknown asset: {}", asset),
+            ));
+        }
+
+        ledger.deposit(user_id, asset, amount);
+        Ok(ledger.balance(user_id, asset).available)
+    }
+
+    /// Moves a symbol to another session state, broadcasts the change and
+    /// returns the state it left. Resting orders stay on the book through
+    /// halts and closes. Turning continuous uncrosses whatever the auction
+    /// call collected.
+    fn set_session_state(
+        &mut self,
+        symbol: &str,
+        state: SessionState,
+        now: i64,
+        event_tx: &EventSender,
+    ) -> Result<SessionState, (RejectReason, String)> {
+        let Some(orderbook) = self.orderbooks.get_mut(symbol) else {
+            return Err((
+                RejectReason::SymbolNotFound,
+                format!("Symbol not found: {}", symbol),
+            ));
+        };
+
+        let previous = orderbook.session_state();
+        if !previous.can_transition_to(state) {
+            return Err((
+                RejectReason::InvalidOrder,
+                format!("Cannot move {} from {} to {}", symbol, previous, state),
+            ));
+        }
+
+        orderbook.set_session_state(state);
+        println!("[Engine] {} moved from {} to {}", symbol, previous, state);
+
+        let event = Event::SessionStateChanged(SessionStateChanged {
+            symbol: symbol.to_string(),
+            previous,
+            state,
+            timestamp: now,
+            sequence: 0,
+        });
+
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+
+        if state == SessionState::Continuous {
+            // A book update means orders traded or were cancelled or
+            // decremented by self-trade prevention
+            match orderbook.uncross(now) {
+                Ok(mut result) if result.book_update.is_some() => {
+                    println!(
+                        "[Engine] Uncrossed {} with {} trades",
+                        symbol,
+                        result.trades.len()
+                    );
+                    orderbook.activate_stops(&mut result, now);
+                    settle_and_emit(self.ledger.as_mut(), orderbook, result, now, event_tx);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[Engine] Failed to uncross {}: {}", symbol, e),
+            }
+        }
+
+        emit_indicative_price(orderbook, now, event_tx);
+
+        Ok(previous)
+    }
+
+    /// Resumes continuous trading on books whose volatility interruption
+    /// ended at or before `now`.
+    fn end_interruptions(&mut self, now: i64, event_tx: &EventSender) {
+        let ended: Vec<Symbol> = self
+            .orderbooks
+            .values()
+            .filter(|orderbook| orderbook.interruption_end().is_some_and(|end| end <= now))
+            .map(|orderbook| orderbook.get_symbol().to_string())
+            .collect();
+
+        for symbol in ended {
+            if let Err((_, message)) =
+                self.set_session_state(&symbol, SessionState::Continuous, now, event_tx)
+            {
+                eprintln!("[Engine] Failed to end interruption: {}", message);
+            }
+        }
+    }
+
+    fn handle_get_exchange_info(&self, reply_tx: oneshot::Sender<CommandResponse>) {
+        let symbols = self
+            .orderbooks
+            .values()
+            .map(|orderbook| orderbook.spec().clone())
+            .collect();
+
+        if let Err(e) = reply_tx.send(CommandResponse::ExchangeInfo(ExchangeInfoResponse {
+            symbols,
+        })) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        }
+    }
+
+    fn handle_get_depth(
+        &mut self,
+        get_depth: GetDepth,
+        reply_tx: oneshot::Sender<CommandResponse>,
+    ) {
+        let response = match self.orderbooks.get_mut(&get_depth.symbol) {
+            Some(orderbook) => {
+                let depth = orderbook.get_depth(get_depth.limit.min(CACHE_LIMIT));
+                CommandResponse::Depth(DepthResponse {
+                    bids: depth.bids,
+                    asks: depth.asks,
+                })
+            }
+            None => CommandResponse::Reject(RejectResponse {
+                reason: RejectReason::SymbolNotFound,
+                message: format!("Symbol not found: {}", get_depth.symbol),
+            }),
+        };
+
+        if let Err(e) = reply_tx.send(response) {
+            eprintln!("[Engine] Failed to send depth response: {}", e);
+        };
+    }
+}
+
+/// Settles `result` on the ledger, if there is one, and emits its events. A
+/// fill its order's lock did not cover means the ledger no longer matches the
+/// book, so the symbol is halted until an operator has looked at it.
+fn settle_and_emit(
+    ledger: Option<&mut Ledger>,
+    orderbook: &mut OrderBook,
+    result: MatchResult,
+    now: i64,
+    event_tx: &EventSender,
+) {
+    let settled = ledger.map_or(Ok(()), |ledger| {
+        let spec = orderbook.spec();
+        ledger.settle(&spec.base, &spec.quote, &result)
+    });
+
+    emit_match_result(orderbook.get_symbol(), result, now, event_tx);
+
+    let Err(e) = settled else {
+        return;
+    };
+
+    eprintln!("[Engine] Halting {}: {}", orderbook.get_symbol(), e);
+    let previous = orderbook.session_state();
+    orderbook.set_session_state(SessionState::Halted);
+
+    let event = Event::SessionStateChanged(SessionStateChanged {
+        symbol: orderbook.get_symbol().to_string(),
+        previous,
+        state: SessionState::Halted,
+        timestamp: now,
+        sequence: 0,
+    });
+
+    if let Err(e) = event_tx.send(event) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    }
+}
+
+fn emit_match_result(symbol: &str, result: MatchResult, now: i64, event_tx: &EventSender) {
+    for fill in result.fills {
+        let event = Event::Fill(fill.into_protocol(symbol));
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+    }
+
+    for trade in result.trades {
+        let event = Event::Trade(trade.into_protocol(symbol));
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+    }
+
+    for update in result.stop_updates {
+        let event = Event::StopPriceUpdated(update.into_protocol(symbol));
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+    }
+
+    for decrement in result.decrements {
+        let event = Event::OrderAmended(decrement.into_protocol(symbol));
         if let Err(e) = event_tx.send(event) {
             eprintln!("[Engine] Failed to send event: {}", e);
         };
     }
+
+    for cancel in result.cancels {
+        let event = Event::OrderCancelled(cancel.into_protocol(symbol));
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+    }
+
+    if let Some(depth) = result.book_update {
+        let event = Event::BookUpdate(depth.into_protocol(symbol));
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+    }
+
+    if result.volatility_interruption {
+        println!("[Engine] Volatility interruption on {}", symbol);
+
+        let event = Event::SessionStateChanged(SessionStateChanged {
+            symbol: symbol.to_string(),
+            previous: SessionState::Continuous,
+            state: SessionState::Auction,
+            timestamp: now,
+            sequence: 0,
+        });
+        if let Err(e) = event_tx.send(event) {
+            eprintln!("[Engine] Failed to send event: {}", e);
+        };
+    }
+}
+
+/// Label the command's latency is recorded under.
+fn command_name(order_command: &OrderCommand) -> &'static str {
+    match order_command {
+        OrderCommand::PlaceOrder(_) => "PlaceOrder",
+        OrderCommand::CancelOrder(_) => "CancelOrder",
+        OrderCommand::AmendOrder(_) => "AmendOrder",
+        OrderCommand::MassCancel(_) => "MassCancel",
+        OrderCommand::GetDepth(_) => "GetDepth",
+        OrderCommand::GetExchangeInfo => "GetExchangeInfo",
+        OrderCommand::SetSessionState(_) => "SetSessionState",
+        OrderCommand::Deposit(_) => "Deposit",
+    }
+}
+
+/// Whether `order_command` can change engine state, and so is journaled.
+fn changes_state(order_command: &OrderCommand) -> bool {
+    !matches!(
+        order_command,
+        OrderCommand::GetDepth(_) | OrderCommand::GetExchangeInfo
+    )
+}
+
+/// Publishes where `orderbook` would uncross at `now` while its auction call
+/// runs.
+fn emit_indicative_price(orderbook: &OrderBook, now: i64, event_tx: &EventSender) {
+    if orderbook.session_state() != SessionState::Auction {
+        return;
+    }
+
+    let auction = orderbook.auction_price();
+    let event = Event::IndicativePrice(IndicativePrice {
+        symbol: orderbook.get_symbol().to_string(),
+        price: auction.as_ref().map(|auction| auction.price),
+        volume: auction.as_ref().map_or(0, |auction| auction.volume),
+        imbalance: auction.as_ref().map_or(0, |auction| auction.imbalance),
+        imbalance_side: auction.and_then(|auction| auction.imbalance_side),
+        timestamp: now,
+        sequence: 0,
+    });
+
+    if let Err(e) = event_tx.send(event) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
+}
+
+fn reject_cancel(
+    cancel_order: &CancelOrder,
+    reason: RejectReason,
+    message: String,
+    reply_tx: oneshot::Sender<CommandResponse>,
+    event_tx: &EventSender,
+) {
+    let reject = Event::OrderReject(OrderReject {
+        order_id: cancel_order.order_id,
+        user_id: cancel_order.user_id,
+        reason: reason.clone(),
+        message: message.clone(),
+        symbol: cancel_order.symbol.clone(),
+        sequence: 0,
+    });
+
+    if let Err(e) = reply_tx.send(CommandResponse::CancelOrder(CancelOrderResponse::Reject {
+        order_id: cancel_order.order_id,
+        reason,
+        message,
+    })) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
+
+    if let Err(e) = event_tx.send(reject) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
+}
+
+fn reject_amend(
+    amend_order: &AmendOrder,
+    reason: RejectReason,
+    message: String,
+    reply_tx: oneshot::Sender<CommandResponse>,
+    event_tx: &EventSender,
+) {
+    let reject = Event::OrderReject(OrderReject {
+        order_id: amend_order.order_id,
+        user_id: amend_order.user_id,
+        reason: reason.clone(),
+        message: message.clone(),
+        symbol: amend_order.symbol.clone(),
+        sequence: 0,
+    });
+
+    if let Err(e) = reply_tx.send(CommandResponse::AmendOrder(AmendOrderResponse::Reject {
+        order_id: amend_order.order_id,
+        reason,
+        message,
+    })) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
+
+    if let Err(e) = event_tx.send(reject) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
+}
+
+/// Checks the prices and quantities of `order` against the instrument spec of
+/// its book. Quote-sized buys have their quantity checked once it is known.
+fn check_instrument(orderbook: &OrderBook, order: &Order) -> Result<(), OrderBookError> {
+    if order.quote_quantity.is_none() {
+        orderbook.check_quantity(order.quantity)?;
+    }
+
+    if let Some(display_quantity) = order.display_quantity {
+        orderbook.check_quantity(display_quantity)?;
+    }
+
+    if let Some(price) = order.price {
+        orderbook.check_price(price)?;
+    }
+
+    if let Some(stop_price) = order.stop_price {
+        orderbook.check_price(stop_price)?;
+    }
+
+    Ok(())
+}
+
+/// First session close strictly after `now`, in unix millis.
+pub(crate) fn next_session_close(session_close: NaiveTime, now: i64) -> i64 {
+    let now_dt = DateTime::from_timestamp_millis(now).unwrap_or_default();
+    let close = now_dt.date_naive().and_time(session_close).and_utc();
+
+    if close.timestamp_millis() > now {
+        close.timestamp_millis()
+    } else {
+        (close + TimeDelta::days(1)).timestamp_millis()
+    }
+}
+
+fn reject_order(
+    order: &Order,
+    reason: RejectReason,
+    message: String,
+    reply_tx: oneshot::Sender<CommandResponse>,
+    event_tx: &EventSender,
+) {
+    let reject = Event::OrderReject(OrderReject {
+        order_id: order.order_id,
+        user_id: order.user_id,
+        reason: reason.clone(),
+        message: message.clone(),
+        symbol: order.symbol.clone(),
+        sequence: 0,
+    });
+
+    if let Err(e) = reply_tx.send(CommandResponse::PlaceOrder(OrderResponse::Reject {
+        order_id: order.order_id,
+        reason,
+        message,
+        symbol: order.symbol.clone(),
+    })) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
+
+    if let Err(e) = event_tx.send(reject) {
+        eprintln!("[Engine] Failed to send event: {}", e);
+    };
 }
 
 impl Default for Engine {
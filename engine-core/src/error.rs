@@ -1,4 +1,4 @@
-use protocol::types::OrderId;
+use protocol::types::{OrderId, RejectReason};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,9 +6,68 @@ pub enum OrderBookError {
     #[error("Order not found: {0}")]
     OrderNotFound(OrderId),
 
+    #[error("Order {0} does not belong to user")]
+    NotOrderOwner(OrderId),
+
+    #[error("Invalid price: {0}")]
+    InvalidPrice(String),
+
+    #[error("Invalid quantity: {0}")]
+    InvalidQuantity(String),
+
     #[error("Invalid Order: {0}")]
     InvalidOrder(String),
 
+    #[error("Insufficient liquidity: {0}")]
+    InsufficientLiquidity(String),
+
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalance(String),
+
+    #[error("Post-only order would cross: {0}")]
+    PostOnlyWouldCross(String),
+
+    #[error("Price outside band: {0}")]
+    PriceOutsideBand(String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
+
+impl OrderBookError {
+    pub fn reject_reason(&self) -> RejectReason {
+        match self {
+            OrderBookError::OrderNotFound(_) => RejectReason::OrderNotFound,
+            OrderBookError::NotOrderOwner(_) => RejectReason::NotOrderOwner,
+            OrderBookError::InvalidPrice(_) => RejectReason::InvalidPrice,
+            OrderBookError::InvalidQuantity(_) => RejectReason::InvalidQuantity,
+            OrderBookError::InvalidOrder(_) => RejectReason::InvalidOrder,
+            OrderBookError::InsufficientLiquidity(_) => RejectReason::InsufficientLiquidity,
+            OrderBookError::InsufficientBalance(_) => RejectReason::InsufficientBalance,
+            OrderBookError::PostOnlyWouldCross(_) => RejectReason::PostOnlyWouldCross,
+            OrderBookError::PriceOutsideBand(_) => RejectReason::PriceOutsideBand,
+            OrderBookError::InternalError(_) => RejectReason::InternalError,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("Journal I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Corrupt journal entry at line {line}: {message}")]
+    Corrupt { line: usize, message: String },
+}
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Snapshot I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Snapshot checksum mismatch: expected {expected:08x}, got {actual:08x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("Malformed snapshot: {0}")]
+    Malformed(String),
+}
@@ -0,0 +1,155 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use protocol::types::OrderCommand;
+use serde::{Deserialize, Serialize};
+
+use crate::error::JournalError;
+
+/// One line of the journal, as read back on startup.
+#[derive(Debug, Deserialize)]
+pub enum JournalEntry {
+    /// A command at the engine time it was taken off the queue.
+    Command {
+        timestamp: i64,
+        command: OrderCommand,
+    },
+    /// An expiry tick that had orders to expire or an interruption to end.
+    Tick { timestamp: i64 },
+}
+
+// Borrowed twin of `JournalEntry` so commands are not cloned to be written
+#[derive(Serialize)]
+enum Record<'a> {
+    Command {
+        timestamp: i64,
+        command: &'a OrderCommand,
+    },
+    Tick {
+        timestamp: i64,
+    },
+}
+
+/// Append-only write-ahead log of everything that changes engine state, one
+/// JSON entry per line.
+///
+/// The engine appends each command before processing it, so a process that
+/// dies mid-command has it on disk and replays it on the next start. Lines
+/// are written through to the OS but not fsynced; the journal survives a
+/// crash of the process, not of the machine.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    // Entries in the file, read back or appended since
+    entry_count: u64,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if missing, and returns it
+    /// with the entries already in it. A torn last line, left by a crash in
+    /// the middle of a write, is cut off; anything else that does not parse
+    /// is an error.
+    pub fn open(path: impl AsRef<Path>) -> Result<(Self, Vec<JournalEntry>), JournalError> {
+        Self::open_after(path, 0)
+    }
+
+    /// Like `open`, but only returns the entries after the first `skip`,
+    /// e.g. those a snapshot does not cover yet.
+    pub fn open_after(
+        path: impl AsRef<Path>,
+        skip: u64,
+    ) -> Result<(Self, Vec<JournalEntry>), JournalError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let mut reader = BufReader::new(&file);
+        let mut entries = Vec::new();
+        let mut line = String::new();
+        let mut line_number = 0;
+        let mut valid_len = 0;
+        let mut missing_line_end = false;
+        let mut entry_count = 0;
+
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+
+            if line.trim().is_empty() {
+                valid_len += read as u64;
+                continue;
+            }
+
+            // Skipped entries are still checked to be whole
+            let parsed = if entry_count < skip {
+                serde_json::from_str::<serde::de::IgnoredAny>(&line).map(|_| None)
+            } else {
+                serde_json::from_str(&line).map(Some)
+            };
+
+            match parsed {
+                Ok(entry) => {
+                    entries.extend(entry);
+                    entry_count += 1;
+                    valid_len += read as u64;
+                    missing_line_end = !line.ends_with('\n');
+                }
+                Err(e) if reader.fill_buf()?.is_empty() => {
+                    eprintln!(
+                        "[Journal] Cutting off torn entry at line {}: {}",
+                        line_number, e
+                    );
+                    file.set_len(valid_len)?;
+                }
+                Err(e) => {
+                    return Err(JournalError::Corrupt {
+                        line: line_number,
+                        message: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        let mut journal = Self { file, entry_count };
+
+        // The last entry made it to disk but its line end did not
+        if missing_line_end {
+            journal.file.write_all(b"\n")?;
+        }
+
+        Ok((journal, entries))
+    }
+
+    /// Number of entries in the journal, which is also the position the next
+    /// entry is written at.
+    pub fn entry_count(&self) -> u64 {
+        self.entry_count
+    }
+
+    pub(crate) fn append_command(
+        &mut self,
+        timestamp: i64,
+        command: &OrderCommand,
+    ) -> io::Result<()> {
+        self.append(&Record::Command { timestamp, command })
+    }
+
+    pub(crate) fn append_tick(&mut self, timestamp: i64) -> io::Result<()> {
+        self.append(&Record::Tick { timestamp })
+    }
+
+    fn append(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.entry_count += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use protocol::types::{OrderId, Quantity, Side, UserId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::OrderBookError;
+use crate::orderbook::types::MatchResult;
+
+/// Funds a user holds in one asset. `locked` is reserved by open orders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    pub available: u64,
+    pub locked: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Reservation {
+    user_id: UserId,
+    asset: String,
+    amount: u64,
+}
+
+/// Per-user, per-asset balances owned by the engine thread.
+///
+/// Orders lock what they may spend when they are accepted: quote for buys and
+/// base for sells. Fills pay out of the order's lock and credit the other
+/// asset as available less the fill's fee, and whatever is still locked when
+/// the order is done is released. Rebates are credited on top. Market and
+/// stop-market buys lock an estimate of their cost and stop matching once it
+/// is spent, so no order ever pays for a fill out of available.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    #[serde(serialize_with = "serialize_balances")]
+    #[serde(deserialize_with = "deserialize_balances")]
+    balances: HashMap<(UserId, String), Balance>,
+    reservations: HashMap<OrderId, Reservation>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deposit(&mut self, user_id: UserId, asset: &str, amount: u64) {
+        let balance = self.balance_mut(user_id, asset);
+        balance.available = balance.available.saturating_add(amount);
+    }
+
+    pub fn balance(&self, user_id: UserId, asset: &str) -> Balance {
+        self.balances
+            .get(&(user_id, asset.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Moves `amount` of `asset` from available to locked for `order_id`.
+    pub(crate) fn reserve(
+        &mut self,
+        order_id: OrderId,
+        user_id: UserId,
+        asset: &str,
+        amount: u64,
+    ) -> Result<(), OrderBookError> {
+        let balance = self.balance_mut(user_id, asset);
+
+        if balance.available < amount {
+            return Err(OrderBookError::InsufficientBalance(format!(
+                "{} {} required, {} available",
+                amount, asset, balance.available
+            )));
+        }
+
+        balance.available -= amount;
+        balance.locked += amount;
+
+        self.reservations.insert(
+            order_id,
+            Reservation {
+                user_id,
+                asset: asset.to_string(),
+                amount,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Whether the lock of `order_id` can grow to `amount`.
+    pub(crate) fn check_resize(
+        &self,
+        order_id: OrderId,
+        amount: u64,
+    ) -> Result<(), OrderBookError> {
+        let Some(reservation) = self.reservations.get(&order_id) else {
+            return Ok(());
+        };
+
+        let extra = amount.saturating_sub(reservation.amount);
+        let available = self
+            .balance(reservation.user_id, &reservation.asset)
+            .available;
+
+        if available < extra {
+            return Err(OrderBookError::InsufficientBalance(format!(
+                "{} more {} required, {} available",
+                extra, reservation.asset, available
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Locks more or releases part of the lock of `order_id` so it holds `amount`.
+    pub(crate) fn resize(&mut self, order_id: OrderId, amount: u64) {
+        let Some(reservation) = self.reservations.get_mut(&order_id) else {
+            return;
+        };
+
+        let balance = self
+            .balances
+            .entry((reservation.user_id, reservation.asset.clone()))
+            .or_default();
+
+        if amount > reservation.amount {
+            let extra = (amount - reservation.amount).min(balance.available);
+            balance.available -= extra;
+            balance.locked += extra;
+            reservation.amount += extra;
+        } else {
+            let freed = reservation.amount - amount;
+            balance.locked = balance.locked.saturating_sub(freed);
+            balance.available = balance.available.saturating_add(freed);
+            reservation.amount = amount;
+        }
+    }
+
+    /// Returns whatever `order_id` still has locked to available.
+    pub(crate) fn release(&mut self, order_id: OrderId) {
+        let Some(reservation) = self.reservations.remove(&order_id) else {
+            return;
+        };
+
+        let balance = self.balance_mut(reservation.user_id, &reservation.asset);
+        balance.locked = balance.locked.saturating_sub(reservation.amount);
+        balance.available = balance.available.saturating_add(reservation.amount);
+    }
+
+    /// Pays for the fills of `result` on a `base`/`quote` book, net of their
+    /// fees, releases the locks of orders that were filled or cancelled and
+    /// shrinks the locks of orders decremented by self-trade prevention.
+    ///
+    /// Every fill is settled even if one of them was not covered by its
+    /// order's lock, which is then returned as an error: the ledger no longer
+    /// matches the book.
+    pub(crate) fn settle(
+        &mut self,
+        base: &str,
+        quote: &str,
+        result: &MatchResult,
+    ) -> Result<(), OrderBookError> {
+        let mut overspent = Ok(());
+
+        for fill in &result.fills {
+            let notional = fill.filled_price.saturating_mul(fill.filled_quantity);
+
+            let spent = match fill.side {
+                Side::Buy => {
+                    let spent = self.spend(fill.order_id, fill.user_id, quote, notional);
+                    let received = fill.filled_quantity.saturating_add_signed(-fill.fee);
+                    self.deposit(fill.user_id, base, received);
+                    spent
+                }
+                Side::Sell => {
+                    let spent = self.spend(fill.order_id, fill.user_id, base, fill.filled_quantity);
+                    let received = notional.saturating_add_signed(-fill.fee);
+                    self.deposit(fill.user_id, quote, received);
+                    spent
+                }
+            };
+            overspent = overspent.and(spent);
+
+            if fill.remaining_quantity == 0 {
+                self.release(fill.order_id);
+            }
+        }
+
+        for cancel in &result.cancels {
+            self.release(cancel.order_id);
+        }
+
+        // Decremented orders only keep what their latest remaining quantity
+        // needs locked. Remaining only goes down, so the smallest one reported
+        // for the order, by a decrement or a later fill, is the latest.
+        for decrement in &result.decrements {
+            let remaining = result
+                .fills
+                .iter()
+                .filter(|fill| fill.order_id == decrement.order_id)
+                .map(|fill| fill.remaining_quantity)
+                .fold(decrement.remaining_quantity, Quantity::min);
+
+            let amount = match decrement.side {
+                Side::Buy => decrement.price.saturating_mul(remaining),
+                Side::Sell => remaining,
+            };
+            self.resize(decrement.order_id, amount);
+        }
+
+        overspent
+    }
+
+    /// Takes `amount` out of the lock of `order_id`. Any shortfall is an
+    /// error rather than being taken from the user's available balance.
+    fn spend(
+        &mut self,
+        order_id: OrderId,
+        user_id: UserId,
+        asset: &str,
+        amount: Quantity,
+    ) -> Result<(), OrderBookError> {
+        let from_lock = match self.reservations.get_mut(&order_id) {
+            Some(reservation) => {
+                let taken = reservation.amount.min(amount);
+                reservation.amount -= taken;
+                taken
+            }
+            None => 0,
+        };
+
+        let balance = self.balance_mut(user_id, asset);
+        balance.locked = balance.locked.saturating_sub(from_lock);
+
+        if from_lock < amount {
+            return Err(OrderBookError::InternalError(format!(
+                "Order {} of user {} spent {} {} but only {} was locked",
+                order_id, user_id, amount, asset, from_lock
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn balance_mut(&mut self, user_id: UserId, asset: &str) -> &mut Balance {
+        self.balances
+            .entry((user_id, asset.to_string()))
+            .or_default()
+    }
+}
+
+// JSON map keys must be strings, so balances are written as a list of pairs
+fn serialize_balances<S>(
+    balances: &HashMap<(UserId, String), Balance>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(balances.iter())
+}
+
+fn deserialize_balances<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<(UserId, String), Balance>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pairs = Vec::<((UserId, String), Balance)>::deserialize(deserializer)?;
+    Ok(pairs.into_iter().collect())
+}
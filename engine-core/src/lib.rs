@@ -1,6 +1,11 @@
+pub mod clock;
 pub mod engine;
 pub mod error;
+pub mod journal;
+pub mod ledger;
 pub mod orderbook;
+mod sequencer;
+pub mod snapshot;
 
 #[cfg(test)]
 mod tests;
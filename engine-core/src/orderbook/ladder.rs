@@ -0,0 +1,548 @@
+use std::collections::{BTreeMap, btree_map};
+use std::ops::{Bound, RangeBounds};
+
+use protocol::types::{InstrumentSpec, Price};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::orderbook::price_levels::PriceLevel;
+
+/// Most ticks a ladder spans from its lowest to its highest price.
+pub(crate) const MAX_LADDER_TICKS: u64 = 1 << 20;
+
+/// Slots allocated together when a level is added where there were none.
+const CHUNK_TICKS: usize = 1 << 10;
+
+/// One side of a book: the price levels in price order, either in a
+/// `BTreeMap` or in a dense ladder indexed by tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum BookSide {
+    Tree(BTreeMap<Price, PriceLevel>),
+    Ladder(PriceLadder),
+}
+
+impl BookSide {
+    pub(crate) fn tree() -> Self {
+        BookSide::Tree(BTreeMap::new())
+    }
+
+    /// An empty ladder over the price range of `spec`, if that range spans
+    /// fewer than `MAX_LADDER_TICKS` ticks.
+    pub(crate) fn ladder(spec: &InstrumentSpec) -> Option<Self> {
+        PriceLadder::new(spec).map(BookSide::Ladder)
+    }
+
+    /// The same levels in a ladder over the price range of `spec`, or the
+    /// side back unchanged if the range is too wide for a ladder or a level
+    /// is outside it or off its tick grid.
+    pub(crate) fn into_ladder(self, spec: &InstrumentSpec) -> Result<Self, Self> {
+        let Some(mut ladder) = PriceLadder::new(spec) else {
+            return Err(self);
+        };
+        if !self.iter().all(|(price, _)| ladder.can_hold(price)) {
+            return Err(self);
+        }
+
+        for (price, level) in self.into_levels() {
+            *ladder.get_or_insert(price) = level;
+        }
+
+        Ok(BookSide::Ladder(ladder))
+    }
+
+    /// The same levels in a `BTreeMap`.
+    pub(crate) fn into_tree(self) -> Self {
+        match self {
+            BookSide::Tree(_) => self,
+            BookSide::Ladder(ladder) => BookSide::Tree(ladder.into_levels().into_iter().collect()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_ladder(&self) -> bool {
+        matches!(self, BookSide::Ladder(_))
+    }
+
+    fn into_levels(self) -> Vec<(Price, PriceLevel)> {
+        match self {
+            BookSide::Tree(levels) => levels.into_iter().collect(),
+            BookSide::Ladder(ladder) => ladder.into_levels(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            BookSide::Tree(levels) => levels.is_empty(),
+            BookSide::Ladder(ladder) => ladder.is_empty(),
+        }
+    }
+
+    /// Lowest price with a level.
+    #[inline]
+    pub(crate) fn first_price(&self) -> Option<Price> {
+        match self {
+            BookSide::Tree(levels) => levels.keys().next().copied(),
+            BookSide::Ladder(ladder) => ladder.first_price(),
+        }
+    }
+
+    /// Highest price with a level.
+    #[inline]
+    pub(crate) fn last_price(&self) -> Option<Price> {
+        match self {
+            BookSide::Tree(levels) => levels.keys().next_back().copied(),
+            BookSide::Ladder(ladder) => ladder.last_price(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self, price: Price) -> Option<&mut PriceLevel> {
+        match self {
+            BookSide::Tree(levels) => levels.get_mut(&price),
+            BookSide::Ladder(ladder) => ladder.get_mut(price),
+        }
+    }
+
+    /// The level at `price`, created empty if there is none. A ladder that
+    /// does not cover `price` turns into a tree first, so an order always has
+    /// somewhere to rest. Prices checked against the spec the ladder was
+    /// built from never do that, so it is logged.
+    #[inline]
+    pub(crate) fn get_or_insert(&mut self, price: Price) -> &mut PriceLevel {
+        if !self.can_hold(price) {
+            eprintln!(
+                "[OrderBook] Price {} is off the price ladder, moving its levels to a tree",
+                price
+            );
+            let levels = std::mem::replace(self, BookSide::tree());
+            *self = levels.into_tree();
+        }
+
+        match self {
+            BookSide::Tree(levels) => levels
+                .entry(price)
+                .or_insert_with(|| PriceLevel::new(price)),
+            BookSide::Ladder(ladder) => ladder.get_or_insert(price),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn remove(&mut self, price: Price) {
+        match self {
+            BookSide::Tree(levels) => {
+                levels.remove(&price);
+            }
+            BookSide::Ladder(ladder) => ladder.remove(price),
+        }
+    }
+
+    /// Whether a level at `price` can be added.
+    #[inline]
+    pub(crate) fn can_hold(&self, price: Price) -> bool {
+        match self {
+            BookSide::Tree(_) => true,
+            BookSide::Ladder(ladder) => ladder.can_hold(price),
+        }
+    }
+
+    /// Levels in ascending price order.
+    #[inline]
+    pub(crate) fn iter(&self) -> Levels<'_> {
+        self.range(..)
+    }
+
+    /// Levels with a price in `range`, in ascending price order.
+    pub(crate) fn range<R>(&self, range: R) -> Levels<'_>
+    where
+        R: RangeBounds<Price>,
+    {
+        match self {
+            BookSide::Tree(levels) => Levels::Tree(levels.range(range)),
+            BookSide::Ladder(ladder) => Levels::Ladder(ladder.range(range)),
+        }
+    }
+}
+
+/// Price levels at consecutive ticks, lowest first. Finding a level is an
+/// index computation, and the slots of the lowest and highest levels are
+/// tracked so the best bid and ask are found without a scan.
+///
+/// The ladder spans every tick in the price range of the symbol, but its
+/// slots are allocated in chunks of `CHUNK_TICKS` when a level is first
+/// added in one and freed when its last level goes, so an empty ladder only
+/// holds the chunk table. Only the levels are serialized.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "LadderLevelsOwned")]
+pub(crate) struct PriceLadder {
+    tick_size: Price,
+    // Price of the first slot
+    base_price: Price,
+    // Number of slots, one per tick of the range
+    len: usize,
+    chunks: Vec<Option<Chunk>>,
+    // Slots of the lowest and highest levels
+    occupied: Option<(usize, usize)>,
+}
+
+/// Consecutive slots of a ladder and how many of them hold a level.
+#[derive(Debug, Clone)]
+struct Chunk {
+    slots: Box<[Option<PriceLevel>]>,
+    levels: usize,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        let mut slots = Vec::with_capacity(CHUNK_TICKS);
+        slots.resize_with(CHUNK_TICKS, || None);
+
+        Self {
+            slots: slots.into_boxed_slice(),
+            levels: 0,
+        }
+    }
+}
+
+impl PriceLadder {
+    /// A ladder over the prices of `spec` on its tick grid, or `None` if the
+    /// range spans `MAX_LADDER_TICKS` ticks or more.
+    pub(crate) fn new(spec: &InstrumentSpec) -> Option<Self> {
+        let tick_size = spec.tick_size.max(1);
+        let base_price = spec.min_price.checked_next_multiple_of(tick_size)?;
+        let top_price = spec.max_price / tick_size * tick_size;
+
+        let ticks = top_price.checked_sub(base_price)? / tick_size;
+        if ticks >= MAX_LADDER_TICKS {
+            return None;
+        }
+
+        Some(Self::with_slots(tick_size, base_price, ticks as usize + 1))
+    }
+
+    fn with_slots(tick_size: Price, base_price: Price, len: usize) -> Self {
+        let mut chunks = Vec::new();
+        chunks.resize_with(len.div_ceil(CHUNK_TICKS), || None);
+
+        Self {
+            tick_size,
+            base_price,
+            len,
+            chunks,
+            occupied: None,
+        }
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.occupied.is_none()
+    }
+
+    #[inline]
+    fn first_price(&self) -> Option<Price> {
+        self.occupied.map(|(first, _)| self.price_at(first))
+    }
+
+    #[inline]
+    fn last_price(&self) -> Option<Price> {
+        self.occupied.map(|(_, last)| self.price_at(last))
+    }
+
+    #[inline]
+    fn price_at(&self, index: usize) -> Price {
+        self.base_price + index as Price * self.tick_size
+    }
+
+    /// Slot of `price`, if it is on the tick grid of the ladder and within it.
+    #[inline]
+    fn index_of(&self, price: Price) -> Option<usize> {
+        let offset = price.checked_sub(self.base_price)?;
+        if !offset.is_multiple_of(self.tick_size) {
+            return None;
+        }
+
+        let index = (offset / self.tick_size) as usize;
+        (index < self.len).then_some(index)
+    }
+
+    /// Level in slot `index`, if its chunk is allocated and the slot is set.
+    #[inline]
+    fn slot(&self, index: usize) -> Option<&PriceLevel> {
+        self.chunks[index / CHUNK_TICKS].as_ref()?.slots[index % CHUNK_TICKS].as_ref()
+    }
+
+    #[inline]
+    fn get_mut(&mut self, price: Price) -> Option<&mut PriceLevel> {
+        let index = self.index_of(price)?;
+        self.chunks[index / CHUNK_TICKS].as_mut()?.slots[index % CHUNK_TICKS].as_mut()
+    }
+
+    #[inline]
+    fn can_hold(&self, price: Price) -> bool {
+        self.index_of(price).is_some()
+    }
+
+    /// The level at `price`, created empty if there is none. Callers check
+    /// `can_hold` first.
+    fn get_or_insert(&mut self, price: Price) -> &mut PriceLevel {
+        let index = self
+            .index_of(price)
+            .expect("price outside the ladder, check can_hold first");
+
+        self.occupied = Some(match self.occupied {
+            Some((first, last)) => (first.min(index), last.max(index)),
+            None => (index, index),
+        });
+
+        let chunk = self.chunks[index / CHUNK_TICKS].get_or_insert_with(Chunk::new);
+        let slot = &mut chunk.slots[index % CHUNK_TICKS];
+        if slot.is_none() {
+            chunk.levels += 1;
+        }
+        slot.get_or_insert_with(|| PriceLevel::new(price))
+    }
+
+    /// Drops the level at `price`, moving the lowest or highest level on to
+    /// the next one if it was either. A chunk left without levels is freed.
+    fn remove(&mut self, price: Price) {
+        let Some(index) = self.index_of(price) else {
+            return;
+        };
+        let entry = &mut self.chunks[index / CHUNK_TICKS];
+        let Some(chunk) = entry else {
+            return;
+        };
+        if chunk.slots[index % CHUNK_TICKS].take().is_none() {
+            return;
+        }
+        chunk.levels -= 1;
+        if chunk.levels == 0 {
+            *entry = None;
+        }
+
+        let Some((mut first, mut last)) = self.occupied else {
+            return;
+        };
+        if first == last {
+            self.occupied = None;
+            return;
+        }
+        if index == first {
+            first = self.next_occupied(first, last + 1).unwrap_or(last);
+        }
+        if index == last {
+            last = self.prev_occupied(first, last + 1).unwrap_or(first);
+        }
+        self.occupied = Some((first, last));
+    }
+
+    /// First slot in `from..to` holding a level, skipping unallocated chunks.
+    fn next_occupied(&self, from: usize, to: usize) -> Option<usize> {
+        let mut index = from;
+
+        while index < to {
+            match &self.chunks[index / CHUNK_TICKS] {
+                Some(chunk) if chunk.slots[index % CHUNK_TICKS].is_some() => return Some(index),
+                Some(_) => index += 1,
+                None => index = (index / CHUNK_TICKS + 1) * CHUNK_TICKS,
+            }
+        }
+
+        None
+    }
+
+    /// Last slot in `from..to` holding a level, skipping unallocated chunks.
+    fn prev_occupied(&self, from: usize, to: usize) -> Option<usize> {
+        let mut end = to;
+
+        while end > from {
+            let index = end - 1;
+            match &self.chunks[index / CHUNK_TICKS] {
+                Some(chunk) if chunk.slots[index % CHUNK_TICKS].is_some() => return Some(index),
+                Some(_) => end = index,
+                None => end = (index / CHUNK_TICKS * CHUNK_TICKS).max(from),
+            }
+        }
+
+        None
+    }
+
+    fn range<R>(&self, range: R) -> LadderLevels<'_>
+    where
+        R: RangeBounds<Price>,
+    {
+        // Only the slots from the lowest to the highest level are visited
+        let (first, len) = match self.occupied {
+            Some((first, last)) => (first, last + 1),
+            None => (0, 0),
+        };
+
+        // First slot at or above the start of the range
+        let start = match range.start_bound() {
+            Bound::Unbounded => 0,
+            Bound::Included(&price) => self.slots_below(price),
+            Bound::Excluded(&price) => self.slots_below(price.saturating_add(1)),
+        };
+
+        // First slot above the end of the range
+        let end = match range.end_bound() {
+            Bound::Unbounded => len,
+            Bound::Included(&price) => self.slots_below(price.saturating_add(1)),
+            Bound::Excluded(&price) => self.slots_below(price),
+        };
+
+        let start = start.max(first);
+        LadderLevels {
+            ladder: self,
+            front: start,
+            back: end.min(len).max(start),
+        }
+    }
+
+    /// Number of slots priced below `price`.
+    #[inline]
+    fn slots_below(&self, price: Price) -> usize {
+        let Some(offset) = price.checked_sub(self.base_price) else {
+            return 0;
+        };
+
+        (offset.div_ceil(self.tick_size) as usize).min(self.len)
+    }
+
+    fn into_levels(self) -> Vec<(Price, PriceLevel)> {
+        let (base_price, tick_size) = (self.base_price, self.tick_size);
+
+        self.chunks
+            .into_iter()
+            .enumerate()
+            .filter_map(|(chunk_index, chunk)| Some((chunk_index * CHUNK_TICKS, chunk?)))
+            .flat_map(|(first_index, chunk)| {
+                chunk
+                    .slots
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(move |(offset, slot)| {
+                        let index = first_index + offset;
+                        slot.map(|level| (base_price + index as Price * tick_size, level))
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Serialize for PriceLadder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LadderLevelsRef {
+            tick_size: self.tick_size,
+            base_price: self.base_price,
+            len: self.len,
+            levels: self.range(..).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// How a ladder is serialized: its shape and the levels it holds, without
+/// the empty slots.
+#[derive(Serialize)]
+struct LadderLevelsRef<'a> {
+    tick_size: Price,
+    base_price: Price,
+    len: usize,
+    levels: Vec<(Price, &'a PriceLevel)>,
+}
+
+#[derive(Deserialize)]
+struct LadderLevelsOwned {
+    tick_size: Price,
+    base_price: Price,
+    len: usize,
+    levels: Vec<(Price, PriceLevel)>,
+}
+
+impl TryFrom<LadderLevelsOwned> for PriceLadder {
+    type Error = String;
+
+    fn try_from(serialized: LadderLevelsOwned) -> Result<Self, Self::Error> {
+        if serialized.len as u64 > MAX_LADDER_TICKS {
+            return Err(format!("ladder of {} slots is too long", serialized.len));
+        }
+
+        let mut ladder = Self::with_slots(
+            serialized.tick_size.max(1),
+            serialized.base_price,
+            serialized.len,
+        );
+        for (price, level) in serialized.levels {
+            if !ladder.can_hold(price) {
+                return Err(format!("level at {} is outside the ladder", price));
+            }
+            *ladder.get_or_insert(price) = level;
+        }
+
+        Ok(ladder)
+    }
+}
+
+/// Levels of a `BookSide` in ascending price order; iterate with `.rev()`
+/// for descending.
+pub(crate) enum Levels<'a> {
+    Tree(btree_map::Range<'a, Price, PriceLevel>),
+    Ladder(LadderLevels<'a>),
+}
+
+impl<'a> Iterator for Levels<'a> {
+    type Item = (Price, &'a PriceLevel);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Levels::Tree(levels) => levels.next().map(|(&price, level)| (price, level)),
+            Levels::Ladder(levels) => levels.next(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for Levels<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            Levels::Tree(levels) => levels.next_back().map(|(&price, level)| (price, level)),
+            Levels::Ladder(levels) => levels.next_back(),
+        }
+    }
+}
+
+/// Occupied slots of a ladder between two slot indices.
+pub(crate) struct LadderLevels<'a> {
+    ladder: &'a PriceLadder,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> Iterator for LadderLevels<'a> {
+    type Item = (Price, &'a PriceLevel);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(index) = self.ladder.next_occupied(self.front, self.back) else {
+            self.front = self.back;
+            return None;
+        };
+        self.front = index + 1;
+
+        let level = self.ladder.slot(index)?;
+        Some((self.ladder.price_at(index), level))
+    }
+}
+
+impl DoubleEndedIterator for LadderLevels<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let Some(index) = self.ladder.prev_occupied(self.front, self.back) else {
+            self.back = self.front;
+            return None;
+        };
+        self.back = index;
+
+        let level = self.ladder.slot(index)?;
+        Some((self.ladder.price_at(index), level))
+    }
+}
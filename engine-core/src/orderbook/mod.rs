@@ -1,3 +1,4 @@
+pub mod ladder;
 pub mod orderbook;
 pub mod price_levels;
 pub mod types;
@@ -1,15 +1,23 @@
 use crate::{
     error::OrderBookError,
     orderbook::{
-        price_levels::PriceLevel,
-        types::{CACHE_LIMIT, CachedDepth, Depth, Fill, MatchResult, Trade},
+        ladder::BookSide,
+        price_levels::{OrderKey, OrderNode, PriceLevel},
+        types::{
+            AuctionPrice, CACHE_LIMIT, CachedDepth, Cancel, Decrement, Depth, FeeSchedule, Fill,
+            MatchResult, MatchingAlgorithm, PriceBand, StopUpdate, Trade,
+        },
     },
 };
-use chrono::Utc;
-use protocol::types::{OrderId, Price, Quantity, Side, UserId};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use protocol::types::{
+    CancelReason, InstrumentSpec, Liquidity, OrderId, OrderType, PostOnly, Price, Quantity,
+    SelfTradePrevention, SessionState, Side, TimeInForce, TrailingOffset, UserId,
+};
+use serde::{Deserialize, Serialize};
+use slab::Slab;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OrderEntry {
     pub(crate) order_id: OrderId,
     pub(crate) user_id: UserId,
@@ -17,17 +25,36 @@ pub struct OrderEntry {
     pub(crate) price: Price,
     pub(crate) quantity: Quantity,
     pub(crate) remaining_quantity: Quantity,
+    // Iceberg peak size and what is left of the current peak. Orders without
+    // a peak show their whole remaining quantity.
+    pub(crate) display_quantity: Option<Quantity>,
+    pub(crate) visible_quantity: Quantity,
+    pub(crate) time_in_force: TimeInForce,
+    pub(crate) expire_at: Option<i64>,
+    pub(crate) post_only: Option<PostOnly>,
+    pub(crate) self_trade_prevention: Option<SelfTradePrevention>,
+    // Market orders only, how far from the best price they may trade
+    pub(crate) max_slippage_bps: Option<u64>,
+    // Market and stop-market buys only, the most quote their fills may cost
+    #[serde(default)]
+    pub(crate) quote_budget: Option<Quantity>,
     pub(crate) timestamp: i64,
+    // Book-wide order in which resting orders last joined the back of a
+    // level queue, so orders on opposite sides can be compared
+    #[serde(default)]
+    pub(crate) queue_sequence: u64,
 }
 
 impl OrderEntry {
     #[inline]
-    pub(crate) fn new(
+    pub fn new(
         order_id: OrderId,
         user_id: UserId,
         side: Side,
         price: Price,
         quantity: Quantity,
+        time_in_force: TimeInForce,
+        expire_at: Option<i64>,
     ) -> Self {
         Self {
             order_id,
@@ -36,10 +63,60 @@ impl OrderEntry {
             price,
             quantity,
             remaining_quantity: quantity,
-            timestamp: Utc::now().timestamp_millis(),
+            display_quantity: None,
+            visible_quantity: quantity,
+            time_in_force,
+            expire_at,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+            quote_budget: None,
+            timestamp: 0,
+            queue_sequence: 0,
         }
     }
 
+    /// Engine time at which the order was accepted.
+    #[inline]
+    pub(crate) fn with_timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_display_quantity(mut self, display_quantity: Option<Quantity>) -> Self {
+        self.display_quantity = display_quantity;
+        self.visible_quantity = self.peak();
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_post_only(mut self, post_only: Option<PostOnly>) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_self_trade_prevention(
+        mut self,
+        self_trade_prevention: Option<SelfTradePrevention>,
+    ) -> Self {
+        self.self_trade_prevention = self_trade_prevention;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_max_slippage_bps(mut self, max_slippage_bps: Option<u64>) -> Self {
+        self.max_slippage_bps = max_slippage_bps;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_quote_budget(mut self, quote_budget: Option<Quantity>) -> Self {
+        self.quote_budget = quote_budget;
+        self
+    }
+
     #[inline]
     pub(crate) fn validate_order(&self) -> Result<(), OrderBookError> {
         if self.quantity == 0 {
@@ -65,58 +142,531 @@ impl OrderEntry {
             ));
         }
 
-        self.remaining_quantity = self.remaining_quantity.saturating_sub(quantity);
+        self.take(quantity);
 
         Ok(())
     }
+
+    /// Takes `quantity` off the order, for callers that have already capped
+    /// it at what is visible.
+    #[inline]
+    fn take(&mut self, quantity: Quantity) {
+        self.remaining_quantity = self.remaining_quantity.saturating_sub(quantity);
+        self.visible_quantity = self.visible_quantity.saturating_sub(quantity);
+    }
+
+    /// Shrinks the order by `quantity` without it trading, so it counts as
+    /// neither filled nor remaining.
+    #[inline]
+    fn decrement(&mut self, quantity: Quantity) {
+        self.take(quantity);
+        self.quantity = self.quantity.saturating_sub(quantity);
+    }
+
+    #[inline]
+    fn decremented(&self) -> Decrement {
+        Decrement {
+            order_id: self.order_id,
+            user_id: self.user_id,
+            side: self.side.clone(),
+            price: self.price,
+            quantity: self.quantity,
+            remaining_quantity: self.remaining_quantity,
+        }
+    }
+
+    /// Quantity to show for the next peak.
+    #[inline]
+    fn peak(&self) -> Quantity {
+        self.display_quantity
+            .map_or(self.remaining_quantity, |peak| {
+                peak.min(self.remaining_quantity)
+            })
+    }
+
+    /// Refills an exhausted iceberg peak from the hidden reserve, taking a new
+    /// time priority at `now`. Returns the newly visible quantity.
+    #[inline]
+    pub(crate) fn refill(&mut self, now: i64) -> Quantity {
+        if self.visible_quantity > 0 || self.remaining_quantity == 0 {
+            return 0;
+        }
+
+        self.visible_quantity = self.peak();
+        self.timestamp = now;
+        self.visible_quantity
+    }
+}
+
+/// A stop order waiting in the trigger book. `order.price` is the limit price
+/// for stop-limit orders and unused for stop-market orders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StopOrder {
+    pub(crate) order: OrderEntry,
+    pub(crate) order_type: OrderType,
+    pub(crate) stop_price: Price,
+    pub(crate) trailing_offset: Option<TrailingOffset>,
+}
+
+impl StopOrder {
+    /// Trigger price a trailing stop would have with the market at
+    /// `best_price`, if that moves it closer to the market.
+    #[inline]
+    fn trailed_stop_price(&self, best_price: Price) -> Option<Price> {
+        let stop_price = trailing_stop_price(&self.order.side, self.trailing_offset?, best_price)?;
+
+        let tightens = match self.order.side {
+            Side::Buy => stop_price < self.stop_price,
+            Side::Sell => stop_price > self.stop_price,
+        };
+
+        tightens.then_some(stop_price)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBook {
     symbol: String,
-    asks: BTreeMap<Price, PriceLevel>,
-    bids: BTreeMap<Price, PriceLevel>,
+    asks: BookSide,
+    bids: BookSide,
+
+    // Resting orders, each linked into the queue of its price level. Matching
+    // walks the queues by arena key; only commands that name an order by id
+    // go through `order_keys`.
+    orders: Slab<OrderNode>,
+    order_keys: HashMap<OrderId, OrderKey>,
+
+    // Resting GTD/DAY orders keyed by expiry time
+    expiries: BTreeSet<(i64, OrderId)>,
+
+    // Untriggered stop orders, kept out of the price levels and depth.
+    // Buy stops fire from the lowest stop price up, sell stops from the
+    // highest down.
+    stop_orders: HashMap<OrderId, StopOrder>,
+    buy_stops: BTreeSet<(Price, OrderId)>,
+    sell_stops: BTreeSet<(Price, OrderId)>,
+    trailing_stops: BTreeSet<OrderId>,
+
+    last_price: Option<Price>,
+
+    // Rebuilt from the levels rather than snapshotted
+    #[serde(skip, default = "CachedDepth::new")]
+    depth_cache: CachedDepth,
 
-    orders: HashMap<OrderId, OrderEntry>,
+    // Tick and lot grid and bounds orders are validated against
+    spec: InstrumentSpec,
 
-    depth_cache: CachedDepth,
+    // Applied to orders that do not choose their own mode
+    self_trade_prevention: SelfTradePrevention,
+
+    // How the quantity trading at a level is shared among its orders
+    matching_algorithm: MatchingAlgorithm,
+
+    // Maker and taker fees charged on every fill
+    fee_schedule: FeeSchedule,
+
+    // Default market order protection band in basis points, None is unlimited
+    max_slippage_bps: Option<u64>,
+
+    // Orders are only matched while the session is continuous
+    session_state: SessionState,
+
+    // Volatility band and the trades its reference price is taken from,
+    // oldest first and kept only while a band is set
+    price_band: Option<PriceBand>,
+    recent_trades: VecDeque<(i64, Price)>,
+
+    // When the current volatility interruption ends, in unix millis
+    interruption_end: Option<i64>,
 
-    // Track best bid/ask for quick access
-    // best_bid: Option<Price>,
-    // best_ask: Option<Price>,
     next_trade_id: u64,
+    #[serde(default)]
+    next_queue_sequence: u64,
 }
 
 impl OrderBook {
     #[inline]
-    pub(crate) fn new(symbol: &str) -> Self {
+    pub fn new(spec: InstrumentSpec) -> Self {
+        // The price-ladder feature makes every book whose price range fits a
+        // ladder start on ladders
+        let side = || {
+            if cfg!(feature = "price-ladder") {
+                BookSide::ladder(&spec).unwrap_or_else(BookSide::tree)
+            } else {
+                BookSide::tree()
+            }
+        };
+
         Self {
-            symbol: symbol.to_string(),
-            asks: BTreeMap::new(),
-            bids: BTreeMap::new(),
-            orders: HashMap::new(),
+            symbol: spec.symbol.clone(),
+            asks: side(),
+            bids: side(),
+            orders: Slab::new(),
+            order_keys: HashMap::new(),
+            expiries: BTreeSet::new(),
+            stop_orders: HashMap::new(),
+            buy_stops: BTreeSet::new(),
+            sell_stops: BTreeSet::new(),
+            trailing_stops: BTreeSet::new(),
+            last_price: None,
             depth_cache: CachedDepth::new(),
-            // best_bid: None,
-            // best_ask: None,
+            spec,
+            self_trade_prevention: SelfTradePrevention::default(),
+            matching_algorithm: MatchingAlgorithm::default(),
+            fee_schedule: FeeSchedule::default(),
+            max_slippage_bps: None,
+            session_state: SessionState::default(),
+            price_band: None,
+            recent_trades: VecDeque::new(),
+            interruption_end: None,
             next_trade_id: 1,
+            next_queue_sequence: 0,
         }
     }
 
-    pub(crate) fn match_market_order(
-        &mut self,
-        taker_order: &mut OrderEntry,
-    ) -> Result<MatchResult, OrderBookError> {
-        let mut fills = Vec::<Fill>::new();
-        let mut trades = Vec::<Trade>::new();
+    #[inline]
+    pub(crate) fn spec(&self) -> &InstrumentSpec {
+        &self.spec
+    }
 
-        let mut prices_to_remove = HashSet::<Price>::new();
+    /// Checks that `price` is on the tick grid and within the price bounds.
+    pub(crate) fn check_price(&self, price: Price) -> Result<(), OrderBookError> {
+        let spec = &self.spec;
 
-        let is_buy_order = matches!(taker_order.side, Side::Buy);
+        if price < spec.min_price || price > spec.max_price {
+            return Err(OrderBookError::InvalidPrice(format!(
+                "Price {} outside [{}, {}]",
+                price, spec.min_price, spec.max_price
+            )));
+        }
 
-        let levels = if is_buy_order {
-            &mut self.asks
+        if !price.is_multiple_of(spec.tick_size) {
+            return Err(OrderBookError::InvalidPrice(format!(
+                "Price {} is not a multiple of tick size {}",
+                price, spec.tick_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `quantity` is on the lot grid and within the quantity bounds.
+    pub(crate) fn check_quantity(&self, quantity: Quantity) -> Result<(), OrderBookError> {
+        let spec = &self.spec;
+
+        if quantity < spec.min_quantity || quantity > spec.max_quantity {
+            return Err(OrderBookError::InvalidQuantity(format!(
+                "Quantity {} outside [{}, {}]",
+                quantity, spec.min_quantity, spec.max_quantity
+            )));
+        }
+
+        if !quantity.is_multiple_of(spec.step_size) {
+            return Err(OrderBookError::InvalidQuantity(format!(
+                "Quantity {} is not a multiple of step size {}",
+                quantity, spec.step_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn set_self_trade_prevention(&mut self, mode: SelfTradePrevention) {
+        self.self_trade_prevention = mode;
+    }
+
+    /// Keeps both sides in dense ladders indexed by tick instead of trees,
+    /// each with a slot for every tick between the spec's minimum and maximum
+    /// price. Returns false if that range spans `MAX_LADDER_TICKS` ticks or
+    /// more, or for a side whose resting levels fall outside it; those sides
+    /// stay trees.
+    pub fn use_price_ladder(&mut self) -> bool {
+        let spec = &self.spec;
+        let mut converted = true;
+
+        for side in [&mut self.asks, &mut self.bids] {
+            let levels = std::mem::replace(side, BookSide::tree());
+            *side = levels.into_ladder(spec).unwrap_or_else(|levels| {
+                converted = false;
+                levels
+            });
+        }
+
+        converted
+    }
+
+    /// Takes the resting orders, stop orders, session and counters of
+    /// `snapshot`, this book as read back from a snapshot. The spec, fees,
+    /// matching and protection settings and level layout configured on this
+    /// book are kept.
+    pub(crate) fn restore(&mut self, snapshot: OrderBook) {
+        let OrderBook {
+            asks,
+            bids,
+            orders,
+            order_keys,
+            expiries,
+            stop_orders,
+            buy_stops,
+            sell_stops,
+            trailing_stops,
+            last_price,
+            session_state,
+            recent_trades,
+            interruption_end,
+            next_trade_id,
+            next_queue_sequence,
+            ..
+        } = snapshot;
+
+        let spec = &self.spec;
+        let relayout = |levels: BookSide, like: &BookSide| {
+            if like.is_ladder() {
+                levels.into_ladder(spec).unwrap_or_else(|levels| levels)
+            } else {
+                levels.into_tree()
+            }
+        };
+        self.asks = relayout(asks, &self.asks);
+        self.bids = relayout(bids, &self.bids);
+
+        self.orders = orders;
+        self.order_keys = order_keys;
+        self.expiries = expiries;
+        self.stop_orders = stop_orders;
+        self.buy_stops = buy_stops;
+        self.sell_stops = sell_stops;
+        self.trailing_stops = trailing_stops;
+        self.last_price = last_price;
+        self.session_state = session_state;
+        self.recent_trades = if self.price_band.is_some() {
+            recent_trades
         } else {
-            &mut self.bids
+            VecDeque::new()
+        };
+        self.interruption_end = interruption_end;
+        self.next_trade_id = next_trade_id;
+        self.next_queue_sequence = next_queue_sequence;
+        self.depth_cache.is_latest = false;
+    }
+
+    #[inline]
+    pub(crate) fn set_matching_algorithm(&mut self, matching_algorithm: MatchingAlgorithm) {
+        self.matching_algorithm = matching_algorithm;
+    }
+
+    #[inline]
+    pub(crate) fn set_fee_schedule(&mut self, fee_schedule: FeeSchedule) {
+        self.fee_schedule = fee_schedule;
+    }
+
+    #[inline]
+    pub(crate) fn set_max_slippage_bps(&mut self, max_slippage_bps: Option<u64>) {
+        self.max_slippage_bps = max_slippage_bps;
+    }
+
+    #[inline]
+    pub(crate) fn session_state(&self) -> SessionState {
+        self.session_state
+    }
+
+    #[inline]
+    pub(crate) fn set_session_state(&mut self, state: SessionState) {
+        self.session_state = state;
+        self.interruption_end = None;
+    }
+
+    #[inline]
+    pub(crate) fn interruption_end(&self) -> Option<i64> {
+        self.interruption_end
+    }
+
+    #[inline]
+    pub(crate) fn set_price_band(&mut self, price_band: Option<PriceBand>) {
+        self.price_band = price_band;
+        self.recent_trades.clear();
+    }
+
+    /// Mean trade price over the band window ending at `now`, falling back
+    /// to the last trade price. `None` before the first trade.
+    pub(crate) fn reference_price(&self, now: i64) -> Option<Price> {
+        let window_start = now - self.price_band?.window_ms;
+
+        let (sum, count) = self
+            .recent_trades
+            .iter()
+            .filter(|(timestamp, _)| *timestamp >= window_start)
+            .fold((0u128, 0u128), |(sum, count), (_, price)| {
+                (sum + *price as u128, count + 1)
+            });
+
+        match count {
+            0 => self.last_price,
+            _ => Some((sum / count) as Price),
+        }
+    }
+
+    /// Lowest and highest price the book may trade at `now`, `None` without
+    /// a band or a reference price.
+    pub(crate) fn band_limits(&self, now: i64) -> Option<(Price, Price)> {
+        let band = self.price_band?;
+        let reference = self.reference_price(now)?;
+        let width: Price = (reference as u128 * band.width_bps as u128 / 10_000)
+            .try_into()
+            .unwrap_or(Price::MAX);
+
+        Some((
+            reference.saturating_sub(width),
+            reference.saturating_add(width),
+        ))
+    }
+
+    /// Checks that a limit `price` lies within the volatility band at `now`.
+    pub(crate) fn check_band(&self, price: Price, now: i64) -> Result<(), OrderBookError> {
+        let Some((low, high)) = self.band_limits(now) else {
+            return Ok(());
+        };
+
+        if price < low || price > high {
+            return Err(OrderBookError::PriceOutsideBand(format!(
+                "Price {} outside [{}, {}]",
+                price, low, high
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Forgets trades that left the band window ending at `now`.
+    fn prune_recent_trades(&mut self, now: i64) {
+        let Some(band) = self.price_band else {
+            return;
+        };
+
+        while self
+            .recent_trades
+            .front()
+            .is_some_and(|(timestamp, _)| *timestamp < now - band.window_ms)
+        {
+            self.recent_trades.pop_front();
+        }
+    }
+
+    /// Pauses continuous trading in an auction call until the interruption
+    /// of the price band is over.
+    fn start_interruption(&mut self, now: i64) {
+        let Some(band) = self.price_band else {
+            return;
+        };
+
+        self.session_state = SessionState::Auction;
+        self.interruption_end = Some(now + band.interruption_ms);
+    }
+
+    /// Worst price a market order on `side` may trade at, `max_slippage_bps`
+    /// away from the current best opposite price. Falls back to the symbol
+    /// default; `None` means the order may sweep the whole book.
+    pub(crate) fn market_price_limit(
+        &self,
+        side: &Side,
+        max_slippage_bps: Option<u64>,
+    ) -> Option<Price> {
+        let bps = max_slippage_bps.or(self.max_slippage_bps)?;
+        let best = match side {
+            Side::Buy => self.get_best_ask(),
+            Side::Sell => self.get_best_bid(),
+        }?;
+
+        trailing_stop_price(side, TrailingOffset::Percentage(bps), best)
+    }
+
+    /// Base quantity a market buy spending `quote_quantity` gets, walking the
+    /// asks level by level on notional (price * quantity) up to `limit_price`.
+    pub(crate) fn quote_to_base_quantity(
+        &self,
+        quote_quantity: Quantity,
+        limit_price: Option<Price>,
+    ) -> Quantity {
+        let mut budget = quote_quantity;
+        let mut quantity: Quantity = 0;
+
+        for (price, level) in self.asks.iter() {
+            if !crosses(&Side::Buy, price, limit_price) {
+                break;
+            }
+
+            let level_quantity = level.get_total_quantity();
+            let notional = price.saturating_mul(level_quantity);
+
+            if budget < notional {
+                quantity = quantity.saturating_add(budget / price);
+                break;
+            }
+
+            quantity = quantity.saturating_add(level_quantity);
+            budget -= notional;
+        }
+
+        // Whole lots only
+        quantity - quantity % self.spec.step_size.max(1)
+    }
+
+    /// Cost in quote of a market buy of `quantity` against the current asks,
+    /// up to `limit_price`.
+    pub(crate) fn market_buy_cost(
+        &self,
+        quantity: Quantity,
+        limit_price: Option<Price>,
+    ) -> Quantity {
+        let mut needed = quantity;
+        let mut cost: Quantity = 0;
+
+        for (price, level) in self.asks.iter() {
+            if needed == 0 || !crosses(&Side::Buy, price, limit_price) {
+                break;
+            }
+
+            let taken = level.get_total_quantity().min(needed);
+            cost = cost.saturating_add(price.saturating_mul(taken));
+            needed -= taken;
+        }
+
+        cost
+    }
+
+    /// Asset and amount `order` locks while it is open: base quantity for
+    /// sells, and quote at the limit (or stop) price for buys. Market buys are
+    /// costed against the current asks.
+    pub(crate) fn required_funds(
+        &self,
+        order: &OrderEntry,
+        order_type: &OrderType,
+        stop_price: Option<Price>,
+    ) -> (&str, Quantity) {
+        let (base, quote) = (self.spec.base.as_str(), self.spec.quote.as_str());
+
+        let price = match (&order.side, order_type) {
+            (Side::Sell, _) => return (base, order.quantity),
+            (Side::Buy, OrderType::Market) => {
+                let limit_price = self.market_price_limit(&Side::Buy, order.max_slippage_bps);
+                return (quote, self.market_buy_cost(order.quantity, limit_price));
+            }
+            (Side::Buy, OrderType::Limit | OrderType::StopLimit) => order.price,
+            (Side::Buy, OrderType::StopMarket) => stop_price.unwrap_or(order.price),
+        };
+
+        (quote, price.saturating_mul(order.quantity))
+    }
+
+    pub fn match_market_order(
+        &mut self,
+        taker_order: &mut OrderEntry,
+        now: i64,
+    ) -> Result<MatchResult, OrderBookError> {
+        let levels = match taker_order.side {
+            Side::Buy => &self.asks,
+            Side::Sell => &self.bids,
         };
 
         // Check if there's any liquidity at all
@@ -126,344 +676,1312 @@ impl OrderBook {
             ));
         }
 
+        let mut result = MatchResult::default();
         let initial_remaining = taker_order.remaining_quantity;
+        let limit_price = self.market_price_limit(&taker_order.side, taker_order.max_slippage_bps);
+
+        let book_changed = self.match_against_book(taker_order, limit_price, now, &mut result);
+
+        // Self-trade prevention may have cancelled resting orders without
+        // anything trading, which still has to be reported
+        if taker_order.remaining_quantity == initial_remaining
+            && !book_changed
+            && !result.volatility_interruption
+        {
+            return Err(OrderBookError::InvalidOrder(
+                "Market order could not be filled - insufficient liquidity".to_string(),
+            ));
+        }
+
+        // Market orders never rest, whatever is left is cancelled. Liquidity
+        // still on the book means the protection band or the order's quote
+        // budget stopped the sweep.
+        if taker_order.remaining_quantity > 0 {
+            let levels = match taker_order.side {
+                Side::Buy => &self.asks,
+                Side::Sell => &self.bids,
+            };
+
+            let out_of_budget = taker_order.quote_budget.is_some_and(|budget| {
+                self.get_best_ask().is_some_and(|ask| {
+                    crosses(&Side::Buy, ask, limit_price)
+                        && budget / ask < self.spec.step_size.max(1)
+                })
+            });
+
+            let reason = if result.volatility_interruption {
+                CancelReason::VolatilityInterruption
+            } else if levels.is_empty() {
+                CancelReason::ImmediateOrCancel
+            } else if out_of_budget {
+                CancelReason::InsufficientBalance
+            } else {
+                CancelReason::SlippageExceeded
+            };
+
+            result.cancels.push(Cancel {
+                order_id: taker_order.order_id,
+                user_id: taker_order.user_id,
+                reason,
+            });
+        }
+
+        if book_changed {
+            self.depth_cache.is_latest = false;
+            result.book_update = Some(self.get_depth(20));
+        }
+
+        Ok(result)
+    }
+
+    pub fn match_limit_order(
+        &mut self,
+        taker_order: &mut OrderEntry,
+        now: i64,
+    ) -> Result<MatchResult, OrderBookError> {
+        // Repricing happens in `pre_trade_check`, a post-only order that
+        // still crosses here must not take liquidity
+        if taker_order.post_only.is_some() && self.would_cross(&taker_order.side, taker_order.price)
+        {
+            return Err(OrderBookError::PostOnlyWouldCross(format!(
+                "Order {} would take liquidity at {}",
+                taker_order.order_id, taker_order.price
+            )));
+        }
+
+        let mut result = MatchResult::default();
+        let limit_price = taker_order.price;
+
+        let mut book_changed =
+            self.match_against_book(taker_order, Some(limit_price), now, &mut result);
+
+        if taker_order.remaining_quantity > 0 {
+            match taker_order.time_in_force {
+                TimeInForce::GTC | TimeInForce::GTD | TimeInForce::DAY => {
+                    self.add_order(taker_order.clone());
+                    book_changed = true;
+                }
+                TimeInForce::IOC | TimeInForce::FOK => {
+                    result.cancels.push(Cancel {
+                        order_id: taker_order.order_id,
+                        user_id: taker_order.user_id,
+                        reason: CancelReason::ImmediateOrCancel,
+                    });
+                }
+            }
+        }
+
+        if book_changed {
+            self.depth_cache.is_latest = false;
+            result.book_update = Some(self.get_depth(20));
+        }
+
+        Ok(result)
+    }
+
+    /// Rests a limit order during the auction call without matching it, which
+    /// may leave the book crossed until `uncross`.
+    pub(crate) fn add_auction_order(
+        &mut self,
+        order: OrderEntry,
+    ) -> Result<MatchResult, OrderBookError> {
+        order.validate_order()?;
+        self.add_order(order);
+
+        Ok(MatchResult {
+            book_update: Some(self.get_depth(20)),
+            ..MatchResult::default()
+        })
+    }
+
+    /// Price the book would uncross at now, `None` if it does not cross.
+    ///
+    /// The price executing the most volume wins. Ties go to the smallest
+    /// imbalance, then to the highest price if buyers are left over at every
+    /// tied price or the lowest if sellers are, and otherwise to the price
+    /// closest to the last trade (the lowest without one).
+    pub(crate) fn auction_price(&self) -> Option<AuctionPrice> {
+        let (best_bid, best_ask) = (self.get_best_bid()?, self.get_best_ask()?);
+        if best_bid < best_ask {
+            return None;
+        }
+
+        let prices: BTreeSet<Price> = self
+            .bids
+            .range(best_ask..=best_bid)
+            .chain(self.asks.range(best_ask..=best_bid))
+            .map(|(price, _)| price)
+            .collect();
+
+        let mut candidates: Vec<AuctionPrice> = prices
+            .into_iter()
+            .map(|price| {
+                let demand = self
+                    .bids
+                    .range(price..)
+                    .fold(0, |sum: Quantity, (_, level)| {
+                        sum.saturating_add(level.get_total_quantity())
+                    });
+                let supply = self
+                    .asks
+                    .range(..=price)
+                    .fold(0, |sum: Quantity, (_, level)| {
+                        sum.saturating_add(level.get_total_quantity())
+                    });
+
+                AuctionPrice::new(price, demand, supply)
+            })
+            .collect();
+
+        let max_volume = candidates.iter().map(|c| c.volume).max()?;
+        candidates.retain(|c| c.volume == max_volume);
+
+        let min_imbalance = candidates.iter().map(|c| c.imbalance).min()?;
+        candidates.retain(|c| c.imbalance == min_imbalance);
+
+        // Candidates are in ascending price order
+        if candidates
+            .iter()
+            .all(|c| c.imbalance_side == Some(Side::Buy))
+        {
+            return candidates.pop();
+        }
+
+        if candidates
+            .iter()
+            .all(|c| c.imbalance_side == Some(Side::Sell))
+        {
+            return candidates.into_iter().next();
+        }
+
+        match self.last_price {
+            Some(reference) => candidates
+                .into_iter()
+                .min_by_key(|c| c.price.abs_diff(reference)),
+            None => candidates.into_iter().next(),
+        }
+    }
+
+    /// Ends an auction call by matching everything that crosses the auction
+    /// price, best price first and in queue order on each side, all at that
+    /// price. Of each matched pair the order that joined its queue first is
+    /// the maker; an amended or refilled order counts from when it requeued.
+    ///
+    /// Pairs of the same user are resolved with self-trade prevention instead
+    /// of trading, in the mode of the order that queued last. That can take
+    /// out quantity the auction price counted on and leave the book crossed,
+    /// so it is uncrossed again at the new auction price until it is not.
+    pub(crate) fn uncross(&mut self, now: i64) -> Result<MatchResult, OrderBookError> {
+        let mut result = MatchResult::default();
         let mut book_changed = false;
 
-        while taker_order.remaining_quantity > 0 && !levels.is_empty() {
-            let (&price, level) = if is_buy_order {
-                match levels.iter_mut().next() {
-                    Some(v) => v,
-                    None => {
-                        break;
+        while let Some(auction) = self.auction_price() {
+            self.uncross_at(auction.price, now, &mut result)?;
+            book_changed = true;
+        }
+
+        if !book_changed {
+            return Ok(result);
+        }
+
+        if let Some(trade) = result.trades.last() {
+            self.last_price = Some(trade.price);
+        }
+        self.prune_recent_trades(now);
+        self.depth_cache.is_latest = false;
+        result.book_update = Some(self.get_depth(20));
+
+        Ok(result)
+    }
+
+    /// Matches the orders crossing `price` against each other at that price.
+    fn uncross_at(
+        &mut self,
+        price: Price,
+        now: i64,
+        result: &mut MatchResult,
+    ) -> Result<(), OrderBookError> {
+        let mut buys = self.auction_queue(&Side::Buy, price);
+        let mut sells = self.auction_queue(&Side::Sell, price);
+        let (mut b, mut s) = (0, 0);
+
+        while b < buys.len() && s < sells.len() {
+            let quantity = buys[b].1.min(sells[s].1);
+            let (buy_id, sell_id) = (buys[b].0, sells[s].0);
+            let (maker_id, taker_id) = if buys[b].2 < sells[s].2 {
+                (buy_id, sell_id)
+            } else {
+                (sell_id, buy_id)
+            };
+
+            if let Some(stp_mode) = self.self_trade_mode(maker_id, taker_id) {
+                match stp_mode {
+                    SelfTradePrevention::CancelNewest => {
+                        self.cancel_self_trade(taker_id, result)?;
+                    }
+                    SelfTradePrevention::CancelOldest => {
+                        self.cancel_self_trade(maker_id, result)?;
+                    }
+                    SelfTradePrevention::CancelBoth => {
+                        self.cancel_self_trade(maker_id, result)?;
+                        self.cancel_self_trade(taker_id, result)?;
+                    }
+                    SelfTradePrevention::DecrementAndCancel => {
+                        self.decrement_self_trade(maker_id, quantity, result)?;
+                        self.decrement_self_trade(taker_id, quantity, result)?;
                     }
                 }
+
+                // Whatever is left of either order keeps its place
+                buys[b].1 = self.get_order(buy_id).map_or(0, |o| o.remaining_quantity);
+                sells[s].1 = self.get_order(sell_id).map_or(0, |o| o.remaining_quantity);
             } else {
-                match levels.iter_mut().next_back() {
-                    Some(v) => v,
-                    None => {
-                        break;
-                    }
+                buys[b].1 -= quantity;
+                sells[s].1 -= quantity;
+
+                let maker = self.fill_auction_order(maker_id, quantity, price, Liquidity::Maker)?;
+                let taker = self.fill_auction_order(taker_id, quantity, price, Liquidity::Taker)?;
+
+                result.trades.push(Trade {
+                    trade_id: self.next_trade_id,
+                    maker_order_id: maker.order_id,
+                    maker_user_id: maker.user_id,
+                    taker_order_id: taker.order_id,
+                    taker_user_id: taker.user_id,
+                    quantity,
+                    price,
+                    maker_fee: maker.fee,
+                    maker_fee_asset: maker.fee_asset.clone(),
+                    taker_fee: taker.fee,
+                    taker_fee_asset: taker.fee_asset.clone(),
+                    timestamp: now,
+                });
+                result.fills.push(maker);
+                result.fills.push(taker);
+
+                if self.price_band.is_some() {
+                    self.recent_trades.push_back((now, price));
                 }
+
+                self.next_trade_id = self.next_trade_id.saturating_add(1);
+            }
+
+            if buys[b].1 == 0 {
+                b += 1;
+            }
+            if sells[s].1 == 0 {
+                s += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Self-trade prevention mode that applies if the resting orders
+    /// `oldest_id` and `newest_id` belong to the same user: the newest
+    /// order's own or the book's default. `None` if they may trade.
+    fn self_trade_mode(
+        &self,
+        oldest_id: OrderId,
+        newest_id: OrderId,
+    ) -> Option<SelfTradePrevention> {
+        let (oldest, newest) = (self.get_order(oldest_id)?, self.get_order(newest_id)?);
+
+        (oldest.user_id == newest.user_id).then(|| {
+            newest
+                .self_trade_prevention
+                .unwrap_or(self.self_trade_prevention)
+        })
+    }
+
+    /// Takes a resting order off the book as cancelled by self-trade
+    /// prevention.
+    fn cancel_self_trade(
+        &mut self,
+        order_id: OrderId,
+        result: &mut MatchResult,
+    ) -> Result<(), OrderBookError> {
+        let order = self.remove_order(order_id)?;
+        result.cancels.push(Cancel {
+            order_id,
+            user_id: order.user_id,
+            reason: CancelReason::SelfTradePrevention,
+        });
+
+        Ok(())
+    }
+
+    /// Shrinks a resting order by `quantity` in place, keeping its queue
+    /// position, or cancels it if nothing would be left.
+    fn decrement_self_trade(
+        &mut self,
+        order_id: OrderId,
+        quantity: Quantity,
+        result: &mut MatchResult,
+    ) -> Result<(), OrderBookError> {
+        let Some(&key) = self.order_keys.get(&order_id) else {
+            return Err(OrderBookError::OrderNotFound(order_id));
+        };
+        let order = &mut self.orders[key].order;
+
+        if order.remaining_quantity <= quantity {
+            return self.cancel_self_trade(order_id, result);
+        }
+
+        let visible_before = order.visible_quantity;
+        order.decrement(quantity);
+        order.visible_quantity = visible_before.min(order.remaining_quantity);
+        result.decrements.push(order.decremented());
+
+        let levels = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if let Some(level) = levels.get_mut(order.price) {
+            level.remove_order(quantity, visible_before - order.visible_quantity);
+        }
+
+        Ok(())
+    }
+
+    /// Resting orders on `side` that cross `price` with their remaining
+    /// quantity and queue sequence, in the order they match in.
+    fn auction_queue(&self, side: &Side, price: Price) -> Vec<(OrderId, Quantity, u64)> {
+        let levels: Vec<&PriceLevel> = match side {
+            Side::Buy => self.bids.range(price..).rev().map(|(_, l)| l).collect(),
+            Side::Sell => self.asks.range(..=price).map(|(_, l)| l).collect(),
+        };
+
+        levels
+            .into_iter()
+            .flat_map(|level| {
+                level.iter(&self.orders).map(|(_, order)| {
+                    (
+                        order.order_id,
+                        order.remaining_quantity,
+                        order.queue_sequence,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Fills `quantity` of a resting order in place, keeping its queue
+    /// position. Iceberg orders trade their hidden reserve as well.
+    fn fill_auction_order(
+        &mut self,
+        order_id: OrderId,
+        quantity: Quantity,
+        price: Price,
+        liquidity: Liquidity,
+    ) -> Result<Fill, OrderBookError> {
+        let Some(&key) = self.order_keys.get(&order_id) else {
+            return Err(OrderBookError::OrderNotFound(order_id));
+        };
+        let order = &mut self.orders[key].order;
+
+        let visible_before = order.visible_quantity;
+        order.fill(quantity)?;
+        order.visible_quantity = visible_before.min(order.remaining_quantity);
+
+        let (fee, fee_asset) =
+            self.fee_schedule
+                .charge(&self.spec, &order.side, liquidity, price, quantity);
+        let fill = Fill {
+            order_id,
+            user_id: order.user_id,
+            side: order.side.clone(),
+            filled_price: price,
+            filled_quantity: quantity,
+            remaining_quantity: order.remaining_quantity,
+            liquidity,
+            fee,
+            fee_asset,
+        };
+        let visible_filled = visible_before - order.visible_quantity;
+        let order_price = order.price;
+        let expire_at = order.expire_at;
+
+        let levels = match fill.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        if let Some(level) = levels.get_mut(order_price) {
+            level.remove_order(quantity, visible_filled);
+            if fill.remaining_quantity == 0 {
+                level.unlink(key, &mut self.orders);
+            }
+            if level.is_empty() {
+                levels.remove(order_price);
+            }
+        }
+
+        if fill.remaining_quantity == 0 {
+            if let Some(expire_at) = expire_at {
+                self.expiries.remove(&(expire_at, order_id));
+            }
+            self.orders.remove(key);
+            self.order_keys.remove(&order_id);
+        }
+
+        Ok(fill)
+    }
+
+    /// Checks that can reject an order before it is acknowledged, without
+    /// touching the book. Post-only orders set to `PostOnly::Reprice` may have
+    /// their price moved behind the opposite best here, and market orders
+    /// with nothing within their limit to trade against are rejected.
+    pub(crate) fn pre_trade_check(
+        &self,
+        order: &mut OrderEntry,
+        order_type: &OrderType,
+        limit_price: Option<Price>,
+        now: i64,
+    ) -> Result<(), OrderBookError> {
+        if let Some(post_only) = order.post_only {
+            self.check_post_only(order, post_only)?;
+        }
+
+        if matches!(order_type, OrderType::Market | OrderType::StopMarket) {
+            let best = match order.side {
+                Side::Buy => self.get_best_ask(),
+                Side::Sell => self.get_best_bid(),
             };
 
-            while !level.is_empty() && taker_order.remaining_quantity > 0 {
-                let Some(&maker_order_id) = level.orders.front() else {
-                    break;
-                };
+            if !best.is_some_and(|price| crosses(&order.side, price, limit_price)) {
+                return Err(OrderBookError::InsufficientLiquidity(format!(
+                    "No liquidity available for market order {}",
+                    order.order_id
+                )));
+            }
+        }
+
+        if order.time_in_force == TimeInForce::FOK {
+            // Nothing trades past the band edge
+            let limit_price = match (self.band_limits(now), &order.side) {
+                (Some((_, high)), Side::Buy) => Some(limit_price.map_or(high, |p| p.min(high))),
+                (Some((low, _)), Side::Sell) => Some(limit_price.map_or(low, |p| p.max(low))),
+                (None, _) => limit_price,
+            };
+
+            let available = self.crossing_quantity(order, limit_price, order.remaining_quantity);
+
+            if available < order.remaining_quantity {
+                return Err(OrderBookError::InsufficientLiquidity(format!(
+                    "Fill-or-kill order {} cannot be fully filled ({} of {} available)",
+                    order.order_id, available, order.remaining_quantity
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_post_only(
+        &self,
+        order: &mut OrderEntry,
+        post_only: PostOnly,
+    ) -> Result<(), OrderBookError> {
+        if !self.would_cross(&order.side, order.price) {
+            return Ok(());
+        }
+
+        // Repricing never moves an order out of the instrument's price range
+        let repriced = match (post_only, &order.side) {
+            (PostOnly::Reject, _) => None,
+            (PostOnly::Reprice, Side::Buy) => self
+                .get_best_ask()
+                .and_then(|ask| ask.checked_sub(self.spec.tick_size)),
+            (PostOnly::Reprice, Side::Sell) => self
+                .get_best_bid()
+                .and_then(|bid| bid.checked_add(self.spec.tick_size)),
+        }
+        .filter(|&price| price > 0 && self.check_price(price).is_ok());
+
+        match repriced {
+            Some(price) => {
+                order.price = price;
+                Ok(())
+            }
+            None => Err(OrderBookError::PostOnlyWouldCross(format!(
+                "Order {} would take liquidity at {}",
+                order.order_id, order.price
+            ))),
+        }
+    }
+
+    /// Whether a limit order on `side` at `price` would trade on arrival.
+    #[inline]
+    fn would_cross(&self, side: &Side, price: Price) -> bool {
+        let best_opposite = match side {
+            Side::Buy => self.get_best_ask(),
+            Side::Sell => self.get_best_bid(),
+        };
+
+        best_opposite.is_some_and(|best| crosses(side, best, Some(price)))
+    }
+
+    /// Quantity resting on the opposite side that `order` could trade
+    /// against at `limit_price` (or any price if `None`). The user's own
+    /// orders never trade with it. Stops summing once `needed` is reached.
+    fn crossing_quantity(
+        &self,
+        order: &OrderEntry,
+        limit_price: Option<Price>,
+        needed: Quantity,
+    ) -> Quantity {
+        let stp_mode = order
+            .self_trade_prevention
+            .unwrap_or(self.self_trade_prevention);
+        let crossing = CrossingOrder {
+            order,
+            limit_price,
+            stp_mode,
+            matching_algorithm: self.matching_algorithm,
+        };
+
+        match order.side {
+            Side::Buy => crossing.sum_levels(self.asks.iter(), &self.orders, needed),
+            Side::Sell => crossing.sum_levels(self.bids.iter().rev(), &self.orders, needed),
+        }
+    }
 
-                let maker_order = match self.orders.get_mut(&maker_order_id) {
-                    Some(m) => m,
-                    None => {
-                        level.orders.pop_front();
-                        continue;
+    /// Walks the opposite side from the best price, filling `taker_order`
+    /// against resting orders until it is filled or no level crosses
+    /// `limit_price`. Within a level the book's matching algorithm decides
+    /// which orders trade and how much. Resting orders of the taker's own user are
+    /// resolved with self-trade prevention instead of trading. Reaching a level
+    /// outside the price band stops matching and starts a volatility
+    /// interruption. Returns whether the book was modified.
+    ///
+    /// Never fails, so callers that already took an order off the book can
+    /// match it without losing it.
+    fn match_against_book(
+        &mut self,
+        taker_order: &mut OrderEntry,
+        limit_price: Option<Price>,
+        now: i64,
+        result: &mut MatchResult,
+    ) -> bool {
+        let is_buy_order = matches!(taker_order.side, Side::Buy);
+        let stp_mode = taker_order
+            .self_trade_prevention
+            .unwrap_or(self.self_trade_prevention);
+        let band = self.band_limits(now);
+        let matching_algorithm = self.matching_algorithm;
+        let step_size = self.spec.step_size;
+
+        let levels = if is_buy_order {
+            &mut self.asks
+        } else {
+            &mut self.bids
+        };
+
+        let mut book_changed = false;
+        // Set once a buy with a quote budget cannot pay for another lot
+        let mut out_of_budget = false;
+
+        while taker_order.remaining_quantity > 0 && !out_of_budget {
+            let best_price = if is_buy_order {
+                levels.first_price()
+            } else {
+                levels.last_price()
+            };
+
+            let Some(price) = best_price else {
+                break;
+            };
+
+            if !crosses(&taker_order.side, price, limit_price) {
+                break;
+            }
+
+            if band.is_some_and(|(low, high)| price < low || price > high) {
+                result.volatility_interruption = true;
+                break;
+            }
+
+            let Some(level) = levels.get_mut(price) else {
+                break;
+            };
+
+            // Pro-rata shares of the level still to be traded, in queue order
+            let mut allocations = VecDeque::new();
+
+            while taker_order.remaining_quantity > 0 && !level.is_empty() {
+                let (maker_key, allocation) = match matching_algorithm {
+                    MatchingAlgorithm::Fifo => match level.front() {
+                        Some(maker_key) => (maker_key, Quantity::MAX),
+                        None => break,
+                    },
+                    MatchingAlgorithm::ProRata {
+                        top_order_priority,
+                        min_allocation,
+                    } => {
+                        if allocations.is_empty() {
+                            allocations = pro_rata_allocations(
+                                level,
+                                &self.orders,
+                                taker_order.remaining_quantity,
+                                top_order_priority,
+                                min_allocation,
+                                step_size,
+                            );
+                        }
+                        match allocations.pop_front() {
+                            Some(allocation) => allocation,
+                            None => break,
+                        }
                     }
                 };
 
+                let maker_order = &mut self.orders[maker_key].order;
+                let maker_order_id = maker_order.order_id;
+
+                // Icebergs only trade their current peak before requeueing
                 let fill_quantity = maker_order
-                    .remaining_quantity
-                    .min(taker_order.remaining_quantity);
+                    .visible_quantity
+                    .min(taker_order.remaining_quantity)
+                    .min(allocation);
+
+                // Buys with a quote budget only take the whole lots it still
+                // pays for
+                let fill_quantity = match taker_order.quote_budget {
+                    Some(budget) if is_buy_order => {
+                        let affordable = budget / price;
+                        let affordable = affordable - affordable % step_size.max(1);
+                        if affordable == 0 {
+                            out_of_budget = true;
+                            break;
+                        }
+                        fill_quantity.min(affordable)
+                    }
+                    _ => fill_quantity,
+                };
 
-                maker_order.fill(fill_quantity)?;
-                taker_order.fill(fill_quantity)?;
-                level.remove_order(fill_quantity);
+                if maker_order.user_id == taker_order.user_id {
+                    let (cancel_maker, cancel_taker) = match stp_mode {
+                        SelfTradePrevention::CancelNewest => (false, true),
+                        SelfTradePrevention::CancelOldest => (true, false),
+                        SelfTradePrevention::CancelBoth => (true, true),
+                        SelfTradePrevention::DecrementAndCancel => {
+                            // Nothing trades, both orders just get smaller.
+                            // Whichever reaches zero is cancelled below, the
+                            // other is reported with its new size.
+                            maker_order.decrement(fill_quantity);
+                            taker_order.decrement(fill_quantity);
+                            level.remove_order(fill_quantity, fill_quantity);
+                            let refilled = maker_order.refill(now);
+                            let maker_done = maker_order.remaining_quantity == 0;
+                            let taker_done = taker_order.remaining_quantity == 0;
+                            if !maker_done {
+                                result.decrements.push(maker_order.decremented());
+                            }
+                            if !taker_done {
+                                result.decrements.push(taker_order.decremented());
+                            }
+                            if refilled > 0 {
+                                maker_order.queue_sequence = self.next_queue_sequence;
+                                self.next_queue_sequence += 1;
+                                level.requeue(maker_key, &mut self.orders, refilled);
+                            }
+                            book_changed = true;
+                            (maker_done, taker_done)
+                        }
+                    };
+
+                    if cancel_maker {
+                        let maker_order = &self.orders[maker_key].order;
+                        level.remove_order(
+                            maker_order.remaining_quantity,
+                            maker_order.visible_quantity,
+                        );
+                        if let Some(expire_at) = maker_order.expire_at {
+                            self.expiries.remove(&(expire_at, maker_order_id));
+                        }
+                        result.cancels.push(Cancel {
+                            order_id: maker_order_id,
+                            user_id: maker_order.user_id,
+                            reason: CancelReason::SelfTradePrevention,
+                        });
+                        level.unlink(maker_key, &mut self.orders);
+                        self.orders.remove(maker_key);
+                        self.order_keys.remove(&maker_order_id);
+                        book_changed = true;
+                    }
+
+                    if cancel_taker {
+                        result.cancels.push(Cancel {
+                            order_id: taker_order.order_id,
+                            user_id: taker_order.user_id,
+                            reason: CancelReason::SelfTradePrevention,
+                        });
+                        // Nothing left to match, rest or cancel
+                        taker_order.remaining_quantity = 0;
+                    }
+
+                    continue;
+                }
+
+                maker_order.take(fill_quantity);
+                taker_order.take(fill_quantity);
+                level.remove_order(fill_quantity, fill_quantity);
+                if let Some(budget) = &mut taker_order.quote_budget {
+                    *budget = budget.saturating_sub(price.saturating_mul(fill_quantity));
+                }
+
+                let (maker_fee, maker_fee_asset) = self.fee_schedule.charge(
+                    &self.spec,
+                    &maker_order.side,
+                    Liquidity::Maker,
+                    price,
+                    fill_quantity,
+                );
+                let (taker_fee, taker_fee_asset) = self.fee_schedule.charge(
+                    &self.spec,
+                    &taker_order.side,
+                    Liquidity::Taker,
+                    price,
+                    fill_quantity,
+                );
+
+                result.trades.push(Trade {
+                    trade_id: self.next_trade_id,
+                    maker_order_id,
+                    maker_user_id: maker_order.user_id,
+                    taker_order_id: taker_order.order_id,
+                    taker_user_id: taker_order.user_id,
+                    quantity: fill_quantity,
+                    price,
+                    maker_fee,
+                    maker_fee_asset: maker_fee_asset.clone(),
+                    taker_fee,
+                    taker_fee_asset: taker_fee_asset.clone(),
+                    timestamp: now,
+                });
 
-                fills.push(Fill {
+                result.fills.push(Fill {
                     order_id: maker_order_id,
                     user_id: maker_order.user_id,
                     side: maker_order.side.clone(),
                     filled_price: price,
                     filled_quantity: fill_quantity,
                     remaining_quantity: maker_order.remaining_quantity,
+                    liquidity: Liquidity::Maker,
+                    fee: maker_fee,
+                    fee_asset: maker_fee_asset,
                 });
 
-                fills.push(Fill {
+                result.fills.push(Fill {
                     order_id: taker_order.order_id,
                     user_id: taker_order.user_id,
                     side: taker_order.side.clone(),
                     filled_price: price,
                     filled_quantity: fill_quantity,
                     remaining_quantity: taker_order.remaining_quantity,
+                    liquidity: Liquidity::Taker,
+                    fee: taker_fee,
+                    fee_asset: taker_fee_asset,
                 });
 
-                trades.push(Trade {
-                    trade_id: self.next_trade_id,
-                    maker_order_id: maker_order_id,
-                    maker_user_id: maker_order.user_id,
-                    taker_order_id: taker_order.order_id,
-                    taker_user_id: taker_order.user_id,
-                    quantity: fill_quantity,
-                    price,
-                    timestamp: Utc::now().timestamp_millis(),
-                });
+                if self.price_band.is_some() {
+                    self.recent_trades.push_back((now, price));
+                }
 
                 self.next_trade_id = self.next_trade_id.saturating_add(1);
-
+                self.last_price = Some(price);
                 book_changed = true;
 
                 if maker_order.remaining_quantity == 0 {
-                    level.orders.pop_front();
-                    self.orders.remove(&maker_order_id);
-                }
-
-                if level.is_empty() {
-                    prices_to_remove.insert(price);
+                    if let Some(expire_at) = maker_order.expire_at {
+                        self.expiries.remove(&(expire_at, maker_order_id));
+                    }
+                    level.unlink(maker_key, &mut self.orders);
+                    self.orders.remove(maker_key);
+                    self.order_keys.remove(&maker_order_id);
+                } else if maker_order.visible_quantity == 0 {
+                    let refilled = maker_order.refill(now);
+                    maker_order.queue_sequence = self.next_queue_sequence;
+                    self.next_queue_sequence += 1;
+                    level.requeue(maker_key, &mut self.orders, refilled);
                 }
             }
 
-            if taker_order.remaining_quantity == 0 {
-                break;
-            }
-
-            for price in &prices_to_remove {
+            if level.is_empty() {
                 levels.remove(price);
             }
         }
 
-        if taker_order.remaining_quantity == initial_remaining {
-            return Err(OrderBookError::InvalidOrder(
-                "Market order could not be filled - insufficient liquidity".to_string(),
-            ));
-        }
+        self.prune_recent_trades(now);
 
-        if book_changed {
-            self.depth_cache.is_latest = false;
+        if result.volatility_interruption {
+            self.start_interruption(now);
         }
 
-        let book_update = if book_changed {
-            Some(self.get_depth(20))
-        } else {
-            None
+        book_changed
+    }
+
+    /// Rests `order` at the back of its level. Never fails, so an order that
+    /// has already traded always finds its place.
+    #[inline]
+    pub(crate) fn add_order(&mut self, mut order: OrderEntry) {
+        order.visible_quantity = order.peak();
+        order.queue_sequence = self.next_queue_sequence;
+        self.next_queue_sequence += 1;
+
+        let order_id = order.order_id;
+        let price = order.price;
+        let side = order.side.clone();
+        let quantity = order.remaining_quantity;
+        let visible = order.visible_quantity;
+        let expire_at = order.expire_at;
+
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
         };
 
-        Ok(MatchResult {
-            fills,
-            trades,
-            book_update: book_update,
-        })
+        let level = levels.get_or_insert(price);
+
+        let key = self.orders.insert(OrderNode::new(order));
+        level.add_order(key, &mut self.orders, quantity, visible);
+        self.order_keys.insert(order_id, key);
+
+        if let Some(expire_at) = expire_at {
+            self.expiries.insert((expire_at, order_id));
+        }
+
+        self.depth_cache.is_latest = false;
     }
 
-    pub(crate) fn match_limit_order(
+    /// Changes the price and/or total quantity of a resting order. Reducing
+    /// the quantity at the same price keeps its queue priority. A new price
+    /// or a larger size sends it to the back of its level, matching first if
+    /// the new price crosses. Returns the amended order.
+    pub(crate) fn amend_order(
         &mut self,
-        taker_order: &mut OrderEntry,
-    ) -> Result<MatchResult, OrderBookError> {
-        let mut fills = Vec::<Fill>::new();
-        let mut trades = Vec::<Trade>::new();
+        order_id: OrderId,
+        price: Option<Price>,
+        quantity: Option<Quantity>,
+        now: i64,
+    ) -> Result<(OrderEntry, MatchResult), OrderBookError> {
+        let Some(current) = self.get_order(order_id) else {
+            if self.stop_orders.contains_key(&order_id) {
+                return Err(OrderBookError::InvalidOrder(
+                    "Stop orders cannot be amended".to_string(),
+                ));
+            }
+            return Err(OrderBookError::OrderNotFound(order_id));
+        };
 
-        let is_buy_order = matches!(taker_order.side, Side::Buy);
+        let filled = current.quantity - current.remaining_quantity;
+        let new_price = price.unwrap_or(current.price);
+        let new_quantity = quantity.unwrap_or(current.quantity);
 
-        let mut prices_to_remove = HashSet::<Price>::new();
+        if new_quantity <= filled {
+            return Err(OrderBookError::InvalidOrder(format!(
+                "New quantity must be greater than the filled quantity {}",
+                filled
+            )));
+        }
 
-        let levels = if is_buy_order {
-            &mut self.asks
-        } else {
-            &mut self.bids
-        };
+        let new_remaining = new_quantity - filled;
+        let mut result = MatchResult::default();
 
-        let price_keys: Vec<Price> = if is_buy_order {
-            levels
-                .range(..=taker_order.price)
-                .map(|(price, _)| *price)
-                .collect::<Vec<Price>>()
-        } else {
-            levels
-                .range(taker_order.price..)
-                .rev()
-                .map(|(price, _)| *price)
-                .collect::<Vec<Price>>()
+        if new_price == current.price && new_remaining <= current.remaining_quantity {
+            let Some(&key) = self.order_keys.get(&order_id) else {
+                return Err(OrderBookError::OrderNotFound(order_id));
+            };
+            let order = &mut self.orders[key].order;
+
+            let reduce_by = order.remaining_quantity - new_remaining;
+            let visible_before = order.visible_quantity;
+
+            order.quantity = new_quantity;
+            order.remaining_quantity = new_remaining;
+            order.visible_quantity = visible_before.min(new_remaining);
+
+            let levels = match order.side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+
+            if let Some(level) = levels.get_mut(new_price) {
+                level.remove_order(reduce_by, visible_before - order.visible_quantity);
+            }
+
+            let amended = order.clone();
+
+            if reduce_by > 0 {
+                self.depth_cache.is_latest = false;
+                result.book_update = Some(self.get_depth(20));
+            }
+
+            return Ok((amended, result));
+        }
+
+        let mut amended = current.clone();
+        amended.price = new_price;
+        amended.quantity = new_quantity;
+        amended.remaining_quantity = new_remaining;
+        amended.timestamp = now;
+
+        // Post-only and similar checks apply to the new price before the
+        // original order is touched. Matching cannot fail, so past this point
+        // the amend goes through.
+        self.pre_trade_check(&mut amended, &OrderType::Limit, Some(new_price), now)?;
+
+        self.remove_order(order_id)?;
+
+        result.merge(self.match_limit_order(&mut amended, now)?);
+        result.book_update = Some(self.get_depth(20));
+
+        Ok((amended, result))
+    }
+
+    /// Parks a stop order in the trigger book. It is activated by
+    /// `activate_stops` once the last trade price reaches its stop price.
+    /// Trailing stops have their stop price moved after every trade.
+    /// `order_type` is `StopMarket` or `StopLimit`, anything else activates
+    /// as a market order.
+    pub(crate) fn add_stop_order(
+        &mut self,
+        order: OrderEntry,
+        order_type: OrderType,
+        stop_price: Price,
+        trailing_offset: Option<TrailingOffset>,
+    ) {
+        let order_id = order.order_id;
+
+        match order.side {
+            Side::Buy => self.buy_stops.insert((stop_price, order_id)),
+            Side::Sell => self.sell_stops.insert((stop_price, order_id)),
         };
 
-        let mut book_changed = false;
+        if let Some(expire_at) = order.expire_at {
+            self.expiries.insert((expire_at, order_id));
+        }
+
+        if trailing_offset.is_some() {
+            self.trailing_stops.insert(order_id);
+        }
+
+        self.stop_orders.insert(
+            order_id,
+            StopOrder {
+                order,
+                order_type,
+                stop_price,
+                trailing_offset,
+            },
+        );
+    }
+
+    /// Activates every stop order triggered by the last trade price, feeding
+    /// them through the normal matching path. Trailing stops are moved after
+    /// each batch of trades in `result` before triggers are checked. Trades
+    /// made by an activated order can trigger further stops, which are
+    /// processed in the same call.
+    pub(crate) fn activate_stops(&mut self, result: &mut MatchResult, now: i64) {
+        let mut activated = false;
+        let mut trailed_trades = 0;
+
+        loop {
+            if result.trades.len() > trailed_trades {
+                self.trail_stops(&mut result.stop_updates);
+                trailed_trades = result.trades.len();
+            }
 
-        for price in price_keys {
-            if taker_order.remaining_quantity == 0 {
+            // Triggered stops wait for continuous trading to resume
+            if self.session_state != SessionState::Continuous {
                 break;
             }
 
-            let Some(level) = levels.get_mut(&price) else {
-                continue;
+            let Some(order_id) = self.next_triggered_stop() else {
+                break;
             };
 
-            // Before matching, lazily pop any dead/cancelled orders at front
-            // loop {
-            //     if let Some(&front_id) = level.orders.front() {
-            //         if let Some(front_entry) = self.orders.get(&front_id) {
-            //             if front_entry.remaining_quantity == 0 {
-            //                 level.orders.pop_front();
-            //                 continue;
-            //             }
-            //         } else {
-            //             level.orders.pop_front();
-            //             continue;
-            //         }
-            //     }
-            //     break;
-            // }
+            let Some(stop) = self.remove_stop_order(order_id) else {
+                break;
+            };
 
-            while taker_order.remaining_quantity > 0 && !level.is_empty() {
-                let Some(&maker_order_id) = level.orders.front() else {
-                    continue;
-                };
+            activated = true;
+            let mut order = stop.order;
 
-                let maker_order = match self.orders.get_mut(&maker_order_id) {
-                    Some(m) => m,
-                    None => {
-                        level.orders.pop_front();
-                        continue;
-                    }
-                };
+            let limit_price = match stop.order_type {
+                OrderType::StopLimit => Some(order.price),
+                _ => None,
+            };
 
-                let fill_quantity = maker_order
-                    .remaining_quantity
-                    .min(taker_order.remaining_quantity);
+            let outcome = self
+                .pre_trade_check(&mut order, &stop.order_type, limit_price, now)
+                .and_then(|_| match stop.order_type {
+                    OrderType::StopLimit => self.match_limit_order(&mut order, now),
+                    _ => self.match_market_order(&mut order, now),
+                });
 
-                maker_order.fill(fill_quantity)?;
-                taker_order.fill(fill_quantity)?;
-                level.remove_order(fill_quantity);
+            match outcome {
+                Ok(activation) => result.merge(activation),
+                // Triggered but rejected before anything was matched
+                Err(_) => result.cancels.push(Cancel {
+                    order_id: order.order_id,
+                    user_id: order.user_id,
+                    reason: CancelReason::StopRejected,
+                }),
+            }
+        }
 
-                fills.push(Fill {
-                    order_id: maker_order_id,
-                    user_id: maker_order.user_id,
-                    side: maker_order.side.clone(),
-                    filled_price: price,
-                    filled_quantity: fill_quantity,
-                    remaining_quantity: maker_order.remaining_quantity,
-                });
+        if activated {
+            result.book_update = Some(self.get_depth(20));
+        }
+    }
 
-                fills.push(Fill {
-                    order_id: taker_order.order_id,
-                    user_id: taker_order.user_id,
-                    side: taker_order.side.clone(),
-                    filled_price: price,
-                    filled_quantity: fill_quantity,
-                    remaining_quantity: taker_order.remaining_quantity,
-                });
+    /// Initial stop price for a trailing stop placed without one, trailing
+    /// the best bid for sells and the best ask for buys.
+    pub(crate) fn initial_trailing_stop_price(
+        &self,
+        side: &Side,
+        trailing_offset: TrailingOffset,
+    ) -> Option<Price> {
+        let best_price = match side {
+            Side::Buy => self.get_best_ask(),
+            Side::Sell => self.get_best_bid(),
+        }?;
+
+        trailing_stop_price(side, trailing_offset, best_price)
+    }
 
-                trades.push(Trade {
-                    trade_id: self.next_trade_id,
-                    maker_order_id: maker_order_id,
-                    maker_user_id: maker_order.user_id,
-                    taker_order_id: taker_order.order_id,
-                    taker_user_id: taker_order.user_id,
-                    quantity: fill_quantity,
-                    price: price,
-                    timestamp: Utc::now().timestamp_millis(),
-                });
+    /// Moves trailing stops after a batch of trades. Sell stops ratchet up
+    /// with the best bid and buy stops down with the best ask, which can
+    /// differ from the price the trades were made at.
+    fn trail_stops(&mut self, updates: &mut Vec<StopUpdate>) {
+        let (best_bid, best_ask) = (self.get_best_bid(), self.get_best_ask());
 
-                self.next_trade_id = self.next_trade_id.saturating_add(1);
-                book_changed = true;
+        for &order_id in &self.trailing_stops {
+            let Some(stop) = self.stop_orders.get_mut(&order_id) else {
+                continue;
+            };
 
-                if maker_order.remaining_quantity == 0 {
-                    level.orders.pop_front();
-                    self.orders.remove(&maker_order_id);
+            let best_price = match stop.order.side {
+                Side::Buy => best_ask,
+                Side::Sell => best_bid,
+            };
 
-                    if level.is_empty() {
-                        prices_to_remove.insert(price);
-                    }
-                }
+            let Some(stop_price) = best_price.and_then(|price| stop.trailed_stop_price(price))
+            else {
+                continue;
+            };
 
-                if taker_order.remaining_quantity == 0 {
-                    break;
+            let triggers = match stop.order.side {
+                Side::Buy => &mut self.buy_stops,
+                Side::Sell => &mut self.sell_stops,
+            };
+            triggers.remove(&(stop.stop_price, order_id));
+            triggers.insert((stop_price, order_id));
+
+            // Stop-limit orders keep their limit at the same distance, as
+            // near as the instrument's price grid allows
+            let price = match stop.order_type {
+                OrderType::StopLimit => {
+                    let limit = if stop_price > stop.stop_price {
+                        stop.order
+                            .price
+                            .saturating_add(stop_price - stop.stop_price)
+                    } else {
+                        stop.order
+                            .price
+                            .saturating_sub(stop.stop_price - stop_price)
+                    };
+                    stop.order.price = price_on_grid(&self.spec, limit);
+                    Some(stop.order.price)
                 }
-            }
-        }
+                _ => None,
+            };
 
-        for price in &prices_to_remove {
-            levels.remove(price);
-        }
+            stop.stop_price = stop_price;
 
-        if taker_order.remaining_quantity > 0 {
-            self.add_order(taker_order.clone())?;
-            book_changed = true;
+            updates.push(StopUpdate {
+                order_id,
+                user_id: stop.order.user_id,
+                stop_price,
+                price,
+            });
         }
+    }
 
-        if book_changed {
-            self.depth_cache.is_latest = false;
-        }
+    fn next_triggered_stop(&self) -> Option<OrderId> {
+        let last_price = self.last_price?;
 
-        let book_update = if book_changed {
-            Some(self.get_depth(20))
-        } else {
-            None
+        let buy = self
+            .buy_stops
+            .first()
+            .filter(|(stop_price, _)| last_price >= *stop_price);
+
+        let sell = self
+            .sell_stops
+            .last()
+            .filter(|(stop_price, _)| last_price <= *stop_price);
+
+        buy.or(sell).map(|(_, order_id)| *order_id)
+    }
+
+    fn remove_stop_order(&mut self, order_id: OrderId) -> Option<StopOrder> {
+        let stop = self.stop_orders.remove(&order_id)?;
+
+        match stop.order.side {
+            Side::Buy => self.buy_stops.remove(&(stop.stop_price, order_id)),
+            Side::Sell => self.sell_stops.remove(&(stop.stop_price, order_id)),
         };
 
-        Ok(MatchResult {
-            fills,
-            trades,
-            book_update: book_update,
-        })
+        if let Some(expire_at) = stop.order.expire_at {
+            self.expiries.remove(&(expire_at, order_id));
+        }
+
+        self.trailing_stops.remove(&order_id);
+
+        Some(stop)
     }
 
+    /// Removes a resting order from the book, or an untriggered stop order
+    /// from the trigger book.
     #[inline]
-    pub(crate) fn add_order(&mut self, order: OrderEntry) -> Result<(), OrderBookError> {
-        let order_id = order.order_id;
-        let price = order.price;
-        let side = order.side.clone();
-        let quantity = order.remaining_quantity;
+    pub(crate) fn remove_order(&mut self, order_id: OrderId) -> Result<OrderEntry, OrderBookError> {
+        let Some(key) = self.order_keys.remove(&order_id) else {
+            return match self.remove_stop_order(order_id) {
+                Some(stop) => Ok(stop.order),
+                None => Err(OrderBookError::OrderNotFound(order_id)),
+            };
+        };
+
+        let (price, side) = {
+            let order = &self.orders[key].order;
+            (order.price, order.side.clone())
+        };
 
-        self.orders.insert(order_id, order);
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
 
-        match side {
-            Side::Buy => {
-                if let Some(level) = self.bids.get_mut(&price) {
-                    level.add_order(order_id, quantity);
-                } else {
-                    let mut new_level = PriceLevel::new(price);
-                    new_level.add_order(order_id, quantity);
-                    self.bids.insert(price, new_level);
-                }
-            }
-            Side::Sell => {
-                if let Some(level) = self.asks.get_mut(&price) {
-                    level.add_order(order_id, quantity);
-                } else {
-                    let mut new_level = PriceLevel::new(price);
-                    new_level.add_order(order_id, quantity);
-                    self.asks.insert(price, new_level);
-                }
+        if let Some(level) = levels.get_mut(price) {
+            level.unlink(key, &mut self.orders);
+
+            let order = &self.orders[key].order;
+            level.remove_order(order.remaining_quantity, order.visible_quantity);
+
+            if level.is_empty() {
+                levels.remove(price);
             }
         }
 
+        let order = self.orders.remove(key).order;
+
+        if let Some(expire_at) = order.expire_at {
+            self.expiries.remove(&(expire_at, order_id));
+        }
+
         self.depth_cache.is_latest = false;
-        Ok(())
+
+        Ok(order)
     }
 
-    #[inline]
-    pub(crate) fn remove_order(&mut self, order_id: OrderId) -> Result<OrderEntry, OrderBookError> {
-        let Some(order) = self.orders.remove(&order_id) else {
-            return Err(OrderBookError::OrderNotFound(order_id));
+    /// Removes an order on behalf of `user_id`, refusing orders owned by someone else.
+    pub fn cancel_order(
+        &mut self,
+        order_id: OrderId,
+        user_id: UserId,
+    ) -> Result<OrderEntry, OrderBookError> {
+        let owner = match self.get_order(order_id) {
+            Some(order) => order.user_id,
+            None => match self.stop_orders.get(&order_id) {
+                Some(stop) => stop.order.user_id,
+                None => return Err(OrderBookError::OrderNotFound(order_id)),
+            },
         };
 
-        let price = order.price;
-        let side = order.side.clone();
-        let remaining_quantity = order.remaining_quantity;
+        if owner != user_id {
+            return Err(OrderBookError::NotOrderOwner(order_id));
+        }
 
-        match side {
-            Side::Buy => {
-                if let Some(level) = self.bids.get_mut(&price) {
-                    level.remove_order(remaining_quantity);
+        self.remove_order(order_id)
+    }
 
-                    if level.is_empty() {
-                        self.bids.remove(&price);
-                    }
-                }
+    /// Cancels all of a user's resting and stop orders, optionally only on one side.
+    pub(crate) fn cancel_user_orders(
+        &mut self,
+        user_id: UserId,
+        side: Option<&Side>,
+    ) -> MatchResult {
+        let mut result = MatchResult::default();
+
+        let matches = |order: &OrderEntry| {
+            order.user_id == user_id && side.is_none_or(|side| order.side == *side)
+        };
+
+        // Sorted so cancels come out in the order the orders were accepted
+        let mut order_ids: Vec<OrderId> = self
+            .orders
+            .iter()
+            .map(|(_, node)| &node.order)
+            .chain(self.stop_orders.values().map(|stop| &stop.order))
+            .filter(|order| matches(order))
+            .map(|order| order.order_id)
+            .collect();
+        order_ids.sort_unstable();
+
+        for order_id in order_ids {
+            if let Ok(order) = self.remove_order(order_id) {
+                result.cancels.push(Cancel {
+                    order_id: order.order_id,
+                    user_id: order.user_id,
+                    reason: CancelReason::UserRequested,
+                });
             }
-            Side::Sell => {
-                if let Some(level) = self.asks.get_mut(&price) {
-                    level.remove_order(remaining_quantity);
+        }
 
-                    if level.is_empty() {
-                        self.asks.remove(&price);
-                    }
+        if !result.cancels.is_empty() {
+            result.book_update = Some(self.get_depth(20));
+        }
+
+        result
+    }
+
+    /// Earliest expiry time of a resting order.
+    pub(crate) fn next_expiry(&self) -> Option<i64> {
+        self.expiries.first().map(|&(expire_at, _)| expire_at)
+    }
+
+    /// Cancels every resting order whose expiry is at or before `now`.
+    pub(crate) fn expire_orders(&mut self, now: i64) -> MatchResult {
+        let mut result = MatchResult::default();
+
+        while let Some(&(expire_at, order_id)) = self.expiries.first() {
+            if expire_at > now {
+                break;
+            }
+
+            match self.remove_order(order_id) {
+                Ok(order) => result.cancels.push(Cancel {
+                    order_id: order.order_id,
+                    user_id: order.user_id,
+                    reason: CancelReason::Expired,
+                }),
+                // Not resting anymore, drop the stale schedule entry
+                Err(_) => {
+                    self.expiries.remove(&(expire_at, order_id));
                 }
             }
         }
 
-        self.depth_cache.is_latest = false;
+        if !result.cancels.is_empty() {
+            result.book_update = Some(self.get_depth(20));
+        }
 
-        Ok(order)
+        result
     }
 
-    pub(crate) fn get_depth(&mut self, limit: usize) -> Depth {
+    pub fn get_depth(&mut self, limit: usize) -> Depth {
         if !self.depth_cache.is_latest {
             self.update_depth_cache();
         }
@@ -483,13 +2001,13 @@ impl OrderBook {
             self.bids
                 .iter()
                 .rev()
-                .map(|(price, level)| (*price, level.get_total_quantity())),
+                .map(|(price, level)| (price, level.get_visible_quantity())),
         );
 
         let asks = collect_to_fixed_array(
             self.asks
                 .iter()
-                .map(|(price, level)| (*price, level.get_total_quantity())),
+                .map(|(price, level)| (price, level.get_visible_quantity())),
         );
 
         self.depth_cache = CachedDepth {
@@ -501,22 +2019,24 @@ impl OrderBook {
 
     #[inline]
     pub(crate) fn get_best_bid(&self) -> Option<Price> {
-        self.bids.keys().next_back().copied()
+        self.bids.last_price()
     }
 
     #[inline]
     pub(crate) fn get_best_ask(&self) -> Option<Price> {
-        self.asks.keys().next().copied()
+        self.asks.first_price()
     }
 
     #[inline]
     pub(crate) fn get_order(&self, order_id: OrderId) -> Option<&OrderEntry> {
-        self.orders.get(&order_id)
+        let &key = self.order_keys.get(&order_id)?;
+        Some(&self.orders[key].order)
     }
 
     #[inline]
     pub(crate) fn get_order_mut(&mut self, order_id: OrderId) -> Option<&mut OrderEntry> {
-        self.orders.get_mut(&order_id)
+        let &key = self.order_keys.get(&order_id)?;
+        Some(&mut self.orders[key].order)
     }
 
     #[inline(always)]
@@ -527,22 +2047,24 @@ impl OrderBook {
     #[inline]
     pub(crate) fn get_next_matchable_order(&self, side: Side) -> Option<OrderId> {
         match side {
-            Side::Buy => self.get_best_ask().and_then(|price| {
-                self.asks
-                    .get(&price)
-                    .and_then(|level| level.orders.front().copied())
-            }),
-            Side::Sell => self.get_best_bid().and_then(|price| {
-                self.bids
-                    .get(&price)
-                    .and_then(|level| level.orders.front().copied())
-            }),
+            Side::Buy => self
+                .asks
+                .iter()
+                .next()
+                .and_then(|(_, level)| level.front().map(|key| self.orders[key].order.order_id)),
+            Side::Sell => self
+                .bids
+                .iter()
+                .next_back()
+                .and_then(|(_, level)| level.front().map(|key| self.orders[key].order.order_id)),
         }
     }
 
     pub(crate) fn update_order_quantity(&mut self, order_id: OrderId, filled_qty: Quantity) {
-        if let Some(order) = self.orders.get_mut(&order_id) {
+        if let Some(&key) = self.order_keys.get(&order_id) {
+            let order = &mut self.orders[key].order;
             let remaining_before_fill = order.remaining_quantity;
+            let visible_before_fill = order.visible_quantity;
 
             if let Err(e) = order.fill(filled_qty) {
                 eprintln!("[OrderBook] Failed to fill order {}: {}", order_id, e);
@@ -550,23 +2072,26 @@ impl OrderBook {
             }
 
             let price = order.price;
+            let visible_filled = visible_before_fill - order.visible_quantity;
 
             match order.side {
                 Side::Buy => {
-                    if let Some(level) = self.bids.get_mut(&price) {
+                    if let Some(level) = self.bids.get_mut(price) {
                         if remaining_before_fill == filled_qty {
-                            level.remove_order(filled_qty);
+                            level.remove_order(filled_qty, visible_filled);
                         } else {
                             level.total_quantity -= filled_qty;
+                            level.visible_quantity -= visible_filled;
                         }
                     }
                 }
                 Side::Sell => {
-                    if let Some(level) = self.asks.get_mut(&price) {
+                    if let Some(level) = self.asks.get_mut(price) {
                         if remaining_before_fill == filled_qty {
-                            level.remove_order(filled_qty);
+                            level.remove_order(filled_qty, visible_filled);
                         } else {
                             level.total_quantity -= filled_qty;
+                            level.visible_quantity -= visible_filled;
                         }
                     }
                 }
@@ -585,3 +2110,173 @@ where
     }
     arr
 }
+
+/// Stop price trailing `reference_price` by `offset`: below it for sell
+/// stops, above it for buy stops. `None` if it would not be a valid price.
+pub(crate) fn trailing_stop_price(
+    side: &Side,
+    offset: TrailingOffset,
+    reference_price: Price,
+) -> Option<Price> {
+    let distance = match offset {
+        TrailingOffset::Absolute(amount) => amount,
+        TrailingOffset::Percentage(bps) => (reference_price as u128 * bps as u128 / 10_000)
+            .try_into()
+            .ok()?,
+    };
+
+    match side {
+        Side::Buy => reference_price.checked_add(distance),
+        Side::Sell => reference_price
+            .checked_sub(distance)
+            .filter(|&price| price > 0),
+    }
+}
+
+/// `price` rounded down to the tick grid of `spec` and kept within its price
+/// range.
+fn price_on_grid(spec: &InstrumentSpec, price: Price) -> Price {
+    let tick_size = spec.tick_size.max(1);
+    let highest = spec.max_price / tick_size * tick_size;
+    let lowest = spec
+        .min_price
+        .max(1)
+        .checked_next_multiple_of(tick_size)
+        .unwrap_or(highest);
+
+    (price / tick_size * tick_size).min(highest).max(lowest)
+}
+
+/// Whether an order on `side` limited at `limit_price` (`None` for market
+/// orders) can trade against a resting order at `price`.
+#[inline]
+pub(crate) fn crosses(side: &Side, price: Price, limit_price: Option<Price>) -> bool {
+    match (side, limit_price) {
+        (_, None) => true,
+        (Side::Buy, Some(limit)) => price <= limit,
+        (Side::Sell, Some(limit)) => price >= limit,
+    }
+}
+
+/// An order walking the opposite side to see how much of it would fill.
+struct CrossingOrder<'a> {
+    order: &'a OrderEntry,
+    limit_price: Option<Price>,
+    stp_mode: SelfTradePrevention,
+    matching_algorithm: MatchingAlgorithm,
+}
+
+impl CrossingOrder<'_> {
+    /// Quantity of `levels`, best first, the order would trade before it is
+    /// filled or stopped. Under `CancelOldest` the user's own orders are
+    /// cancelled and skipped. Under the other modes matching stops at the
+    /// first of them, so only what is visible ahead of it in a FIFO queue
+    /// counts, and nothing at that level under pro-rata.
+    fn sum_levels<'b, I>(&self, levels: I, orders: &Slab<OrderNode>, needed: Quantity) -> Quantity
+    where
+        I: Iterator<Item = (Price, &'b PriceLevel)>,
+    {
+        let user_id = self.order.user_id;
+        let mut available: Quantity = 0;
+
+        for (price, level) in levels {
+            if !crosses(&self.order.side, price, self.limit_price) || available >= needed {
+                break;
+            }
+
+            let own = level
+                .iter(orders)
+                .position(|(_, maker)| maker.user_id == user_id);
+
+            let Some(ahead) = own else {
+                available = available.saturating_add(level.get_total_quantity());
+                continue;
+            };
+
+            if self.stp_mode == SelfTradePrevention::CancelOldest {
+                let others: Quantity = level
+                    .iter(orders)
+                    .filter(|(_, maker)| maker.user_id != user_id)
+                    .map(|(_, maker)| maker.remaining_quantity)
+                    .sum();
+                available = available.saturating_add(others);
+                continue;
+            }
+
+            if self.matching_algorithm == MatchingAlgorithm::Fifo {
+                let visible: Quantity = level
+                    .iter(orders)
+                    .take(ahead)
+                    .map(|(_, maker)| maker.visible_quantity)
+                    .sum();
+                available = available.saturating_add(visible);
+            }
+            break;
+        }
+
+        available
+    }
+}
+
+/// Shares `quantity` among the orders queued at `level` for pro-rata
+/// matching and returns each order's allocation in queue order. Shares are
+/// proportional to visible size, so iceberg reserve does not count.
+fn pro_rata_allocations(
+    level: &PriceLevel,
+    orders: &Slab<OrderNode>,
+    quantity: Quantity,
+    top_order_priority: bool,
+    min_allocation: Quantity,
+    step_size: Quantity,
+) -> VecDeque<(OrderKey, Quantity)> {
+    let queue: Vec<(OrderKey, Quantity)> = level
+        .iter(orders)
+        .filter(|(_, order)| order.visible_quantity > 0)
+        .map(|(key, order)| (key, order.visible_quantity))
+        .collect();
+
+    let mut allocated = vec![0; queue.len()];
+    let mut left = quantity;
+
+    if top_order_priority && let Some(&(_, visible)) = queue.first() {
+        allocated[0] = visible.min(left);
+        left -= allocated[0];
+    }
+
+    let open: u128 = queue
+        .iter()
+        .zip(&allocated)
+        .map(|(&(_, visible), &allocated)| (visible - allocated) as u128)
+        .sum();
+
+    if left > 0 && open > 0 {
+        let pool = left as u128;
+        for (&(_, visible), allocated) in queue.iter().zip(allocated.iter_mut()) {
+            let remaining = visible - *allocated;
+            let share = (pool * remaining as u128 / open) as Quantity;
+            let share = (share - share % step_size.max(1)).min(remaining);
+
+            if share > 0 && share >= min_allocation {
+                *allocated += share;
+                left -= share;
+            }
+        }
+    }
+
+    // Rounding leftovers and dropped shares go in time priority
+    for (&(_, visible), allocated) in queue.iter().zip(allocated.iter_mut()) {
+        if left == 0 {
+            break;
+        }
+        let extra = (visible - *allocated).min(left);
+        *allocated += extra;
+        left -= extra;
+    }
+
+    queue
+        .into_iter()
+        .zip(allocated)
+        .filter(|&(_, allocated)| allocated > 0)
+        .map(|((key, _), allocated)| (key, allocated))
+        .collect()
+}
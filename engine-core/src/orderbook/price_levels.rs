@@ -1,12 +1,46 @@
-use std::collections::VecDeque;
+use protocol::types::{Price, Quantity};
+use serde::{Deserialize, Serialize};
+use slab::Slab;
 
-use protocol::types::{OrderId, Price, Quantity};
+use crate::orderbook::orderbook::OrderEntry;
 
-#[derive(Debug, Clone, Default)]
+/// Slot of a resting order in the book's order arena.
+pub(crate) type OrderKey = usize;
+
+/// A resting order and its neighbours in the queue of its price level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct OrderNode {
+    pub(crate) order: OrderEntry,
+    prev: Option<OrderKey>,
+    next: Option<OrderKey>,
+}
+
+impl OrderNode {
+    #[inline]
+    pub(crate) fn new(order: OrderEntry) -> Self {
+        Self {
+            order,
+            prev: None,
+            next: None,
+        }
+    }
+}
+
+/// Orders resting at one price in time priority. `total_quantity` includes
+/// hidden iceberg reserve and is what can be matched, `visible_quantity` is
+/// what gets published in depth.
+///
+/// The queue is a doubly linked list threaded through the book's order
+/// arena, so orders join, leave from anywhere and move to the back in O(1).
+/// Every method that touches the queue takes that arena.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PriceLevel {
     pub(crate) price: Price,
-    pub(crate) orders: VecDeque<OrderId>,
+    head: Option<OrderKey>,
+    tail: Option<OrderKey>,
+    len: usize,
     pub(crate) total_quantity: Quantity,
+    pub(crate) visible_quantity: Quantity,
 }
 
 impl PriceLevel {
@@ -14,20 +48,103 @@ impl PriceLevel {
     pub(crate) fn new(price: Price) -> Self {
         Self {
             price,
-            orders: VecDeque::new(),
+            head: None,
+            tail: None,
+            len: 0,
             total_quantity: 0,
+            visible_quantity: 0,
         }
     }
 
+    /// Queues the order at `key` last, counting `quantity` and `visible`
+    /// towards the level.
     #[inline]
-    pub(crate) fn add_order(&mut self, order_id: OrderId, quantity: Quantity) {
-        self.orders.push_back(order_id);
+    pub(crate) fn add_order(
+        &mut self,
+        key: OrderKey,
+        nodes: &mut Slab<OrderNode>,
+        quantity: Quantity,
+        visible: Quantity,
+    ) {
+        self.push_back(key, nodes);
         self.total_quantity = self.total_quantity.saturating_add(quantity);
+        self.visible_quantity = self.visible_quantity.saturating_add(visible);
     }
 
     #[inline]
-    pub(crate) fn remove_order(&mut self, quantity: Quantity) {
+    pub(crate) fn remove_order(&mut self, quantity: Quantity, visible: Quantity) {
         self.total_quantity = self.total_quantity.saturating_sub(quantity);
+        self.visible_quantity = self.visible_quantity.saturating_sub(visible);
+    }
+
+    /// Sends the order at `key` to the back of the queue after its iceberg
+    /// peak was refilled with `visible` from the hidden reserve.
+    #[inline]
+    pub(crate) fn requeue(
+        &mut self,
+        key: OrderKey,
+        nodes: &mut Slab<OrderNode>,
+        visible: Quantity,
+    ) {
+        self.unlink(key, nodes);
+        self.push_back(key, nodes);
+        self.visible_quantity = self.visible_quantity.saturating_add(visible);
+    }
+
+    /// Takes the order at `key` out of the queue. Its quantity is left to
+    /// `remove_order`.
+    #[inline]
+    pub(crate) fn unlink(&mut self, key: OrderKey, nodes: &mut Slab<OrderNode>) {
+        let (prev, next) = {
+            let node = &mut nodes[key];
+            (node.prev.take(), node.next.take())
+        };
+
+        match prev {
+            Some(prev) => nodes[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => nodes[next].prev = prev,
+            None => self.tail = prev,
+        }
+
+        self.len -= 1;
+    }
+
+    #[inline]
+    fn push_back(&mut self, key: OrderKey, nodes: &mut Slab<OrderNode>) {
+        nodes[key].prev = self.tail;
+        nodes[key].next = None;
+
+        match self.tail {
+            Some(tail) => nodes[tail].next = Some(key),
+            None => self.head = Some(key),
+        }
+
+        self.tail = Some(key);
+        self.len += 1;
+    }
+
+    /// Oldest order in the queue.
+    #[inline]
+    pub(crate) fn front(&self) -> Option<OrderKey> {
+        self.head
+    }
+
+    /// Orders in queue order with their arena keys.
+    pub(crate) fn iter<'a>(
+        &self,
+        nodes: &'a Slab<OrderNode>,
+    ) -> impl Iterator<Item = (OrderKey, &'a OrderEntry)> + 'a {
+        let mut cursor = self.head;
+
+        std::iter::from_fn(move || {
+            let key = cursor?;
+            let node = &nodes[key];
+            cursor = node.next;
+            Some((key, &node.order))
+        })
     }
 
     #[inline]
@@ -36,8 +153,8 @@ impl PriceLevel {
     }
 
     #[inline]
-    pub(crate) fn get_orders(&self) -> &VecDeque<OrderId> {
-        &self.orders
+    pub(crate) fn get_visible_quantity(&self) -> Quantity {
+        self.visible_quantity
     }
 
     #[inline]
@@ -47,11 +164,11 @@ impl PriceLevel {
 
     #[inline]
     pub(crate) fn get_orders_count(&self) -> usize {
-        self.orders.len()
+        self.len
     }
 
     #[inline]
     pub(crate) fn is_empty(&self) -> bool {
-        self.total_quantity == 0 || self.orders.is_empty()
+        self.head.is_none()
     }
 }
@@ -1,4 +1,7 @@
-use protocol::types::{OrderId, Price, Quantity, Side, UserId};
+use protocol::types::{
+    CancelReason, InstrumentSpec, Liquidity, OrderId, Price, Quantity, Side, UserId,
+};
+use serde::{Deserialize, Serialize};
 
 pub const CACHE_LIMIT: usize = 25;
 
@@ -11,6 +14,10 @@ pub(crate) struct Trade {
     pub(crate) taker_user_id: UserId,
     pub(crate) quantity: Quantity,
     pub(crate) price: Price,
+    pub(crate) maker_fee: i64,
+    pub(crate) maker_fee_asset: String,
+    pub(crate) taker_fee: i64,
+    pub(crate) taker_fee_asset: String,
     pub(crate) timestamp: i64,
 }
 
@@ -26,7 +33,12 @@ impl Trade {
             taker_user_id: self.taker_user_id,
             quantity: self.quantity,
             price: self.price,
+            maker_fee: self.maker_fee,
+            maker_fee_asset: self.maker_fee_asset,
+            taker_fee: self.taker_fee,
+            taker_fee_asset: self.taker_fee_asset,
             timestamp: self.timestamp,
+            sequence: 0,
         }
     }
 }
@@ -39,6 +51,9 @@ pub(crate) struct Fill {
     pub(crate) filled_price: Price,
     pub(crate) filled_quantity: Quantity,
     pub(crate) remaining_quantity: Quantity,
+    pub(crate) liquidity: Liquidity,
+    pub(crate) fee: i64,
+    pub(crate) fee_asset: String,
 }
 
 impl Fill {
@@ -52,6 +67,188 @@ impl Fill {
             filled_price: self.filled_price,
             filled_quantity: self.filled_quantity,
             remaining_quantity: self.remaining_quantity,
+            liquidity: self.liquidity,
+            fee: self.fee,
+            fee_asset: self.fee_asset,
+            sequence: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Cancel {
+    pub(crate) order_id: OrderId,
+    pub(crate) user_id: UserId,
+    pub(crate) reason: CancelReason,
+}
+
+impl Cancel {
+    #[inline]
+    pub(crate) fn into_protocol(self, symbol: &str) -> protocol::types::OrderCancelled {
+        protocol::types::OrderCancelled {
+            order_id: self.order_id,
+            user_id: self.user_id,
+            symbol: symbol.to_string(),
+            reason: self.reason,
+            sequence: 0,
+        }
+    }
+}
+
+/// A resting order made smaller without trading, by decrement-and-cancel
+/// self-trade prevention.
+#[derive(Debug, Clone)]
+pub(crate) struct Decrement {
+    pub(crate) order_id: OrderId,
+    pub(crate) user_id: UserId,
+    pub(crate) side: Side,
+    pub(crate) price: Price,
+    pub(crate) quantity: Quantity,
+    pub(crate) remaining_quantity: Quantity,
+}
+
+impl Decrement {
+    #[inline]
+    pub(crate) fn into_protocol(self, symbol: &str) -> protocol::types::OrderAmended {
+        protocol::types::OrderAmended {
+            order_id: self.order_id,
+            user_id: self.user_id,
+            symbol: symbol.to_string(),
+            price: self.price,
+            quantity: self.quantity,
+            remaining_quantity: self.remaining_quantity,
+            sequence: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct StopUpdate {
+    pub(crate) order_id: OrderId,
+    pub(crate) user_id: UserId,
+    pub(crate) stop_price: Price,
+    pub(crate) price: Option<Price>,
+}
+
+impl StopUpdate {
+    #[inline]
+    pub(crate) fn into_protocol(self, symbol: &str) -> protocol::types::StopPriceUpdated {
+        protocol::types::StopPriceUpdated {
+            order_id: self.order_id,
+            user_id: self.user_id,
+            symbol: symbol.to_string(),
+            stop_price: self.stop_price,
+            price: self.price,
+            sequence: 0,
+        }
+    }
+}
+
+/// Dynamic limit-up/limit-down band. Trades must stay within `width_bps` of
+/// the mean trade price over the last `window_ms`, or of the last trade price
+/// when nothing traded in the window. Matching that would leave the band
+/// pauses the book in an auction call for `interruption_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PriceBand {
+    pub(crate) width_bps: u64,
+    pub(crate) window_ms: i64,
+    pub(crate) interruption_ms: i64,
+}
+
+/// Fees charged on the fills of a symbol, in basis points of what the order
+/// receives: base for buys, quote for sells. A negative maker fee pays
+/// resting orders a rebate. Rates are capped at 10,000 either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker_fee_bps: i64,
+    pub taker_fee_bps: i64,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_fee_bps: i64, taker_fee_bps: i64) -> Self {
+        Self {
+            maker_fee_bps,
+            taker_fee_bps,
+        }
+    }
+
+    /// Fee of an order on `side` filling `quantity` at `price`, and the asset
+    /// it is charged in. Fees round up and rebates round down.
+    pub(crate) fn charge(
+        &self,
+        spec: &InstrumentSpec,
+        side: &Side,
+        liquidity: Liquidity,
+        price: Price,
+        quantity: Quantity,
+    ) -> (i64, String) {
+        let rate = match liquidity {
+            Liquidity::Maker => self.maker_fee_bps,
+            Liquidity::Taker => self.taker_fee_bps,
+        }
+        .clamp(-10_000, 10_000);
+
+        let (received, asset) = match side {
+            Side::Buy => (quantity, &spec.base),
+            Side::Sell => (price.saturating_mul(quantity), &spec.quote),
+        };
+
+        let fee = i128::from(received) * i128::from(rate);
+        let fee = if fee > 0 {
+            (fee + 9_999) / 10_000
+        } else {
+            fee / 10_000
+        };
+
+        let fee = fee.clamp(i64::MIN.into(), i64::MAX.into()) as i64;
+
+        (fee, asset.clone())
+    }
+}
+
+/// How an incoming order is shared among the orders resting at the price it
+/// trades at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MatchingAlgorithm {
+    /// Price-time priority: the oldest order at a level fills first.
+    #[default]
+    Fifo,
+    /// Each order gets a share of the incoming quantity in proportion to its
+    /// visible size, rounded down to the lot size. With
+    /// `top_order_priority` the oldest order at the level is filled in full
+    /// before the rest is shared out. Shares under `min_allocation` are
+    /// dropped, and whatever rounding and dropped shares leave over is filled
+    /// in time priority.
+    ProRata {
+        top_order_priority: bool,
+        min_allocation: Quantity,
+    },
+}
+
+/// Outcome of uncrossing the book at `price`: the volume that would trade and
+/// what would be left unmatched on the heavier side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AuctionPrice {
+    pub(crate) price: Price,
+    pub(crate) volume: Quantity,
+    pub(crate) imbalance: Quantity,
+    pub(crate) imbalance_side: Option<Side>,
+}
+
+impl AuctionPrice {
+    #[inline]
+    pub(crate) fn new(price: Price, demand: Quantity, supply: Quantity) -> Self {
+        let imbalance_side = match demand.cmp(&supply) {
+            std::cmp::Ordering::Greater => Some(Side::Buy),
+            std::cmp::Ordering::Less => Some(Side::Sell),
+            std::cmp::Ordering::Equal => None,
+        };
+
+        Self {
+            price,
+            volume: demand.min(supply),
+            imbalance: demand.abs_diff(supply),
+            imbalance_side,
         }
     }
 }
@@ -82,6 +279,7 @@ impl Depth {
             bids,
             asks,
             last_price: None,
+            sequence: 0,
         }
     }
 }
@@ -102,9 +300,32 @@ impl CachedDepth {
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct MatchResult {
+/// Everything matching one order did to the book.
+#[derive(Debug, Clone, Default)]
+pub struct MatchResult {
     pub(crate) fills: Vec<Fill>,
     pub(crate) trades: Vec<Trade>,
+    pub(crate) cancels: Vec<Cancel>,
+    pub(crate) decrements: Vec<Decrement>,
+    pub(crate) stop_updates: Vec<StopUpdate>,
     pub(crate) book_update: Option<Depth>,
+    // Matching stopped at the price band and the book entered an auction call
+    pub(crate) volatility_interruption: bool,
+}
+
+impl MatchResult {
+    /// Appends the events of `other`, keeping the most recent book update.
+    #[inline]
+    pub(crate) fn merge(&mut self, other: MatchResult) {
+        self.fills.extend(other.fills);
+        self.trades.extend(other.trades);
+        self.cancels.extend(other.cancels);
+        self.decrements.extend(other.decrements);
+        self.stop_updates.extend(other.stop_updates);
+        self.volatility_interruption |= other.volatility_interruption;
+
+        if other.book_update.is_some() {
+            self.book_update = other.book_update;
+        }
+    }
 }
@@ -0,0 +1,38 @@
+use std::cell::Cell;
+
+use crossbeam_channel::{SendError, Sender};
+use protocol::types::Event;
+
+/// Event channel that stamps every event with the engine's next sequence
+/// number as it is sent.
+///
+/// A number is used up even when the receiver is gone, so the sequence only
+/// depends on the events produced, never on who listens to them.
+#[derive(Debug)]
+pub(crate) struct EventSender {
+    tx: Sender<Event>,
+    next_sequence: Cell<u64>,
+}
+
+impl EventSender {
+    pub(crate) fn new(tx: Sender<Event>, next_sequence: u64) -> Self {
+        Self {
+            tx,
+            next_sequence: Cell::new(next_sequence),
+        }
+    }
+
+    // Hands back the event on failure, as `Sender::send` does
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn send(&self, mut event: Event) -> Result<(), SendError<Event>> {
+        let sequence = self.next_sequence.get();
+        self.next_sequence.set(sequence + 1);
+        event.set_sequence(sequence);
+        self.tx.send(event)
+    }
+
+    /// Sequence the next event will get.
+    pub(crate) fn next_sequence(&self) -> u64 {
+        self.next_sequence.get()
+    }
+}
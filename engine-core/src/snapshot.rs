@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use protocol::types::{OrderId, Symbol};
+use serde::{Deserialize, Serialize};
+
+use crate::error::SnapshotError;
+use crate::ledger::Ledger;
+use crate::orderbook::orderbook::OrderBook;
+
+/// How often the engine writes a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotInterval {
+    /// After this many state-changing commands.
+    Commands(u64),
+    /// Once this much time has passed, checked on the expiry tick.
+    Every(Duration),
+}
+
+/// Engine state after the first `journal_entries` entries of the journal:
+/// every book with its resting orders in queue order, stop orders and
+/// trade id counter, plus the ledger and the next event sequence.
+///
+/// On disk a snapshot is the CRC-32 of its body as 8 hex digits on the first
+/// line, followed by the body as JSON.
+#[derive(Debug, Deserialize)]
+pub struct Snapshot {
+    pub(crate) journal_entries: u64,
+    pub(crate) last_order_id: OrderId,
+    pub(crate) next_sequence: u64,
+    pub(crate) orderbooks: BTreeMap<Symbol, OrderBook>,
+    pub(crate) ledger: Option<Ledger>,
+}
+
+// Borrowed twin of `Snapshot` so the books are not cloned to be written
+#[derive(Serialize)]
+pub(crate) struct SnapshotRef<'a> {
+    pub(crate) journal_entries: u64,
+    pub(crate) last_order_id: OrderId,
+    pub(crate) next_sequence: u64,
+    pub(crate) orderbooks: &'a BTreeMap<Symbol, OrderBook>,
+    pub(crate) ledger: &'a Option<Ledger>,
+}
+
+impl Snapshot {
+    /// Journal entries already applied to this snapshot; replay only the
+    /// ones after them.
+    pub fn journal_entries(&self) -> u64 {
+        self.journal_entries
+    }
+
+    /// Reads the snapshot at `path`, `None` if there is none. The body is
+    /// only parsed once its checksum matches.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, SnapshotError> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let Some(split) = contents.iter().position(|&byte| byte == b'\n') else {
+            return Err(SnapshotError::Malformed(
+                "missing checksum line".to_string(),
+            ));
+        };
+        let (header, body) = (&contents[..split], &contents[split + 1..]);
+
+        let expected = std::str::from_utf8(header)
+            .ok()
+            .and_then(|header| u32::from_str_radix(header, 16).ok())
+            .ok_or_else(|| SnapshotError::Malformed("invalid checksum line".to_string()))?;
+        let actual = crc32fast::hash(body);
+
+        if expected != actual {
+            return Err(SnapshotError::ChecksumMismatch { expected, actual });
+        }
+
+        serde_json::from_slice(body)
+            .map(Some)
+            .map_err(|e| SnapshotError::Malformed(e.to_string()))
+    }
+}
+
+impl SnapshotRef<'_> {
+    /// Writes the snapshot next to `path` and moves it into place, so a
+    /// crash while writing leaves the previous snapshot intact.
+    pub(crate) fn write(&self, path: &Path) -> Result<(), SnapshotError> {
+        let body = serde_json::to_vec(self).map_err(|e| SnapshotError::Malformed(e.to_string()))?;
+
+        let mut contents = format!("{:08x}\n", crc32fast::hash(&body)).into_bytes();
+        contents.extend_from_slice(&body);
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Where the engine writes snapshots and when the next one is due.
+#[derive(Debug)]
+pub(crate) struct SnapshotSchedule {
+    pub(crate) path: PathBuf,
+    interval: SnapshotInterval,
+    commands: u64,
+    last: Instant,
+}
+
+impl SnapshotSchedule {
+    pub(crate) fn new(path: PathBuf, interval: SnapshotInterval) -> Self {
+        Self {
+            path,
+            interval,
+            commands: 0,
+            last: Instant::now(),
+        }
+    }
+
+    pub(crate) fn record_command(&mut self) {
+        self.commands += 1;
+    }
+
+    /// Whether a snapshot is due. Nothing is due while no command changed
+    /// the state since the last one.
+    pub(crate) fn is_due(&self) -> bool {
+        match self.interval {
+            SnapshotInterval::Commands(commands) => self.commands >= commands.max(1),
+            SnapshotInterval::Every(every) => self.commands > 0 && self.last.elapsed() >= every,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.commands = 0;
+        self.last = Instant::now();
+    }
+}
@@ -1,9 +1,21 @@
-use crate::engine::Engine;
+use crate::clock::ManualClock;
+use crate::engine::{Engine, next_session_close};
+use crate::error::{JournalError, OrderBookError, SnapshotError};
+use crate::journal::Journal;
+use crate::ledger::{Balance, Ledger};
+use crate::orderbook::orderbook::{OrderBook, OrderEntry, trailing_stop_price};
+use crate::orderbook::types::{FeeSchedule, MatchResult, MatchingAlgorithm};
+use crate::snapshot::{Snapshot, SnapshotInterval};
 use crossbeam_channel;
-use net::http::models::orders::{CommandResponse, OrderResponse};
+use net::http::models::orders::{
+    AmendOrderResponse, CancelOrderResponse, CommandResponse, DepositResponse, MassCancelResponse,
+    OrderResponse, SessionStateResponse,
+};
 use oneshot;
 use protocol::types::{
-    CancelOrder, CancelReason, Event, Order, OrderCommand, OrderType, RejectReason, Side,
+    AmendOrder, CancelOrder, CancelReason, Deposit, Event, Fill, GetDepth, InstrumentSpec,
+    Liquidity, MassCancel, Order, OrderCommand, OrderType, PostOnly, RejectReason,
+    SelfTradePrevention, SessionState, SetSessionState, Side, TimeInForce, TrailingOffset,
 };
 
 #[cfg(test)]
@@ -38,9 +50,112 @@ mod tests {
             .unwrap()
     }
 
+    fn send_amend_and_get_response(
+        order_tx: &crossbeam_channel::Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        amend: AmendOrder,
+    ) -> CommandResponse {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((OrderCommand::AmendOrder(amend), reply_tx))
+            .unwrap();
+        std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn send_mass_cancel_and_get_response(
+        order_tx: &crossbeam_channel::Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        mass_cancel: MassCancel,
+    ) -> CommandResponse {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((OrderCommand::MassCancel(mass_cancel), reply_tx))
+            .unwrap();
+        std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn send_session_state_and_get_response(
+        order_tx: &crossbeam_channel::Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        symbol: &str,
+        state: SessionState,
+    ) -> CommandResponse {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((
+                OrderCommand::SetSessionState(SetSessionState::new(symbol.to_string(), state)),
+                reply_tx,
+            ))
+            .unwrap();
+        std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn send_deposit_and_get_response(
+        order_tx: &crossbeam_channel::Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        user_id: u64,
+        asset: &str,
+        amount: u64,
+    ) -> CommandResponse {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((
+                OrderCommand::Deposit(Deposit::new(user_id, asset.to_string(), amount)),
+                reply_tx,
+            ))
+            .unwrap();
+        std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn send_get_depth_and_get_response(
+        order_tx: &crossbeam_channel::Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        symbol: &str,
+        limit: usize,
+    ) -> CommandResponse {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((
+                OrderCommand::GetDepth(GetDepth::new(symbol.to_string(), limit)),
+                reply_tx,
+            ))
+            .unwrap();
+        std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Spec of the books most tests trade on: any price and quantity, or
+    /// with the price-ladder feature, prices up to 2^20 so the books start
+    /// on ladders.
+    fn test_spec(symbol: &str) -> InstrumentSpec {
+        let spec = InstrumentSpec::new(symbol);
+        if cfg!(feature = "price-ladder") {
+            spec.with_price_rules(1, 1, 1 << 20)
+        } else {
+            spec
+        }
+    }
+
+    fn test_engine(symbol: &str) -> Engine {
+        test_engine_with_symbols([symbol])
+    }
+
+    fn test_engine_with_symbols<'a>(symbols: impl IntoIterator<Item = &'a str>) -> Engine {
+        Engine::with_instruments(symbols.into_iter().map(test_spec))
+    }
+
     #[test]
     fn test_place_valid_limit_order() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -56,7 +171,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let (reply_tx, reply_rx) = oneshot::channel();
@@ -100,7 +224,7 @@ mod tests {
 
     #[test]
     fn test_place_valid_market_order() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -109,6 +233,12 @@ mod tests {
             engine.run(order_rx, event_tx);
         });
 
+        // Something for the market order to trade against
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 200, "SOL_USDC", Side::Sell, 10, 50000),
+        );
+
         let order = Order {
             order_id: 1,
             user_id: 100,
@@ -116,7 +246,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Market,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: None,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let (reply_tx, reply_rx) = oneshot::channel();
@@ -145,7 +284,7 @@ mod tests {
 
     #[test]
     fn test_reject_zero_quantity() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -161,7 +300,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 0,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let (reply_tx, reply_rx) = oneshot::channel();
@@ -193,7 +341,7 @@ mod tests {
 
     #[test]
     fn test_reject_limit_order_without_price() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -209,7 +357,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: None, // Missing price for limit order
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let (reply_tx, reply_rx) = oneshot::channel();
@@ -241,7 +398,7 @@ mod tests {
 
     #[test]
     fn test_reject_limit_order_with_zero_price() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -257,7 +414,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(0), // Zero price
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let response = send_order_and_get_response(&order_tx, order);
@@ -280,7 +446,7 @@ mod tests {
 
     #[test]
     fn test_buy_limit_matches_sell_limit_full_fill() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -297,7 +463,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack event
@@ -310,7 +485,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -363,7 +547,7 @@ mod tests {
 
     #[test]
     fn test_sell_limit_matches_buy_limit_full_fill() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -380,7 +564,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -393,7 +586,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
 
@@ -423,7 +625,7 @@ mod tests {
 
     #[test]
     fn test_partial_fill_maker_remaining() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -440,7 +642,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 100,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -453,7 +664,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -498,7 +718,7 @@ mod tests {
 
     #[test]
     fn test_partial_fill_taker_remaining() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -515,7 +735,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -528,7 +757,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 100,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -561,7 +799,7 @@ mod tests {
 
     #[test]
     fn test_market_order_matches_limit_order() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -578,7 +816,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -591,7 +838,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Market,
             quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
             price: None,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, market_buy);
 
@@ -622,7 +878,7 @@ mod tests {
 
     #[test]
     fn test_market_order_no_liquidity() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -639,28 +895,39 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Market,
             quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
             price: None,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
-        let _ = send_order_and_get_response(&order_tx, market_buy);
+        let response = send_order_and_get_response(&order_tx, market_buy);
 
-        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
-            Ok(Event::OrderAck(ack)) => {
-                assert_eq!(ack.order_id, 1);
-                assert_eq!(ack.user_id, 100);
-                assert_eq!(ack.symbol, "SOL_USDC");
+        // Rejected instead of acknowledged (no liquidity available)
+        match response {
+            CommandResponse::PlaceOrder(OrderResponse::Reject {
+                order_id, reason, ..
+            }) => {
+                assert_eq!(order_id, 1);
+                assert!(matches!(reason, RejectReason::InsufficientLiquidity));
             }
-            other => panic!("Expected OrderAck, got {:?}", other),
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. }) => {
+                panic!("Expected Reject, got Ack")
+            }
+            _ => panic!("Expected PlaceOrder, got {:?}", response),
         }
 
-        // Should get OrderReject (no liquidity available)
         match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
             Ok(Event::OrderReject(reject)) => {
                 assert_eq!(reject.order_id, 1);
                 assert_eq!(reject.user_id, 100);
-                assert!(matches!(reject.reason, RejectReason::InvalidOrder));
-                assert!(
-                    reject.message.contains("liquidity") || reject.message.contains("No liquidity")
-                );
+                assert!(matches!(reject.reason, RejectReason::InsufficientLiquidity));
+                assert!(reject.message.contains("No liquidity"));
             }
             other => panic!("Expected OrderReject, got {:?}", other),
         }
@@ -671,7 +938,7 @@ mod tests {
 
     #[test]
     fn test_order_rests_on_book() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -688,7 +955,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(60000), // High price
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
 
@@ -720,7 +996,7 @@ mod tests {
 
     #[test]
     fn test_fifo_priority_same_price() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -738,7 +1014,16 @@ mod tests {
                 side: Side::Sell,
                 order_type: OrderType::Limit,
                 quantity: 10,
+                quote_quantity: None,
+                display_quantity: None,
                 price: Some(50000),
+                stop_price: None,
+                trailing_offset: None,
+                time_in_force: TimeInForce::GTC,
+                expire_at: None,
+                post_only: None,
+                self_trade_prevention: None,
+                max_slippage_bps: None,
             };
             let _ = send_order_and_get_response(&order_tx, sell_order);
             let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -752,7 +1037,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -791,7 +1085,7 @@ mod tests {
 
     #[test]
     fn test_price_priority_best_price_first() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -809,7 +1103,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(51000), // Higher price
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell1);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1));
@@ -822,7 +1125,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000), // Lower price (better for buyer)
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell2);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1));
@@ -835,7 +1147,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(52000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -875,7 +1196,7 @@ mod tests {
 
     #[test]
     fn test_cancel_resting_order() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -892,7 +1213,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(60000), // High price, won't match
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -927,9 +1257,60 @@ mod tests {
         handle.join().unwrap();
     }
 
+    #[test]
+    fn test_cancel_from_middle_of_queue_keeps_time_priority() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for order_id in 1..=3 {
+            let _ = send_order_and_get_response(
+                &order_tx,
+                limit_order(order_id, 100, "SOL_USDC", Side::Sell, 10, 100),
+            );
+        }
+
+        let cancel = CancelOrder::new(2, 100, "SOL_USDC".to_string());
+        match send_cancel_and_get_response(&order_tx, cancel) {
+            CommandResponse::CancelOrder(CancelOrderResponse::Ack { .. }) => {}
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(4, 100, "SOL_USDC", Side::Sell, 10, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(5, 200, "SOL_USDC", Side::Buy, 25, 100),
+        );
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => assert_eq!(depth.asks[0], (100, 5)),
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        let makers: Vec<(u64, u64)> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some((trade.maker_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(makers, vec![(1, 10), (3, 10), (4, 5)]);
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
     #[test]
     fn test_cancel_non_existent_order() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -944,14 +1325,19 @@ mod tests {
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
         };
-        let _ = send_cancel_and_get_response(&order_tx, cancel);
+        match send_cancel_and_get_response(&order_tx, cancel) {
+            CommandResponse::CancelOrder(CancelOrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::OrderNotFound));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
 
         // Should get OrderReject
         match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
             Ok(Event::OrderReject(reject)) => {
                 assert_eq!(reject.order_id, 999);
                 assert_eq!(reject.user_id, 100);
-                assert!(matches!(reject.reason, RejectReason::InvalidOrder));
+                assert!(matches!(reject.reason, RejectReason::OrderNotFound));
             }
             other => panic!("Expected OrderReject, got {:?}", other),
         }
@@ -962,7 +1348,7 @@ mod tests {
 
     #[test]
     fn test_cancel_partially_filled_order() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -979,7 +1365,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 100,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, sell_order);
         let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -992,7 +1387,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -1027,7 +1431,7 @@ mod tests {
 
     #[test]
     fn test_single_order_matches_multiple_orders() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -1045,7 +1449,16 @@ mod tests {
                 side: Side::Sell,
                 order_type: OrderType::Limit,
                 quantity: 20,
+                quote_quantity: None,
+                display_quantity: None,
                 price: Some(50000),
+                stop_price: None,
+                trailing_offset: None,
+                time_in_force: TimeInForce::GTC,
+                expire_at: None,
+                post_only: None,
+                self_trade_prevention: None,
+                max_slippage_bps: None,
             };
             let _ = send_order_and_get_response(&order_tx, sell_order);
             let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -1059,7 +1472,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 60, // Matches all 3
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -1106,7 +1528,7 @@ mod tests {
 
     #[test]
     fn test_single_order_matches_multiple_levels() {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = test_engine("SOL_USDC");
         let (order_tx, order_rx) =
             crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
         let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
@@ -1124,7 +1546,16 @@ mod tests {
                 side: Side::Sell,
                 order_type: OrderType::Limit,
                 quantity: 20,
+                quote_quantity: None,
+                display_quantity: None,
                 price: Some(50000 + i as u64 * 1000),
+                stop_price: None,
+                trailing_offset: None,
+                time_in_force: TimeInForce::GTC,
+                expire_at: None,
+                post_only: None,
+                self_trade_prevention: None,
+                max_slippage_bps: None,
             };
             let _ = send_order_and_get_response(&order_tx, sell_order);
             let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
@@ -1138,7 +1569,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 80, // Matches all 3
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(53000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
         let _ = send_order_and_get_response(&order_tx, buy_order);
 
@@ -1182,4 +1622,4579 @@ mod tests {
         drop(order_tx);
         handle.join().unwrap();
     }
+
+    #[test]
+    fn test_reject_unknown_symbol() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "DOGE_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+
+        match send_order_and_get_response(&order_tx, order) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject {
+                order_id, reason, ..
+            }) => {
+                assert_eq!(order_id, 1);
+                assert!(matches!(reason, RejectReason::SymbolNotFound));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(Event::OrderReject(reject)) => {
+                assert_eq!(reject.symbol, "DOGE_USDC");
+                assert!(matches!(reject.reason, RejectReason::SymbolNotFound));
+            }
+            other => panic!("Expected OrderReject, got {:?}", other),
+        }
+
+        let cancel = CancelOrder {
+            order_id: 1,
+            user_id: 100,
+            symbol: "DOGE_USDC".to_string(),
+        };
+
+        match send_cancel_and_get_response(&order_tx, cancel) {
+            CommandResponse::CancelOrder(CancelOrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::SymbolNotFound));
+            }
+            other => panic!("Expected cancel Reject, got {:?}", other),
+        }
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((
+                OrderCommand::GetDepth(GetDepth::new("DOGE_USDC".to_string(), 20)),
+                reply_tx,
+            ))
+            .unwrap();
+        let response = std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap();
+
+        assert!(matches!(response, CommandResponse::Reject(_)));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_orders_routed_to_their_own_book() {
+        let mut engine = test_engine_with_symbols(["SOL_USDC", "ETH_USDC"]);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // Resting sell on SOL_USDC
+        let sell_order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, sell_order);
+
+        // Crossing buy on ETH_USDC must not match the SOL_USDC ask
+        let buy_order = Order {
+            order_id: 2,
+            user_id: 200,
+            symbol: "ETH_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, buy_order);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        assert!(
+            !events.iter().any(|e| matches!(e, Event::Trade(_))),
+            "Orders on different symbols must not match"
+        );
+
+        let book_updates: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::BookUpdate(b) => Some(b),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(book_updates.len(), 2);
+        assert_eq!(book_updates[0].symbol, "SOL_USDC");
+        assert_eq!(book_updates[0].asks[0].price, 50000);
+        assert_eq!(book_updates[1].symbol, "ETH_USDC");
+        assert_eq!(book_updates[1].bids[0].price, 50000);
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((
+                OrderCommand::GetDepth(GetDepth::new("ETH_USDC".to_string(), 5)),
+                reply_tx,
+            ))
+            .unwrap();
+        let response = std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap();
+
+        match response {
+            CommandResponse::Depth(depth) => {
+                assert_eq!(depth.bids.len(), 5);
+                assert_eq!(depth.bids[0], (50000, 10));
+            }
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_ioc_limit_order_cancels_remainder() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell_order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, sell_order);
+
+        let ioc_buy = Order {
+            order_id: 2,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 30,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::IOC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, ioc_buy);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        let trades: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Trade(t) => Some(t),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 10);
+
+        let cancelled = events
+            .iter()
+            .find_map(|e| match e {
+                Event::OrderCancelled(c) => Some(c),
+                _ => None,
+            })
+            .expect("IOC remainder should be cancelled");
+        assert_eq!(cancelled.order_id, 2);
+        assert!(matches!(cancelled.reason, CancelReason::ImmediateOrCancel));
+
+        // The remainder must not rest on the book
+        match events.last() {
+            Some(Event::BookUpdate(b)) => {
+                assert_eq!(b.bids[0].quantity, 0);
+                assert_eq!(b.asks[0].quantity, 0);
+            }
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_fok_order_rejected_without_touching_book() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for (order_id, price) in [(1, 50000), (2, 51000)] {
+            let sell_order = Order {
+                order_id,
+                user_id: 100,
+                symbol: "SOL_USDC".to_string(),
+                side: Side::Sell,
+                order_type: OrderType::Limit,
+                quantity: 10,
+                quote_quantity: None,
+                display_quantity: None,
+                price: Some(price),
+                stop_price: None,
+                trailing_offset: None,
+                time_in_force: TimeInForce::GTC,
+                expire_at: None,
+                post_only: None,
+                self_trade_prevention: None,
+                max_slippage_bps: None,
+            };
+            let _ = send_order_and_get_response(&order_tx, sell_order);
+        }
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        // Only 10 available at or below 50500
+        let fok_buy = Order {
+            order_id: 3,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 15,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50500),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::FOK,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+
+        match send_order_and_get_response(&order_tx, fok_buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject {
+                order_id, reason, ..
+            }) => {
+                assert_eq!(order_id, 3);
+                assert!(matches!(reason, RejectReason::InsufficientLiquidity));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(Event::OrderReject(reject)) => assert_eq!(reject.order_id, 3),
+            other => panic!("Expected OrderReject, got {:?}", other),
+        }
+
+        // Enough liquidity across both levels: fills completely
+        let fok_buy = Order {
+            order_id: 4,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 15,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(51000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::FOK,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, fok_buy);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        let traded: u64 = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Trade(t) => Some(t.quantity),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(traded, 15);
+        assert!(
+            !events
+                .iter()
+                .any(|e| matches!(e, Event::OrderCancelled(_) | Event::OrderReject(_)))
+        );
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_market_order_remainder_cancelled() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell_order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, sell_order);
+
+        let market_buy = Order {
+            order_id: 2,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Market,
+            quantity: 25,
+            quote_quantity: None,
+            display_quantity: None,
+            price: None,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, market_buy);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        let cancelled = events
+            .iter()
+            .find_map(|e| match e {
+                Event::OrderCancelled(c) => Some(c),
+                _ => None,
+            })
+            .expect("Unfilled market remainder should be cancelled");
+        assert_eq!(cancelled.order_id, 2);
+        assert!(matches!(cancelled.reason, CancelReason::ImmediateOrCancel));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_gtd_order_expires() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let expire_at = chrono::Utc::now().timestamp_millis() + 150;
+        let gtd_order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTD,
+            expire_at: Some(expire_at),
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+
+        match send_order_and_get_response(&order_tx, gtd_order) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { order_id, .. }) => {
+                assert_eq!(order_id, 1);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let _ = event_rx.recv_timeout(std::time::Duration::from_secs(1)); // Ack
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(Event::BookUpdate(b)) => assert_eq!(b.bids[0].quantity, 50),
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(2)) {
+            Ok(Event::OrderCancelled(c)) => {
+                assert_eq!(c.order_id, 1);
+                assert!(matches!(c.reason, CancelReason::Expired));
+                assert!(chrono::Utc::now().timestamp_millis() >= expire_at);
+            }
+            other => panic!("Expected OrderCancelled, got {:?}", other),
+        }
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(Event::BookUpdate(b)) => assert_eq!(b.bids[0].quantity, 0),
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_reject_gtd_without_valid_expiry() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let past = chrono::Utc::now().timestamp_millis() - 1_000;
+        for (order_id, expire_at) in [(1, None), (2, Some(past))] {
+            let gtd_order = Order {
+                order_id,
+                user_id: 100,
+                symbol: "SOL_USDC".to_string(),
+                side: Side::Buy,
+                order_type: OrderType::Limit,
+                quantity: 50,
+                quote_quantity: None,
+                display_quantity: None,
+                price: Some(50000),
+                stop_price: None,
+                trailing_offset: None,
+                time_in_force: TimeInForce::GTD,
+                expire_at,
+                post_only: None,
+                self_trade_prevention: None,
+                max_slippage_bps: None,
+            };
+
+            match send_order_and_get_response(&order_tx, gtd_order) {
+                CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                    assert!(matches!(reason, RejectReason::InvalidOrder));
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_next_session_close() {
+        let close = chrono::NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+        let at = |h: u32, m: u32| {
+            chrono::NaiveDate::from_ymd_opt(2025, 1, 10)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp_millis()
+        };
+
+        // Before the close: expires the same day
+        assert_eq!(next_session_close(close, at(9, 30)), at(16, 0));
+        // At or after the close: expires on the next session
+        let next_day = at(16, 0) + 24 * 60 * 60 * 1000;
+        assert_eq!(next_session_close(close, at(16, 0)), next_day);
+        assert_eq!(next_session_close(close, at(20, 0)), next_day);
+    }
+
+    #[test]
+    fn test_post_only_rejected_when_crossing() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell_order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, sell_order);
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let post_only_buy = Order {
+            order_id: 2,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: Some(PostOnly::Reject),
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+
+        match send_order_and_get_response(&order_tx, post_only_buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject {
+                order_id, reason, ..
+            }) => {
+                assert_eq!(order_id, 2);
+                assert!(matches!(reason, RejectReason::PostOnlyWouldCross));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(Event::OrderReject(reject)) => {
+                assert_eq!(reject.order_id, 2);
+                assert!(matches!(reject.reason, RejectReason::PostOnlyWouldCross));
+            }
+            other => panic!("Expected OrderReject, got {:?}", other),
+        }
+
+        // No trade happened, nothing else was emitted
+        assert!(
+            event_rx
+                .recv_timeout(std::time::Duration::from_millis(200))
+                .is_err()
+        );
+
+        // A post-only order that does not cross rests normally
+        let post_only_buy = Order {
+            order_id: 3,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(49000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: Some(PostOnly::Reject),
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+
+        match send_order_and_get_response(&order_tx, post_only_buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { order_id, .. }) => {
+                assert_eq!(order_id, 3);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_post_only_repriced_behind_best() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let buy_order = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: 10,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, buy_order);
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let post_only_sell = Order {
+            order_id: 2,
+            user_id: 200,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            quantity: 5,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(49000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: Some(PostOnly::Reprice),
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, post_only_sell);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        assert!(!events.iter().any(|e| matches!(e, Event::Trade(_))));
+
+        match events.last() {
+            Some(Event::BookUpdate(b)) => {
+                assert_eq!(b.bids[0].price, 50000);
+                assert_eq!(b.asks[0].price, 50001, "Repriced one tick above best bid");
+                assert_eq!(b.asks[0].quantity, 5);
+            }
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    fn run_self_trade(
+        mut engine: Engine,
+        taker_mode: Option<SelfTradePrevention>,
+        maker_quantity: u64,
+        taker_quantity: u64,
+    ) -> Vec<Event> {
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let maker = Order {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            quantity: maker_quantity,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, maker);
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let taker = Order {
+            order_id: 2,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            quantity: taker_quantity,
+            quote_quantity: None,
+            display_quantity: None,
+            price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: taker_mode,
+            max_slippage_bps: None,
+        };
+        let _ = send_order_and_get_response(&order_tx, taker);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+        events
+    }
+
+    fn self_trade_cancels(events: &[Event]) -> Vec<u64> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                Event::OrderCancelled(c) => {
+                    assert!(matches!(c.reason, CancelReason::SelfTradePrevention));
+                    Some(c.order_id)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancel_newest_by_default() {
+        let events = run_self_trade(test_engine("SOL_USDC"), None, 10, 10);
+
+        assert!(!events.iter().any(|e| matches!(e, Event::Trade(_))));
+        assert_eq!(self_trade_cancels(&events), vec![2]);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancel_oldest() {
+        let events = run_self_trade(
+            test_engine("SOL_USDC"),
+            Some(SelfTradePrevention::CancelOldest),
+            10,
+            10,
+        );
+
+        assert!(!events.iter().any(|e| matches!(e, Event::Trade(_))));
+        assert_eq!(self_trade_cancels(&events), vec![1]);
+
+        // The taker rests in place of the cancelled maker
+        match events.last() {
+            Some(Event::BookUpdate(b)) => {
+                assert_eq!(b.asks[0].quantity, 0);
+                assert_eq!(b.bids[0].price, 50000);
+                assert_eq!(b.bids[0].quantity, 10);
+            }
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancel_both() {
+        let events = run_self_trade(
+            test_engine("SOL_USDC"),
+            Some(SelfTradePrevention::CancelBoth),
+            10,
+            10,
+        );
+
+        assert!(!events.iter().any(|e| matches!(e, Event::Trade(_))));
+        assert_eq!(self_trade_cancels(&events), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_decrement_and_cancel() {
+        let events = run_self_trade(
+            test_engine("SOL_USDC"),
+            Some(SelfTradePrevention::DecrementAndCancel),
+            10,
+            4,
+        );
+
+        assert!(
+            !events
+                .iter()
+                .any(|e| matches!(e, Event::Trade(_) | Event::Fill(_)))
+        );
+        assert_eq!(self_trade_cancels(&events), vec![2]);
+
+        // The maker's owner sees it shrink
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::OrderAmended(a)
+                if a.order_id == 1 && a.quantity == 6 && a.remaining_quantity == 6
+        )));
+
+        match events.last() {
+            Some(Event::BookUpdate(b)) => {
+                assert_eq!(b.asks[0].quantity, 6, "Maker decremented by taker size");
+                assert_eq!(b.bids[0].quantity, 0);
+            }
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_self_trade_prevention_symbol_default() {
+        let engine = test_engine("SOL_USDC")
+            .with_self_trade_prevention("SOL_USDC", SelfTradePrevention::CancelOldest);
+        let events = run_self_trade(engine, None, 10, 10);
+        assert_eq!(self_trade_cancels(&events), vec![1]);
+
+        // The order's own mode takes precedence over the symbol default
+        let engine = test_engine("SOL_USDC")
+            .with_self_trade_prevention("SOL_USDC", SelfTradePrevention::CancelOldest);
+        let events = run_self_trade(engine, Some(SelfTradePrevention::CancelNewest), 10, 10);
+        assert_eq!(self_trade_cancels(&events), vec![2]);
+    }
+
+    #[test]
+    fn test_fok_does_not_count_own_orders() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // User 100's own order sits between two others in the queue
+        for (order_id, user_id) in [(1, 200), (2, 100), (3, 200)] {
+            let _ = send_order_and_get_response(
+                &order_tx,
+                limit_order(order_id, user_id, "SOL_USDC", Side::Sell, 10, 50000),
+            );
+        }
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        // Cancelling the newest stops the buy at order 2, after only 10
+        let mut fok_buy = limit_order(4, 100, "SOL_USDC", Side::Buy, 15, 50000);
+        fok_buy.time_in_force = TimeInForce::FOK;
+        match send_order_and_get_response(&order_tx, fok_buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::InsufficientLiquidity));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => assert_eq!(depth.asks[0], (50000, 30)),
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        // Cancelling the oldest skips order 2 and leaves 20 to trade
+        let mut fok_buy = limit_order(5, 100, "SOL_USDC", Side::Buy, 15, 50000);
+        fok_buy.time_in_force = TimeInForce::FOK;
+        fok_buy.self_trade_prevention = Some(SelfTradePrevention::CancelOldest);
+        let _ = send_order_and_get_response(&order_tx, fok_buy);
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let filled: u64 = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some(trade.quantity),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(filled, 15);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_reports_makers_cancelled_by_market_orders() {
+        let mut ledger = Ledger::new();
+        ledger.deposit(100, "SOL", 20);
+        ledger.deposit(100, "USDC", 1_000_000);
+        ledger.deposit(200, "USDC", 1_000_000);
+        ledger.deposit(300, "SOL", 5);
+        let mut engine = test_engine("SOL_USDC")
+            .with_ledger(ledger)
+            .with_self_trade_prevention("SOL_USDC", SelfTradePrevention::CancelOldest);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let cancelled =
+            |event_rx: &crossbeam_channel::Receiver<Event>| -> Vec<(u64, CancelReason)> {
+                std::iter::from_fn(|| {
+                    event_rx
+                        .recv_timeout(std::time::Duration::from_millis(200))
+                        .ok()
+                })
+                .filter_map(|event| match event {
+                    Event::OrderReject(reject) => panic!("Unexpected reject {:?}", reject),
+                    Event::OrderCancelled(c) => Some((c.order_id, c.reason)),
+                    _ => None,
+                })
+                .collect()
+            };
+
+        // A market order meeting only the user's own order
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 100, "SOL_USDC", Side::Sell, 10, 50000),
+        );
+        let market_buy = Order::new(
+            2,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            10,
+            None,
+        );
+        let _ = send_order_and_get_response(&order_tx, market_buy);
+        assert_eq!(
+            cancelled(&event_rx),
+            vec![
+                (1, CancelReason::SelfTradePrevention),
+                (2, CancelReason::ImmediateOrCancel)
+            ]
+        );
+
+        // A triggered stop-market order meeting only the user's own order
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 300, "SOL_USDC", Side::Sell, 5, 50000),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(4, 100, "SOL_USDC", Side::Sell, 10, 50001),
+        );
+        let stop_buy = Order::new(
+            5,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::StopMarket,
+            10,
+            None,
+        )
+        .with_stop_price(Some(50000));
+        let _ = send_order_and_get_response(&order_tx, stop_buy);
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(6, 200, "SOL_USDC", Side::Buy, 5, 50000),
+        );
+        assert_eq!(
+            cancelled(&event_rx),
+            vec![
+                (4, CancelReason::SelfTradePrevention),
+                (5, CancelReason::ImmediateOrCancel)
+            ]
+        );
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+
+        // Nothing the cancelled orders locked is left behind
+        let ledger = engine.ledger().unwrap();
+        assert_eq!(ledger.balance(100, "SOL").locked, 0);
+        assert_eq!(ledger.balance(100, "USDC").locked, 0);
+    }
+
+    #[test]
+    fn test_stop_order_hidden_until_triggered_and_cancellable() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let stop_sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::StopLimit,
+            10,
+            Some(48000),
+        )
+        .with_stop_price(Some(49000));
+
+        match send_order_and_get_response(&order_tx, stop_sell) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { order_id, .. }) => {
+                assert_eq!(order_id, 1);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => {
+                assert_eq!(depth.asks[0], (0, 0), "Stop orders are not in depth");
+            }
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        let cancel = CancelOrder::new(1, 100, "SOL_USDC".to_string());
+        let _ = send_cancel_and_get_response(&order_tx, cancel);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::OrderCancelled(c)
+                if c.order_id == 1 && matches!(c.reason, CancelReason::UserRequested)
+        )));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_reject_stop_order_without_stop_price() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let stop_buy = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::StopMarket,
+            10,
+            None,
+        );
+
+        match send_order_and_get_response(&order_tx, stop_buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::InvalidOrder));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        // A trailing stop has nothing to start from before the first trade
+        let trailing_sell = Order::new(
+            2,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::StopMarket,
+            10,
+            None,
+        )
+        .with_trailing_offset(Some(TrailingOffset::Absolute(1000)));
+
+        match send_order_and_get_response(&order_tx, trailing_sell) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::InvalidOrder));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        // Rejected instead of acknowledged
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| matches!(e, Event::OrderReject(_))));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_stop_orders_trigger_and_cascade() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for (order_id, price) in [(1, 49500), (2, 49000)] {
+            let bid = Order::new(
+                order_id,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Limit,
+                10,
+                Some(price),
+            );
+            let _ = send_order_and_get_response(&order_tx, bid);
+        }
+
+        // Fires when the price trades down to 49500 and sweeps into 49000
+        let stop_market = Order::new(
+            3,
+            300,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::StopMarket,
+            10,
+            None,
+        )
+        .with_stop_price(Some(49500));
+        let _ = send_order_and_get_response(&order_tx, stop_market);
+
+        // Fires only once the first stop trades at 49000
+        let stop_limit = Order::new(
+            4,
+            400,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::StopLimit,
+            8,
+            Some(49000),
+        )
+        .with_stop_price(Some(49000));
+        let _ = send_order_and_get_response(&order_tx, stop_limit);
+
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let sell = Order::new(
+            5,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            5,
+            Some(49500),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        let trades: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Trade(t) => Some((t.taker_order_id, t.price, t.quantity)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            trades,
+            vec![(5, 49500, 5), (3, 49500, 5), (3, 49000, 5), (4, 49000, 5),]
+        );
+
+        // The stop-limit remainder rests at its limit price
+        match events.last() {
+            Some(Event::BookUpdate(b)) => {
+                assert_eq!(b.bids[0].quantity, 0);
+                assert_eq!(b.asks[0].price, 49000);
+                assert_eq!(b.asks[0].quantity, 3);
+            }
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_triggered_stop_rejected_on_activation() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let bid = limit_order(1, 100, "SOL_USDC", Side::Buy, 10, 49500);
+        let _ = send_order_and_get_response(&order_tx, bid);
+
+        // Fill-or-kill once triggered, with more than will be left to sell into
+        let stop_limit = Order::new(
+            2,
+            300,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::StopLimit,
+            20,
+            Some(49000),
+        )
+        .with_stop_price(Some(49500))
+        .with_time_in_force(TimeInForce::FOK);
+        assert!(matches!(
+            send_order_and_get_response(&order_tx, stop_limit),
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+        ));
+
+        let sell = limit_order(3, 200, "SOL_USDC", Side::Sell, 5, 49500);
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let cancels: Vec<_> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::OrderCancelled(c) => Some((c.order_id, c.reason)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cancels, vec![(2, CancelReason::StopRejected)]);
+    }
+
+    #[test]
+    fn test_trailing_stop_price() {
+        assert_eq!(
+            trailing_stop_price(&Side::Sell, TrailingOffset::Percentage(100), 50000),
+            Some(49500)
+        );
+        assert_eq!(
+            trailing_stop_price(&Side::Buy, TrailingOffset::Absolute(10), 100),
+            Some(110)
+        );
+        assert_eq!(
+            trailing_stop_price(&Side::Sell, TrailingOffset::Absolute(200), 100),
+            None
+        );
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_price_and_triggers() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 400, "SOL_USDC", Side::Buy, 10, 50000),
+        );
+
+        // No stop price: starts 500 below the best bid
+        let trailing_stop = Order::new(
+            2,
+            300,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::StopMarket,
+            5,
+            None,
+        )
+        .with_trailing_offset(Some(TrailingOffset::Absolute(500)));
+
+        match send_order_and_get_response(&order_tx, trailing_stop) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { order_id, .. }) => {
+                assert_eq!(order_id, 2);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 400, "SOL_USDC", Side::Buy, 10, 50300),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(4, 400, "SOL_USDC", Side::Buy, 1, 50400),
+        );
+
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        // Trades at 50400 and leaves the best bid at 50300, the stop follows
+        // the bid up
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(5, 100, "SOL_USDC", Side::Sell, 1, 50400),
+        );
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        let updated = events
+            .iter()
+            .find_map(|e| match e {
+                Event::StopPriceUpdated(u) => Some(u),
+                _ => None,
+            })
+            .expect("Trailing stop should move with the best bid");
+        assert_eq!(updated.order_id, 2);
+        assert_eq!(updated.user_id, 300);
+        assert_eq!(updated.stop_price, 49800);
+        assert_eq!(updated.price, None);
+
+        for order_id in [1, 3] {
+            let cancel = CancelOrder::new(order_id, 400, "SOL_USDC".to_string());
+            let _ = send_cancel_and_get_response(&order_tx, cancel);
+        }
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(6, 400, "SOL_USDC", Side::Buy, 10, 49800),
+        );
+
+        // Falling back through the moved stop triggers it
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(7, 100, "SOL_USDC", Side::Sell, 1, 49800),
+        );
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        let trades: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Trade(t) => Some((t.taker_order_id, t.price, t.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trades, vec![(7, 49800, 1), (2, 49800, 5)]);
+        assert!(
+            !events
+                .iter()
+                .any(|e| matches!(e, Event::StopPriceUpdated(_)))
+        );
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_iceberg_order_shows_peak_and_refills_at_back() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let iceberg = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            30,
+            Some(50000),
+        )
+        .with_display_quantity(Some(10));
+        let _ = send_order_and_get_response(&order_tx, iceberg);
+
+        let sell = Order::new(
+            2,
+            300,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            5,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => {
+                assert_eq!(depth.asks[0], (50000, 15), "Only the peak is displayed");
+            }
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let collect_trades = || {
+            let mut trades = Vec::new();
+            while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+                if let Event::Trade(t) = event {
+                    trades.push((t.maker_order_id, t.quantity));
+                }
+            }
+            trades
+        };
+
+        // Consumes the peak, the refill goes behind order 2
+        let buy = Order::new(
+            3,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            12,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, buy);
+        assert_eq!(collect_trades(), vec![(1, 10), (2, 2)]);
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => assert_eq!(depth.asks[0], (50000, 13)),
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        // Hidden reserve keeps trading peak by peak
+        let buy = Order::new(
+            4,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            20,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, buy);
+        assert_eq!(collect_trades(), vec![(2, 3), (1, 10), (1, 7)]);
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => assert_eq!(depth.asks[0], (50000, 3)),
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_reject_invalid_display_quantity() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let iceberg = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        )
+        .with_display_quantity(Some(20));
+
+        match send_order_and_get_response(&order_tx, iceberg) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::InvalidQuantity));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    fn run_amend_priority(amend: AmendOrder, buy_quantity: u64) -> Vec<(u64, u64)> {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for (order_id, user_id) in [(1, 100), (2, 300)] {
+            let sell = Order::new(
+                order_id,
+                user_id,
+                "SOL_USDC".to_string(),
+                Side::Sell,
+                OrderType::Limit,
+                10,
+                Some(50000),
+            );
+            let _ = send_order_and_get_response(&order_tx, sell);
+        }
+
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Ack { order_id, .. }) => {
+                assert_eq!(order_id, 1);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let buy = Order::new(
+            3,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            buy_quantity,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        let mut trades = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            if let Event::Trade(t) = event {
+                trades.push((t.maker_order_id, t.quantity));
+            }
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+        trades
+    }
+
+    #[test]
+    fn test_amend_reduce_quantity_keeps_priority() {
+        let amend = AmendOrder::new(1, 100, "SOL_USDC".to_string(), None, Some(6));
+        assert_eq!(run_amend_priority(amend, 8), vec![(1, 6), (2, 2)]);
+    }
+
+    #[test]
+    fn test_amend_increase_quantity_loses_priority() {
+        let amend = AmendOrder::new(1, 100, "SOL_USDC".to_string(), None, Some(15));
+        assert_eq!(run_amend_priority(amend, 12), vec![(2, 10), (1, 2)]);
+    }
+
+    #[test]
+    fn test_amend_price_loses_priority_and_can_match() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(51000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        let bid = Order::new(
+            2,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            4,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, bid);
+
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let amend = AmendOrder::new(1, 100, "SOL_USDC".to_string(), Some(50000), None);
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Ack {
+                price, quantity, ..
+            }) => {
+                assert_eq!(price, 50000);
+                assert_eq!(quantity, 10);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            events.push(event);
+        }
+
+        assert!(matches!(&events[0], Event::OrderAmended(a) if a.order_id == 1));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Trade(t) if t.taker_order_id == 1 && t.quantity == 4
+        )));
+
+        match events.last() {
+            Some(Event::BookUpdate(b)) => {
+                assert_eq!(b.bids[0].quantity, 0);
+                assert_eq!(b.asks[0].price, 50000);
+                assert_eq!(b.asks[0].quantity, 6);
+            }
+            other => panic!("Expected BookUpdate, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_amend_rejects() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(51000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        let amends = [
+            // Someone else's order
+            (
+                AmendOrder::new(1, 999, "SOL_USDC".to_string(), None, Some(5)),
+                RejectReason::NotOrderOwner,
+            ),
+            (
+                AmendOrder::new(42, 100, "SOL_USDC".to_string(), None, Some(5)),
+                RejectReason::OrderNotFound,
+            ),
+            // Nothing to change
+            (
+                AmendOrder::new(1, 100, "SOL_USDC".to_string(), None, None),
+                RejectReason::InvalidOrder,
+            ),
+        ];
+
+        for (amend, expected) in amends {
+            match send_amend_and_get_response(&order_tx, amend) {
+                CommandResponse::AmendOrder(AmendOrderResponse::Reject { reason, .. }) => {
+                    assert_eq!(reason.to_string(), expected.to_string());
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_cancel_rejects_other_users_order() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let cancel = CancelOrder::new(1, 200, "SOL_USDC".to_string());
+        match send_cancel_and_get_response(&order_tx, cancel) {
+            CommandResponse::CancelOrder(CancelOrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::NotOrderOwner));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        match event_rx.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(Event::OrderReject(reject)) => {
+                assert_eq!(reject.user_id, 200);
+                assert!(matches!(reject.reason, RejectReason::NotOrderOwner));
+            }
+            other => panic!("Expected OrderReject, got {:?}", other),
+        }
+
+        // The order is still resting
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 10) {
+            CommandResponse::Depth(depth) => assert_eq!(depth.asks[0], (50000, 10)),
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_mass_cancel_with_symbol_and_side_filters() {
+        let mut engine = test_engine_with_symbols(["SOL_USDC", "BTC_USDC"]);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let orders = [
+            (1, 100, "SOL_USDC", Side::Buy, 49000),
+            (2, 100, "SOL_USDC", Side::Sell, 51000),
+            (3, 100, "BTC_USDC", Side::Buy, 49000),
+            (4, 200, "SOL_USDC", Side::Buy, 48000),
+        ];
+        for (order_id, user_id, symbol, side, price) in orders {
+            let order = Order::new(
+                order_id,
+                user_id,
+                symbol.to_string(),
+                side,
+                OrderType::Limit,
+                10,
+                Some(price),
+            );
+            let _ = send_order_and_get_response(&order_tx, order);
+        }
+
+        // A pending stop order is cancelled too
+        let stop = Order::new(
+            5,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::StopMarket,
+            10,
+            None,
+        )
+        .with_stop_price(Some(52000));
+        let _ = send_order_and_get_response(&order_tx, stop);
+
+        while event_rx
+            .recv_timeout(std::time::Duration::from_millis(100))
+            .is_ok()
+        {}
+
+        let mass_cancel = MassCancel::new(100, Some("SOL_USDC".to_string()), Some(Side::Buy));
+        match send_mass_cancel_and_get_response(&order_tx, mass_cancel) {
+            CommandResponse::MassCancel(MassCancelResponse::Ack { cancelled, .. }) => {
+                assert_eq!(cancelled, 2);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let mut cancelled = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            if let Event::OrderCancelled(c) = event {
+                assert!(matches!(c.reason, CancelReason::UserRequested));
+                cancelled.push(c.order_id);
+            }
+        }
+        assert_eq!(cancelled, vec![1, 5]);
+
+        // Everything else left for user 100, across both symbols
+        match send_mass_cancel_and_get_response(&order_tx, MassCancel::new(100, None, None)) {
+            CommandResponse::MassCancel(MassCancelResponse::Ack { cancelled, .. }) => {
+                assert_eq!(cancelled, 2);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        // User 200's order is untouched
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 10) {
+            CommandResponse::Depth(depth) => {
+                assert_eq!(depth.bids[0], (48000, 10));
+                assert_eq!(depth.bids[1], (0, 0));
+                assert_eq!(depth.asks[0], (0, 0));
+            }
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        let unknown = MassCancel::new(100, Some("ETH_USDC".to_string()), None);
+        match send_mass_cancel_and_get_response(&order_tx, unknown) {
+            CommandResponse::MassCancel(MassCancelResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::SymbolNotFound));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    /// Rests asks of 10 at 50000, 50200 and 51000, sends `order`, and returns
+    /// the trades (price, quantity) and cancels it produced.
+    fn run_market_sweep(engine: Engine, order: Order) -> (Vec<(u64, u64)>, Vec<CancelReason>) {
+        let mut engine = engine;
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for (order_id, price) in [(1, 50000), (2, 50200), (3, 51000)] {
+            let sell = Order::new(
+                order_id,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Sell,
+                OrderType::Limit,
+                10,
+                Some(price),
+            );
+            let _ = send_order_and_get_response(&order_tx, sell);
+        }
+
+        match send_order_and_get_response(&order_tx, order) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. }) => {}
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let mut trades = Vec::new();
+        let mut cancels = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            match event {
+                Event::Trade(t) => trades.push((t.price, t.quantity)),
+                Event::OrderCancelled(c) => cancels.push(c.reason),
+                _ => {}
+            }
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+        (trades, cancels)
+    }
+
+    #[test]
+    fn test_market_order_stops_at_symbol_slippage_band() {
+        // 1% band from 50000 allows up to 50500
+        let engine = test_engine("SOL_USDC").with_max_slippage("SOL_USDC", 100);
+        let buy = Order::new(
+            4,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            25,
+            None,
+        );
+
+        let (trades, cancels) = run_market_sweep(engine, buy);
+        assert_eq!(trades, vec![(50000, 10), (50200, 10)]);
+        assert_eq!(cancels.len(), 1);
+        assert!(matches!(cancels[0], CancelReason::SlippageExceeded));
+    }
+
+    #[test]
+    fn test_market_order_slippage_overrides_symbol_default() {
+        let engine = test_engine("SOL_USDC").with_max_slippage("SOL_USDC", 100);
+        let buy = Order::new(
+            4,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            25,
+            None,
+        )
+        .with_max_slippage_bps(Some(10));
+
+        let (trades, cancels) = run_market_sweep(engine, buy);
+        assert_eq!(trades, vec![(50000, 10)]);
+        assert!(matches!(cancels[..], [CancelReason::SlippageExceeded]));
+
+        // Without any band the order sweeps the book
+        let buy = Order::new(
+            4,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            25,
+            None,
+        );
+        let (trades, cancels) = run_market_sweep(test_engine("SOL_USDC"), buy);
+        assert_eq!(trades, vec![(50000, 10), (50200, 10), (51000, 5)]);
+        assert!(cancels.is_empty());
+    }
+
+    #[test]
+    fn test_quote_quantity_market_buy() {
+        // 500000 buys the first level and 502000 the second, the remaining
+        // 208000 buys 4 at 51000 with 4000 left unspent
+        let buy = Order::new(
+            4,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            0,
+            None,
+        )
+        .with_quote_quantity(Some(1_210_000));
+
+        let (trades, cancels) = run_market_sweep(test_engine("SOL_USDC"), buy);
+        assert_eq!(trades, vec![(50000, 10), (50200, 10), (51000, 4)]);
+        assert!(cancels.is_empty());
+    }
+
+    #[test]
+    fn test_quote_quantity_rejected_outside_market_buys() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let orders = [
+            // Market sell
+            Order::new(
+                1,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Sell,
+                OrderType::Market,
+                0,
+                None,
+            )
+            .with_quote_quantity(Some(1000)),
+            // Both quantities set
+            Order::new(
+                2,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Market,
+                5,
+                None,
+            )
+            .with_quote_quantity(Some(1000)),
+            // Limit buy
+            Order::new(
+                3,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Limit,
+                0,
+                Some(50000),
+            )
+            .with_quote_quantity(Some(1000)),
+        ];
+
+        for order in orders {
+            match send_order_and_get_response(&order_tx, order) {
+                CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                    assert!(matches!(reason, RejectReason::InvalidQuantity));
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    fn funded_ledger() -> Ledger {
+        let mut ledger = Ledger::new();
+        ledger.deposit(100, "SOL", 10);
+        ledger.deposit(200, "USDC", 1_000_000);
+        ledger
+    }
+
+    #[test]
+    fn test_ledger_locks_settles_and_releases() {
+        let mut engine = test_engine("SOL_USDC").with_ledger(funded_ledger());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        // Locks 4 * 51000 but only pays 4 * 50000
+        let buy = Order::new(
+            2,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            4,
+            Some(51000),
+        );
+        match send_order_and_get_response(&order_tx, buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. }) => {}
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let cancel = CancelOrder::new(1, 100, "SOL_USDC".to_string());
+        let _ = send_cancel_and_get_response(&order_tx, cancel);
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+        let ledger = engine.ledger().unwrap();
+
+        assert_eq!(
+            ledger.balance(100, "SOL"),
+            Balance {
+                available: 6,
+                locked: 0
+            }
+        );
+        assert_eq!(
+            ledger.balance(100, "USDC"),
+            Balance {
+                available: 200_000,
+                locked: 0
+            }
+        );
+        assert_eq!(
+            ledger.balance(200, "SOL"),
+            Balance {
+                available: 4,
+                locked: 0
+            }
+        );
+        assert_eq!(
+            ledger.balance(200, "USDC"),
+            Balance {
+                available: 800_000,
+                locked: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_fees_charged_on_fills_and_settled() {
+        // Makers get a 2 bps rebate, takers pay 10 bps
+        let mut engine = test_engine("SOL_USDC")
+            .with_ledger(funded_ledger())
+            .with_fee_schedule("SOL_USDC", FeeSchedule::new(-2, 10));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 100, "SOL_USDC", Side::Sell, 10, 50000),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 200, "SOL_USDC", Side::Buy, 4, 50000),
+        );
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+
+        let fills: Vec<Fill> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Fill(fill) => Some(fill),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills.len(), 2);
+
+        // The seller receives 200,000 USDC and a 40 USDC rebate on it
+        assert_eq!(fills[0].order_id, 1);
+        assert_eq!(fills[0].liquidity, Liquidity::Maker);
+        assert_eq!(fills[0].fee, -40);
+        assert_eq!(fills[0].fee_asset, "USDC");
+
+        // The buyer receives 4 SOL, and 10 bps of it rounds up to 1
+        assert_eq!(fills[1].order_id, 2);
+        assert_eq!(fills[1].liquidity, Liquidity::Taker);
+        assert_eq!(fills[1].fee, 1);
+        assert_eq!(fills[1].fee_asset, "SOL");
+
+        let ledger = engine.ledger().unwrap();
+        assert_eq!(ledger.balance(100, "USDC").available, 200_040);
+        assert_eq!(ledger.balance(200, "SOL").available, 3);
+        assert_eq!(ledger.balance(200, "USDC").available, 800_000);
+    }
+
+    #[test]
+    fn test_trade_carries_maker_and_taker_fees() {
+        let mut engine =
+            test_engine("SOL_USDC").with_fee_schedule("SOL_USDC", FeeSchedule::new(1, 5));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Buy, 20_000, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 2, "SOL_USDC", Side::Sell, 20_000, 100),
+        );
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let trade = event_rx
+            .try_iter()
+            .find_map(|event| match event {
+                Event::Trade(trade) => Some(trade),
+                _ => None,
+            })
+            .expect("Expected a trade");
+
+        // 1 bps of the 20,000 SOL the buyer gets, 5 bps of the 2,000,000
+        // USDC the seller gets
+        assert_eq!(trade.maker_fee, 2);
+        assert_eq!(trade.maker_fee_asset, "SOL");
+        assert_eq!(trade.taker_fee, 1_000);
+        assert_eq!(trade.taker_fee_asset, "USDC");
+    }
+
+    #[test]
+    fn test_ledger_keeps_resting_remainder_locked() {
+        let mut engine = test_engine("SOL_USDC").with_ledger(funded_ledger());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            3,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        // Buys 3, the other 5 rest locked at 50000
+        let buy = Order::new(
+            2,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            8,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+        let ledger = engine.ledger().unwrap();
+
+        assert_eq!(
+            ledger.balance(200, "USDC"),
+            Balance {
+                available: 600_000,
+                locked: 250_000
+            }
+        );
+        assert_eq!(
+            ledger.balance(100, "SOL"),
+            Balance {
+                available: 7,
+                locked: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_ledger_rejects_uncovered_orders() {
+        let mut engine = test_engine("SOL_USDC").with_ledger(funded_ledger());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        let orders = [
+            // More SOL than user 100 has left
+            Order::new(
+                2,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Sell,
+                OrderType::Limit,
+                1,
+                Some(60000),
+            ),
+            // 21 * 50000 is more USDC than user 200 has
+            Order::new(
+                3,
+                200,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Limit,
+                21,
+                Some(50000),
+            ),
+            // Market buys are costed against the asks
+            Order::new(
+                4,
+                200,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Market,
+                10,
+                None,
+            ),
+        ];
+
+        let mut responses = Vec::new();
+        for order in orders {
+            responses.push(send_order_and_get_response(&order_tx, order));
+        }
+
+        for response in &responses[..2] {
+            match response {
+                CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                    assert!(matches!(reason, RejectReason::InsufficientBalance));
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            responses[2],
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+        ));
+
+        // Rejected orders never reach the book
+        let mut trades = Vec::new();
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            if let Event::Trade(t) = event {
+                trades.push(t.taker_order_id);
+            }
+        }
+        assert_eq!(trades, vec![4]);
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+        let ledger = engine.ledger().unwrap();
+
+        assert_eq!(ledger.balance(200, "USDC").available, 500_000);
+        assert_eq!(ledger.balance(200, "SOL").available, 10);
+    }
+
+    #[test]
+    fn test_deposit_funds_orders() {
+        let mut engine = test_engine("SOL_USDC").with_ledger(Ledger::new());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let buy = || {
+            Order::new(
+                1,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Limit,
+                2,
+                Some(50000),
+            )
+        };
+        assert!(matches!(
+            send_order_and_get_response(&order_tx, buy()),
+            CommandResponse::PlaceOrder(OrderResponse::Reject {
+                reason: RejectReason::InsufficientBalance,
+                ..
+            })
+        ));
+
+        for _ in 0..2 {
+            let _ = send_deposit_and_get_response(&order_tx, 100, "USDC", 60_000);
+        }
+        match send_deposit_and_get_response(&order_tx, 100, "USDC", 1) {
+            CommandResponse::Deposit(DepositResponse::Ack { available, .. }) => {
+                assert_eq!(available, 120_001);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+        assert!(matches!(
+            send_order_and_get_response(&order_tx, buy()),
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+        ));
+
+        for (asset, amount, expected) in [
+            ("BTC", 1, RejectReason::InvalidOrder),
+            ("USDC", 0, RejectReason::InvalidQuantity),
+        ] {
+            match send_deposit_and_get_response(&order_tx, 100, asset, amount) {
+                CommandResponse::Deposit(DepositResponse::Reject { reason, .. }) => {
+                    assert_eq!(reason, expected);
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+        assert_eq!(
+            engine.ledger().unwrap().balance(100, "USDC"),
+            Balance {
+                available: 20_001,
+                locked: 100_000
+            }
+        );
+
+        // Without a ledger there is nothing to credit
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+        let handle = std::thread::spawn(move || engine.run(order_rx, event_tx));
+
+        assert!(matches!(
+            send_deposit_and_get_response(&order_tx, 100, "USDC", 1),
+            CommandResponse::Deposit(DepositResponse::Reject { .. })
+        ));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_ledger_amend_relocks_funds() {
+        let mut engine = test_engine("SOL_USDC").with_ledger(funded_ledger());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let buy = Order::new(
+            1,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        // 10 * 110000 is more than the deposit
+        let amend = AmendOrder::new(1, 200, "SOL_USDC".to_string(), Some(110000), None);
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::InsufficientBalance));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        let amend = AmendOrder::new(1, 200, "SOL_USDC".to_string(), Some(40000), Some(5));
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Ack { .. }) => {}
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+
+        assert_eq!(
+            engine.ledger().unwrap().balance(200, "USDC"),
+            Balance {
+                available: 800_000,
+                locked: 200_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_ledger_amend_locks_at_post_only_slide_price() {
+        let mut engine = test_engine("SOL_USDC").with_ledger(funded_ledger());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let sell = limit_order(1, 100, "SOL_USDC", Side::Sell, 10, 60000);
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        let buy = limit_order(2, 200, "SOL_USDC", Side::Buy, 10, 50000)
+            .with_post_only(Some(PostOnly::Reprice));
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        // Amending through the ask slides the buy to one tick below it
+        let amend = AmendOrder::new(2, 200, "SOL_USDC".to_string(), Some(70000), None);
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Ack { price, .. }) => {
+                assert_eq!(price, 59999);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+
+        assert_eq!(
+            engine.ledger().unwrap().balance(200, "USDC"),
+            Balance {
+                available: 400_010,
+                locked: 599_990
+            }
+        );
+    }
+
+    #[test]
+    fn test_ledger_shrinks_lock_of_decremented_order() {
+        let mut ledger = funded_ledger();
+        ledger.deposit(200, "SOL", 10);
+        let mut engine = test_engine("SOL_USDC").with_ledger(ledger);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let buy = limit_order(1, 200, "SOL_USDC", Side::Buy, 10, 50000);
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        // The buy is decremented by 4 and the sell cancelled, nothing trades
+        let sell = limit_order(2, 200, "SOL_USDC", Side::Sell, 4, 50000)
+            .with_self_trade_prevention(Some(SelfTradePrevention::DecrementAndCancel));
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+        let ledger = engine.ledger().unwrap();
+
+        assert_eq!(
+            ledger.balance(200, "USDC"),
+            Balance {
+                available: 700_000,
+                locked: 300_000
+            }
+        );
+        assert_eq!(
+            ledger.balance(200, "SOL"),
+            Balance {
+                available: 10,
+                locked: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_ledger_market_buy_stops_at_locked_funds() {
+        let mut ledger = funded_ledger();
+        ledger.deposit(200, "SOL", 10);
+        let mut engine = test_engine("SOL_USDC").with_ledger(ledger);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        let own_sell = limit_order(1, 200, "SOL_USDC", Side::Sell, 10, 50000);
+        let _ = send_order_and_get_response(&order_tx, own_sell);
+        let sell = limit_order(2, 100, "SOL_USDC", Side::Sell, 10, 60000);
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        // Locks 10 at 50000, but self-trade prevention takes the user's own
+        // ask out of the way and the buy can only afford 8 at 60000
+        let buy = Order::new(
+            3,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            10,
+            None,
+        )
+        .with_self_trade_prevention(Some(SelfTradePrevention::CancelOldest));
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+
+        let filled: u64 = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Trade(trade) if trade.taker_order_id == 3 => Some(trade.quantity),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(filled, 8);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::OrderCancelled(c)
+                if c.order_id == 3 && c.reason == CancelReason::InsufficientBalance
+        )));
+
+        let ledger = engine.ledger().unwrap();
+        assert_eq!(
+            ledger.balance(200, "USDC"),
+            Balance {
+                available: 520_000,
+                locked: 0
+            }
+        );
+        assert_eq!(
+            ledger.balance(200, "SOL"),
+            Balance {
+                available: 18,
+                locked: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_ledger_settle_fails_on_fill_beyond_lock() {
+        let mut ledger = funded_ledger();
+        ledger.reserve(1, 200, "USDC", 100_000).unwrap();
+
+        let result = MatchResult {
+            fills: vec![crate::orderbook::types::Fill {
+                order_id: 1,
+                user_id: 200,
+                side: Side::Buy,
+                filled_price: 60000,
+                filled_quantity: 2,
+                remaining_quantity: 0,
+                liquidity: Liquidity::Taker,
+                fee: 0,
+                fee_asset: "SOL".to_string(),
+            }],
+            ..MatchResult::default()
+        };
+
+        assert!(ledger.settle("SOL", "USDC", &result).is_err());
+        // The shortfall is not taken from available
+        assert_eq!(
+            ledger.balance(200, "USDC"),
+            Balance {
+                available: 900_000,
+                locked: 0
+            }
+        );
+    }
+
+    fn sol_usdc_spec() -> InstrumentSpec {
+        InstrumentSpec::new("SOL_USDC")
+            .with_price_rules(100, 1000, 1_000_000)
+            .with_quantity_rules(5, 10, 10_000)
+    }
+
+    #[test]
+    fn test_instrument_spec_rejects() {
+        let mut engine = Engine::with_instruments([sol_usdc_spec()]);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let limit = |order_id, quantity, price| {
+            Order::new(
+                order_id,
+                100,
+                "SOL_USDC".to_string(),
+                Side::Buy,
+                OrderType::Limit,
+                quantity,
+                Some(price),
+            )
+        };
+
+        let cases = [
+            // Off the tick grid
+            (limit(1, 10, 50050), RejectReason::InvalidPrice),
+            // Above the max price
+            (limit(2, 10, 2_000_000), RejectReason::InvalidPrice),
+            // Below the min price
+            (limit(3, 10, 500), RejectReason::InvalidPrice),
+            // Off the lot grid
+            (limit(4, 12, 50000), RejectReason::InvalidQuantity),
+            // Below the min quantity
+            (limit(5, 5, 50000), RejectReason::InvalidQuantity),
+            // Above the max quantity
+            (limit(6, 10_005, 50000), RejectReason::InvalidQuantity),
+            // Stop price off the tick grid
+            (
+                Order::new(
+                    7,
+                    100,
+                    "SOL_USDC".to_string(),
+                    Side::Buy,
+                    OrderType::StopMarket,
+                    10,
+                    None,
+                )
+                .with_stop_price(Some(50001)),
+                RejectReason::InvalidPrice,
+            ),
+        ];
+
+        for (order, expected) in cases {
+            let order_id = order.order_id;
+            match send_order_and_get_response(&order_tx, order) {
+                CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                    assert_eq!(
+                        reason.to_string(),
+                        expected.to_string(),
+                        "order {}",
+                        order_id
+                    );
+                }
+                other => panic!("Expected Reject for {}, got {:?}", order_id, other),
+            }
+        }
+
+        match send_order_and_get_response(&order_tx, limit(8, 15, 50100)) {
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. }) => {}
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let amends = [
+            (
+                AmendOrder::new(8, 100, "SOL_USDC".to_string(), Some(50150), None),
+                RejectReason::InvalidPrice,
+            ),
+            (
+                AmendOrder::new(8, 100, "SOL_USDC".to_string(), None, Some(17)),
+                RejectReason::InvalidQuantity,
+            ),
+        ];
+
+        for (amend, expected) in amends {
+            match send_amend_and_get_response(&order_tx, amend) {
+                CommandResponse::AmendOrder(AmendOrderResponse::Reject { reason, .. }) => {
+                    assert_eq!(reason.to_string(), expected.to_string());
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_quote_quantity_rounds_down_to_lot() {
+        let mut engine = Engine::with_instruments([sol_usdc_spec()]);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            100,
+            Some(1000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        // 23000 buys 23, rounded down to 20
+        let buy = Order::new(
+            2,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            0,
+            None,
+        )
+        .with_quote_quantity(Some(23000));
+        let _ = send_order_and_get_response(&order_tx, buy);
+
+        let mut traded = 0;
+        while let Ok(event) = event_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            if let Event::Trade(t) = event {
+                traded += t.quantity;
+            }
+        }
+        assert_eq!(traded, 20);
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    /// Runs the same orders and cancels on a tree book and on a ladder book
+    /// and returns the events of each.
+    fn run_on_both_layouts() -> (Vec<String>, Vec<String>) {
+        let run = |engine: Engine| {
+            let mut engine = engine.with_clock(ManualClock::new(1_700_000_000_000));
+            let (order_tx, order_rx) =
+                crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+            let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+            let handle = std::thread::spawn(move || {
+                engine.run(order_rx, event_tx);
+            });
+
+            let orders = [
+                limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 50000),
+                limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 50200),
+                limit_order(3, 2, "SOL_USDC", Side::Sell, 20, 50500),
+                limit_order(4, 2, "SOL_USDC", Side::Sell, 15, 50100),
+                limit_order(5, 3, "SOL_USDC", Side::Buy, 10, 49900),
+                limit_order(6, 3, "SOL_USDC", Side::Buy, 10, 49500),
+                limit_order(7, 4, "SOL_USDC", Side::Buy, 30, 50200),
+            ];
+            for order in orders {
+                let _ = send_order_and_get_response(&order_tx, order);
+            }
+
+            let _ = send_cancel_and_get_response(
+                &order_tx,
+                CancelOrder::new(3, 2, "SOL_USDC".to_string()),
+            );
+            let _ = send_order_and_get_response(
+                &order_tx,
+                limit_order(8, 1, "SOL_USDC", Side::Sell, 15, 49500),
+            );
+            let depth = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+
+            drop(order_tx);
+            handle.join().unwrap();
+
+            event_rx
+                .try_iter()
+                .map(|event| format!("{:?}", event))
+                .chain([format!("{:?}", depth)])
+                .collect()
+        };
+
+        let spec = || sol_usdc_spec().with_quantity_rules(5, 5, 10_000);
+        let tree = run(Engine::with_instruments([spec()]));
+        let ladder = run(Engine::with_instruments([spec()]).with_price_ladder("SOL_USDC"));
+        (tree, ladder)
+    }
+
+    #[test]
+    fn test_price_ladder_matches_like_tree() {
+        let (tree, ladder) = run_on_both_layouts();
+        assert!(tree.iter().any(|event| event.starts_with("Trade")));
+        assert_eq!(tree, ladder);
+    }
+
+    #[test]
+    fn test_price_ladder_feature_starts_test_books_on_ladders() {
+        let book = serde_json::to_value(OrderBook::new(test_spec("SOL_USDC"))).unwrap();
+        let on_ladder = |side: &str| book[side].get("Ladder").is_some();
+
+        assert_eq!(on_ladder("asks"), cfg!(feature = "price-ladder"));
+        assert_eq!(on_ladder("bids"), cfg!(feature = "price-ladder"));
+    }
+
+    #[test]
+    fn test_price_ladder_spans_spec_price_range() {
+        // A ladder has a slot per tick of the price range, up to 2^20 of them
+        let spec = |max_price| InstrumentSpec::new("SOL_USDC").with_price_rules(1, 1, max_price);
+        assert!(OrderBook::new(spec(1 << 20)).use_price_ladder());
+        assert!(!OrderBook::new(spec((1 << 20) + 1)).use_price_ladder());
+        assert!(!OrderBook::new(InstrumentSpec::new("SOL_USDC")).use_price_ladder());
+
+        let mut engine = Engine::with_instruments([sol_usdc_spec()]).with_price_ladder("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // Both ends of the range rest on either side, however far apart
+        let orders = [
+            limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 1000),
+            limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 1_000_000),
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 10, 999_900),
+            limit_order(4, 3, "SOL_USDC", Side::Sell, 20, 1000),
+        ];
+        for order in orders {
+            let order_id = order.order_id;
+            match send_order_and_get_response(&order_tx, order) {
+                CommandResponse::PlaceOrder(OrderResponse::Ack { .. }) => {}
+                other => panic!("Unexpected response to order {}: {:?}", order_id, other),
+            }
+        }
+
+        let trades: Vec<_> = std::iter::from_fn(|| {
+            event_rx
+                .recv_timeout(std::time::Duration::from_millis(200))
+                .ok()
+        })
+        .filter_map(|event| match event {
+            Event::Trade(t) => Some((t.maker_order_id, t.price)),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(trades, vec![(3, 999_900), (1, 1000)]);
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_price_ladder_finds_best_levels_across_chunks() {
+        // 1000 to 1_000_000 in ticks of 100 spans several chunks of slots
+        let mut engine = Engine::with_instruments([sol_usdc_spec()]).with_price_ladder("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for order in [
+            limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 1000),
+            limit_order(2, 1, "SOL_USDC", Side::Buy, 10, 500_000),
+            limit_order(3, 1, "SOL_USDC", Side::Buy, 10, 999_900),
+        ] {
+            let _ = send_order_and_get_response(&order_tx, order);
+        }
+
+        // Cancelling the best bids frees their chunks and moves the best bid
+        // down past the empty ones
+        for order_id in [3, 2] {
+            let _ = send_cancel_and_get_response(
+                &order_tx,
+                CancelOrder::new(order_id, 1, "SOL_USDC".to_string()),
+            );
+        }
+        for order in [
+            limit_order(4, 2, "SOL_USDC", Side::Sell, 10, 1000),
+            limit_order(5, 2, "SOL_USDC", Side::Sell, 10, 1_000_000),
+            limit_order(6, 3, "SOL_USDC", Side::Buy, 10, 1_000_000),
+        ] {
+            let _ = send_order_and_get_response(&order_tx, order);
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let trades: Vec<_> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(t) => Some((t.maker_order_id, t.price)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trades, vec![(1, 1000), (5, 1_000_000)]);
+    }
+
+    #[test]
+    fn test_price_ladder_holds_repriced_and_trailed_prices() {
+        let on_ladder = |book: &OrderBook| {
+            let book = serde_json::to_value(book).unwrap();
+            book["asks"].get("Ladder").is_some() && book["bids"].get("Ladder").is_some()
+        };
+
+        let ladder_book = || {
+            let mut book = OrderBook::new(sol_usdc_spec());
+            assert!(book.use_price_ladder());
+            book
+        };
+
+        // Repricing behind an ask at the lowest price would leave the range
+        let mut book = ladder_book();
+        let mut ask = OrderEntry::new(1, 1, Side::Sell, 1000, 10, TimeInForce::GTC, None);
+        book.match_limit_order(&mut ask, 0).unwrap();
+        let mut post_only = OrderEntry::new(2, 2, Side::Buy, 1100, 10, TimeInForce::GTC, None)
+            .with_post_only(Some(PostOnly::Reprice));
+        assert!(matches!(
+            book.pre_trade_check(&mut post_only, &OrderType::Limit, Some(1100), 0),
+            Err(OrderBookError::PostOnlyWouldCross(_))
+        ));
+
+        // 1.5% of 60_100 is off the tick grid, the trailed limit is not
+        let mut book = ladder_book();
+        for (order_id, price) in [(3, 60_100), (4, 59_100)] {
+            let mut bid =
+                OrderEntry::new(order_id, 3, Side::Buy, price, 10, TimeInForce::GTC, None);
+            book.match_limit_order(&mut bid, 0).unwrap();
+        }
+        let stop = OrderEntry::new(5, 4, Side::Sell, 58_900, 10, TimeInForce::GTC, None);
+        book.add_stop_order(
+            stop,
+            OrderType::StopLimit,
+            59_000,
+            Some(TrailingOffset::Percentage(150)),
+        );
+
+        let mut sell = OrderEntry::new(6, 5, Side::Sell, 60_100, 5, TimeInForce::GTC, None);
+        let mut result = book.match_limit_order(&mut sell, 0).unwrap();
+        book.activate_stops(&mut result, 0);
+        let update = &result.stop_updates[0];
+        assert_eq!((update.stop_price, update.price), (59_199, Some(59_000)));
+
+        // Trading through the stop rests its limit on the ladder
+        let mut sell = OrderEntry::new(7, 5, Side::Sell, 59_100, 15, TimeInForce::GTC, None);
+        let mut result = book.match_limit_order(&mut sell, 0).unwrap();
+        book.activate_stops(&mut result, 0);
+        assert_eq!(book.get_best_ask(), Some(59_000));
+        assert!(on_ladder(&book));
+    }
+
+    #[test]
+    fn test_get_exchange_info() {
+        let mut engine =
+            Engine::with_instruments([sol_usdc_spec(), InstrumentSpec::new("BTC_USDC")]);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        order_tx
+            .send((OrderCommand::GetExchangeInfo, reply_tx))
+            .unwrap();
+        let response = std::thread::spawn(move || runtime::RUNTIME.block_on(reply_rx))
+            .join()
+            .unwrap()
+            .unwrap();
+
+        match response {
+            CommandResponse::ExchangeInfo(info) => {
+                assert_eq!(
+                    info.symbols,
+                    vec![InstrumentSpec::new("BTC_USDC"), sol_usdc_spec()]
+                );
+            }
+            other => panic!("Expected ExchangeInfo, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_halt_rejects_orders_and_accepts_cancels() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let sell = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            50,
+            Some(50000),
+        );
+        let _ = send_order_and_get_response(&order_tx, sell);
+
+        match send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Halted) {
+            CommandResponse::SessionState(SessionStateResponse::Ack {
+                previous, state, ..
+            }) => {
+                assert_eq!(previous, SessionState::Continuous);
+                assert_eq!(state, SessionState::Halted);
+            }
+            other => panic!("Expected Ack, got {:?}", other),
+        }
+
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        let changed = event_rx
+            .try_iter()
+            .find_map(|event| match event {
+                Event::SessionStateChanged(changed) => Some(changed),
+                _ => None,
+            })
+            .expect("Expected SessionStateChanged");
+        assert_eq!(changed.symbol, "SOL_USDC");
+        assert_eq!(changed.previous, SessionState::Continuous);
+        assert_eq!(changed.state, SessionState::Halted);
+
+        // New orders and amends are refused, the resting order is untouched
+        let buy = Order::new(
+            2,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            50,
+            Some(50000),
+        );
+        match send_order_and_get_response(&order_tx, buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::MarketClosed));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        let amend = AmendOrder::new(1, 100, "SOL_USDC".to_string(), Some(49000), None);
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::MarketClosed));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => assert_eq!(depth.asks[0], (50000, 50)),
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        let cancel = CancelOrder::new(1, 100, "SOL_USDC".to_string());
+        assert!(matches!(
+            send_cancel_and_get_response(&order_tx, cancel),
+            CommandResponse::CancelOrder(CancelOrderResponse::Ack { .. })
+        ));
+
+        // A halt is only lifted through a call auction
+        match send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Continuous) {
+            CommandResponse::SessionState(SessionStateResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::InvalidOrder));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        // Resuming lets orders in again
+        let _ = send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Auction);
+        let _ =
+            send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Continuous);
+        let buy = Order::new(
+            3,
+            200,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            50,
+            Some(50000),
+        );
+        assert!(matches!(
+            send_order_and_get_response(&order_tx, buy),
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+        ));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_session_state_rejects() {
+        let mut engine = test_engine_with_symbols(["SOL_USDC", "BTC_USDC"])
+            .with_session_state("BTC_USDC", SessionState::PreOpen);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let buy = Order::new(
+            1,
+            100,
+            "BTC_USDC".to_string(),
+            Side::Buy,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        );
+        match send_order_and_get_response(&order_tx, buy) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::MarketClosed));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        let rejects = [
+            ("SOL_USDC", SessionState::Continuous),
+            ("SOL_USDC", SessionState::PreOpen),
+            ("BTC_USDC", SessionState::Continuous),
+            ("ETH_USDC", SessionState::Halted),
+        ];
+        for (symbol, state) in rejects {
+            match send_session_state_and_get_response(&order_tx, symbol, state) {
+                CommandResponse::SessionState(SessionStateResponse::Reject { reason, .. }) => {
+                    if symbol == "ETH_USDC" {
+                        assert!(matches!(reason, RejectReason::SymbolNotFound));
+                    } else {
+                        assert!(matches!(reason, RejectReason::InvalidOrder));
+                    }
+                }
+                other => panic!("Expected Reject, got {:?}", other),
+            }
+        }
+
+        assert!(
+            !event_rx
+                .try_iter()
+                .any(|event| matches!(event, Event::SessionStateChanged(_)))
+        );
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    fn limit_order(
+        order_id: u64,
+        user_id: u64,
+        symbol: &str,
+        side: Side,
+        qty: u64,
+        price: u64,
+    ) -> Order {
+        Order::new(
+            order_id,
+            user_id,
+            symbol.to_string(),
+            side,
+            OrderType::Limit,
+            qty,
+            Some(price),
+        )
+    }
+
+    #[test]
+    fn test_auction_collects_orders_and_uncrosses_at_max_volume() {
+        let mut engine =
+            test_engine("SOL_USDC").with_session_state("SOL_USDC", SessionState::PreOpen);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Auction);
+
+        let orders = [
+            limit_order(1, 1, "SOL_USDC", Side::Buy, 100, 101),
+            limit_order(2, 1, "SOL_USDC", Side::Buy, 50, 100),
+            limit_order(3, 2, "SOL_USDC", Side::Sell, 80, 99),
+            limit_order(4, 3, "SOL_USDC", Side::Sell, 50, 100),
+        ];
+        for order in orders {
+            assert!(matches!(
+                send_order_and_get_response(&order_tx, order),
+                CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+            ));
+        }
+
+        // Nothing trades during the call, the indication follows the book.
+        // The depth request returns once the engine has sent every event.
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        let call_events: Vec<Event> = event_rx.try_iter().collect();
+        assert!(
+            !call_events
+                .iter()
+                .any(|event| matches!(event, Event::Trade(_) | Event::Fill(_)))
+        );
+
+        let indicative = call_events
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                Event::IndicativePrice(indicative) => Some(indicative.clone()),
+                _ => None,
+            })
+            .expect("Expected IndicativePrice");
+        assert_eq!(indicative.price, Some(100));
+        assert_eq!(indicative.volume, 130);
+        assert_eq!(indicative.imbalance, 20);
+        assert_eq!(indicative.imbalance_side, Some(Side::Buy));
+
+        let _ =
+            send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Continuous);
+
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        let trades: Vec<_> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some(trade),
+                _ => None,
+            })
+            .collect();
+
+        let matched: Vec<_> = trades
+            .iter()
+            .map(|t| (t.maker_order_id, t.taker_order_id, t.quantity, t.price))
+            .collect();
+        assert_eq!(
+            matched,
+            vec![(1, 3, 80, 100), (1, 4, 20, 100), (2, 4, 30, 100)]
+        );
+
+        match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => {
+                assert_eq!(depth.bids[0], (100, 20));
+                assert_eq!(depth.asks[0], (0, 0));
+            }
+            other => panic!("Expected Depth, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_auction_maker_is_the_order_queued_first() {
+        let mut engine =
+            test_engine("SOL_USDC").with_fee_schedule("SOL_USDC", FeeSchedule::new(1, 5));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // Rests before the call and is amended to a new price, keeping its id
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(8, 1, "SOL_USDC", Side::Buy, 20_000, 99),
+        );
+        assert!(matches!(
+            send_amend_and_get_response(
+                &order_tx,
+                AmendOrder::new(8, 1, "SOL_USDC".to_string(), Some(100), None),
+            ),
+            CommandResponse::AmendOrder(AmendOrderResponse::Ack { .. })
+        ));
+
+        let _ = send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Halted);
+        let _ = send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Auction);
+
+        // Joins the call afterwards with a lower id, as restored or replayed
+        // ids can be
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(4, 2, "SOL_USDC", Side::Sell, 20_000, 100),
+        );
+        let _ =
+            send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Continuous);
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let trade = event_rx
+            .try_iter()
+            .find_map(|event| match event {
+                Event::Trade(trade) => Some(trade),
+                _ => None,
+            })
+            .expect("Expected a trade");
+
+        assert_eq!((trade.maker_order_id, trade.taker_order_id), (8, 4));
+        // Maker rate on the 20,000 SOL the buyer gets, taker rate on the
+        // 2,000,000 USDC the seller gets
+        assert_eq!(trade.maker_fee, 2);
+        assert_eq!(trade.maker_fee_asset, "SOL");
+        assert_eq!(trade.taker_fee, 1_000);
+        assert_eq!(trade.taker_fee_asset, "USDC");
+    }
+
+    /// Places `orders` during an auction call on SOL_USDC, opens continuous
+    /// trading and returns the events of the uncross.
+    fn run_auction_uncross(engine: Engine, orders: Vec<Order>) -> Vec<Event> {
+        let mut engine = engine.with_session_state("SOL_USDC", SessionState::PreOpen);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Auction);
+        for order in orders {
+            assert!(matches!(
+                send_order_and_get_response(&order_tx, order),
+                CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+            ));
+        }
+        let _ = event_rx.try_iter().count();
+
+        let _ =
+            send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Continuous);
+
+        drop(order_tx);
+        handle.join().unwrap();
+        event_rx.try_iter().collect()
+    }
+
+    fn auction_trades(events: &[Event]) -> Vec<(u64, u64, u64, u64)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Trade(t) => Some((t.maker_order_id, t.taker_order_id, t.quantity, t.price)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_auction_uncross_cancels_newest_self_trade() {
+        let events = run_auction_uncross(
+            test_engine("SOL_USDC"),
+            vec![
+                limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 100),
+                limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 100),
+                limit_order(3, 2, "SOL_USDC", Side::Sell, 10, 100),
+            ],
+        );
+
+        assert_eq!(self_trade_cancels(&events), vec![2]);
+        assert_eq!(auction_trades(&events), vec![(1, 3, 10, 100)]);
+    }
+
+    #[test]
+    fn test_auction_uncross_decrements_self_trade() {
+        let events = run_auction_uncross(
+            test_engine("SOL_USDC")
+                .with_self_trade_prevention("SOL_USDC", SelfTradePrevention::DecrementAndCancel),
+            vec![
+                limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 100),
+                limit_order(2, 1, "SOL_USDC", Side::Sell, 4, 100),
+                limit_order(3, 2, "SOL_USDC", Side::Sell, 6, 100),
+            ],
+        );
+
+        // The smaller order is used up and cancelled, the other shrinks by as
+        // much and trades the rest
+        assert_eq!(self_trade_cancels(&events), vec![2]);
+        let amended: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::OrderAmended(a) => Some((a.order_id, a.quantity, a.remaining_quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(amended, vec![(1, 6, 6)]);
+        assert_eq!(auction_trades(&events), vec![(1, 3, 6, 100)]);
+    }
+
+    #[test]
+    fn test_auction_uncrosses_again_after_self_trade_prevention() {
+        // The auction price of 100 counts on order 2, which self-trade
+        // prevention cancels, leaving 3 and 4 crossed at 101
+        let buy = limit_order(3, 1, "SOL_USDC", Side::Buy, 10, 101)
+            .with_self_trade_prevention(Some(SelfTradePrevention::CancelOldest));
+        let events = run_auction_uncross(
+            test_engine("SOL_USDC"),
+            vec![
+                limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 100),
+                buy,
+                limit_order(4, 2, "SOL_USDC", Side::Sell, 10, 101),
+            ],
+        );
+
+        assert_eq!(self_trade_cancels(&events), vec![2]);
+        assert_eq!(auction_trades(&events), vec![(3, 4, 10, 101)]);
+    }
+
+    #[test]
+    fn test_auction_price_tie_breakers() {
+        let mut engine = test_engine_with_symbols(["SOL_USDC", "BTC_USDC"])
+            .with_session_state("SOL_USDC", SessionState::PreOpen)
+            .with_session_state("BTC_USDC", SessionState::PreOpen);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_session_state_and_get_response(&order_tx, "SOL_USDC", SessionState::Auction);
+        let _ = send_session_state_and_get_response(&order_tx, "BTC_USDC", SessionState::Auction);
+
+        // Market orders cannot join the call
+        let market = Order::new(
+            1,
+            1,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            10,
+            None,
+        );
+        match send_order_and_get_response(&order_tx, market) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::MarketClosed));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        // Same volume and imbalance at 100 and 102, buyers left over at both
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 1, "SOL_USDC", Side::Buy, 100, 102),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 2, "SOL_USDC", Side::Sell, 60, 100),
+        );
+
+        // Balanced at 101 and 102 with no reference price
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(4, 1, "BTC_USDC", Side::Buy, 100, 102),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(5, 2, "BTC_USDC", Side::Sell, 50, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(6, 3, "BTC_USDC", Side::Sell, 50, 101),
+        );
+
+        let _ = send_get_depth_and_get_response(&order_tx, "BTC_USDC", 5);
+        let mut latest = std::collections::HashMap::new();
+        for event in event_rx.try_iter() {
+            if let Event::IndicativePrice(indicative) = event {
+                latest.insert(indicative.symbol.clone(), indicative);
+            }
+        }
+
+        assert_eq!(latest["SOL_USDC"].price, Some(102));
+        assert_eq!(latest["SOL_USDC"].volume, 60);
+        assert_eq!(latest["BTC_USDC"].price, Some(101));
+        assert_eq!(latest["BTC_USDC"].volume, 100);
+        assert_eq!(latest["BTC_USDC"].imbalance_side, None);
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_price_band_rejects_limit_orders_outside_band() {
+        let mut engine = test_engine("SOL_USDC").with_price_band(
+            "SOL_USDC",
+            1_000,
+            std::time::Duration::from_secs(60),
+            std::time::Duration::from_secs(60),
+        );
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // No band before the first trade sets a reference price
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 2, "SOL_USDC", Side::Buy, 10, 100),
+        );
+
+        match send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 10, 111),
+        ) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::PriceOutsideBand));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        assert!(matches!(
+            send_order_and_get_response(
+                &order_tx,
+                limit_order(4, 2, "SOL_USDC", Side::Buy, 10, 110),
+            ),
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+        ));
+
+        let amend = AmendOrder::new(4, 2, "SOL_USDC".to_string(), Some(89), None);
+        match send_amend_and_get_response(&order_tx, amend) {
+            CommandResponse::AmendOrder(AmendOrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::PriceOutsideBand));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_sweep_outside_band_triggers_volatility_interruption() {
+        let mut engine = test_engine("SOL_USDC").with_price_band(
+            "SOL_USDC",
+            1_000,
+            std::time::Duration::from_secs(60),
+            std::time::Duration::from_millis(200),
+        );
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // Rests before the first trade, so no band applies to it yet
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 5, 150),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 10, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(4, 1, "SOL_USDC", Side::Sell, 5, 105),
+        );
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        let _: Vec<Event> = event_rx.try_iter().collect();
+
+        let sweep = Order::new(
+            5,
+            3,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            20,
+            None,
+        );
+        let _ = send_order_and_get_response(&order_tx, sweep);
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+
+        let events: Vec<Event> = event_rx.try_iter().collect();
+        let prices: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some(trade.price),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(prices, vec![105]);
+
+        let cancel = events.iter().find_map(|event| match event {
+            Event::OrderCancelled(cancelled) if cancelled.order_id == 5 => Some(cancelled),
+            _ => None,
+        });
+        assert!(matches!(
+            cancel.map(|c| &c.reason),
+            Some(CancelReason::VolatilityInterruption)
+        ));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Event::SessionStateChanged(changed) if changed.state == SessionState::Auction
+        )));
+
+        let market = Order::new(
+            6,
+            3,
+            "SOL_USDC".to_string(),
+            Side::Buy,
+            OrderType::Market,
+            5,
+            None,
+        );
+        match send_order_and_get_response(&order_tx, market) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert!(matches!(reason, RejectReason::MarketClosed));
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        // Continuous trading resumes once the interruption is over
+        let resumed = std::iter::from_fn(|| {
+            event_rx
+                .recv_timeout(std::time::Duration::from_secs(2))
+                .ok()
+        })
+        .find_map(|event| match event {
+            Event::SessionStateChanged(changed) => Some(changed),
+            _ => None,
+        })
+        .expect("Expected the interruption to end");
+        assert_eq!(resumed.previous, SessionState::Auction);
+        assert_eq!(resumed.state, SessionState::Continuous);
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    /// Rests the `asks` (order id, quantity) at 100 in that order, buys
+    /// `quantity` at 100 and returns the trades (maker order id, quantity)
+    /// plus what is left resting at 100.
+    fn run_level_allocation(
+        algorithm: MatchingAlgorithm,
+        asks: &[(u64, u64)],
+        quantity: u64,
+    ) -> (Vec<(u64, u64)>, u64) {
+        let mut engine = test_engine("SOL_USDC").with_matching_algorithm("SOL_USDC", algorithm);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for &(order_id, ask_quantity) in asks {
+            let _ = send_order_and_get_response(
+                &order_tx,
+                limit_order(order_id, 100, "SOL_USDC", Side::Sell, ask_quantity, 100),
+            );
+        }
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(99, 200, "SOL_USDC", Side::Buy, quantity, 100),
+        );
+
+        let resting = match send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5) {
+            CommandResponse::Depth(depth) => depth.asks[0].1,
+            other => panic!("Expected Depth, got {:?}", other),
+        };
+
+        let trades = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some((trade.maker_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+
+        drop(order_tx);
+        handle.join().unwrap();
+        (trades, resting)
+    }
+
+    #[test]
+    fn test_pro_rata_shares_level_by_size() {
+        let asks = [(1, 10), (2, 30)];
+
+        let (trades, resting) = run_level_allocation(MatchingAlgorithm::Fifo, &asks, 20);
+        assert_eq!(trades, vec![(1, 10), (2, 10)]);
+        assert_eq!(resting, 20);
+
+        let pro_rata = MatchingAlgorithm::ProRata {
+            top_order_priority: false,
+            min_allocation: 0,
+        };
+        let (trades, resting) = run_level_allocation(pro_rata, &asks, 20);
+        assert_eq!(trades, vec![(1, 5), (2, 15)]);
+        assert_eq!(resting, 20);
+
+        // A taker bigger than the level fills everything either way
+        let (trades, resting) = run_level_allocation(pro_rata, &asks, 50);
+        assert_eq!(trades, vec![(1, 10), (2, 30)]);
+        assert_eq!(resting, 0);
+    }
+
+    #[test]
+    fn test_pro_rata_top_order_and_minimum_allocation() {
+        let asks = [(1, 30), (2, 5), (3, 65)];
+
+        // Order 2's share of 1 is under the minimum; the leftover goes to
+        // order 1 in time priority
+        let minimum = MatchingAlgorithm::ProRata {
+            top_order_priority: false,
+            min_allocation: 2,
+        };
+        let (trades, resting) = run_level_allocation(minimum, &asks, 20);
+        assert_eq!(trades, vec![(1, 7), (3, 13)]);
+        assert_eq!(resting, 80);
+
+        // Order 1 fills first in full, 10 is shared between orders 2 and 3
+        let top_order = MatchingAlgorithm::ProRata {
+            top_order_priority: true,
+            min_allocation: 0,
+        };
+        let (trades, resting) = run_level_allocation(top_order, &asks, 40);
+        assert_eq!(trades, vec![(1, 30), (2, 1), (3, 9)]);
+        assert_eq!(resting, 60);
+    }
+
+    #[test]
+    fn test_manual_clock_stamps_trades() {
+        let clock = ManualClock::new(1_700_000_000_000);
+        let mut engine = test_engine("SOL_USDC").with_clock(clock.clone());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 100),
+        );
+        clock.advance(250);
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 2, "SOL_USDC", Side::Buy, 4, 100),
+        );
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+
+        let timestamps: Vec<i64> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some(trade.timestamp),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(timestamps, vec![1_700_000_000_250]);
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_manual_clock_drives_expiry() {
+        let clock = ManualClock::new(1_700_000_000_000);
+        let mut engine = test_engine("SOL_USDC").with_clock(clock.clone());
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        // Far in the past by the wall clock, but ahead of the engine's clock
+        let mut gtd_order = limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 100);
+        gtd_order.time_in_force = TimeInForce::GTD;
+        gtd_order.expire_at = Some(1_700_000_001_000);
+        assert!(matches!(
+            send_order_and_get_response(&order_tx, gtd_order),
+            CommandResponse::PlaceOrder(OrderResponse::Ack { .. })
+        ));
+
+        // Several expiry ticks pass without the clock moving
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        assert!(
+            !event_rx
+                .try_iter()
+                .any(|event| matches!(event, Event::OrderCancelled(_)))
+        );
+
+        clock.set(1_700_000_001_000);
+        let cancelled = std::iter::from_fn(|| {
+            event_rx
+                .recv_timeout(std::time::Duration::from_secs(2))
+                .ok()
+        })
+        .find_map(|event| match event {
+            Event::OrderCancelled(cancelled) => Some(cancelled),
+            _ => None,
+        })
+        .expect("Expected the order to expire");
+        assert_eq!(cancelled.order_id, 1);
+        assert!(matches!(cancelled.reason, CancelReason::Expired));
+
+        drop(order_tx);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_replaying_commands_reproduces_events() {
+        fn run_script() -> Vec<String> {
+            let clock = ManualClock::new(1_700_000_000_000);
+            let mut engine = test_engine("SOL_USDC").with_clock(clock.clone());
+            let (order_tx, order_rx) =
+                crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+            let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+            let handle = std::thread::spawn(move || {
+                engine.run(order_rx, event_tx);
+            });
+
+            let orders = [
+                limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 101),
+                limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 102),
+                limit_order(3, 2, "SOL_USDC", Side::Buy, 15, 102),
+                Order::new(
+                    4,
+                    3,
+                    "SOL_USDC".to_string(),
+                    Side::Sell,
+                    OrderType::Market,
+                    3,
+                    None,
+                ),
+            ];
+            for order in orders {
+                clock.advance(7);
+                let _ = send_order_and_get_response(&order_tx, order);
+            }
+
+            clock.advance(7);
+            let amend = AmendOrder::new(2, 1, "SOL_USDC".to_string(), Some(103), None);
+            let _ = send_amend_and_get_response(&order_tx, amend);
+            let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+
+            drop(order_tx);
+            handle.join().unwrap();
+
+            event_rx
+                .try_iter()
+                .map(|event| format!("{:?}", event))
+                .collect()
+        }
+
+        let first = run_script();
+        assert!(first.iter().any(|event| event.starts_with("Trade")));
+        assert_eq!(first, run_script());
+    }
+
+    fn journal_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "engine-core-{}-{}.journal",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_journal_replay_restores_books() {
+        let path = journal_path("replay");
+        let clock = ManualClock::new(1_700_000_000_000);
+
+        let (journal, entries) = Journal::open(&path).unwrap();
+        assert!(entries.is_empty());
+
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock.clone())
+            .with_journal(journal);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let orders = [
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 101),
+            limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 102),
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 4, 101),
+            limit_order(4, 2, "SOL_USDC", Side::Buy, 8, 99),
+        ];
+        for order in orders {
+            clock.advance(5);
+            let _ = send_order_and_get_response(&order_tx, order);
+        }
+
+        let amend = AmendOrder::new(2, 1, "SOL_USDC".to_string(), Some(103), Some(6));
+        let _ = send_amend_and_get_response(&order_tx, amend);
+        let cancel = CancelOrder::new(4, 2, "SOL_USDC".to_string());
+        let _ = send_cancel_and_get_response(&order_tx, cancel);
+
+        let depth_before = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        drop(order_tx);
+        handle.join().unwrap();
+
+        // Reads are not journaled
+        let (journal, entries) = Journal::open(&path).unwrap();
+        assert_eq!(entries.len(), 6);
+
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock.clone())
+            .with_journal(journal);
+        engine.replay(entries);
+
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let depth_after = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        assert_eq!(format!("{:?}", depth_before), format!("{:?}", depth_after));
+
+        // Trade ids carry on from the replayed book
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(5, 3, "SOL_USDC", Side::Buy, 6, 101),
+        );
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+
+        let trades: Vec<_> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some((trade.trade_id, trade.maker_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trades, vec![(2, 1, 6)]);
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let (_, entries) = Journal::open(&path).unwrap();
+        assert_eq!(entries.len(), 7);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_events_carry_consecutive_sequence_numbers() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 2, "SOL_USDC", Side::Buy, 4, 100),
+        );
+        let _ =
+            send_cancel_and_get_response(&order_tx, CancelOrder::new(1, 1, "SOL_USDC".to_string()));
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        // One sequence across event types, with no gaps
+        let sequences: Vec<u64> = event_rx.try_iter().map(|event| event.sequence()).collect();
+        assert!(sequences.len() > 5);
+        assert_eq!(sequences, (1..=sequences.len() as u64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run_records_command_latency() {
+        let mut engine = test_engine("SOL_USDC");
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+            engine
+        });
+
+        for order_id in 1..=3 {
+            let _ = send_order_and_get_response(
+                &order_tx,
+                limit_order(order_id, 1, "SOL_USDC", Side::Sell, 10, 100),
+            );
+        }
+        let _ =
+            send_cancel_and_get_response(&order_tx, CancelOrder::new(2, 1, "SOL_USDC".to_string()));
+
+        drop(order_tx);
+        let engine = handle.join().unwrap();
+
+        let latency = engine.latency();
+        assert_eq!(latency.histogram("PlaceOrder").unwrap().len(), 3);
+        assert_eq!(latency.histogram("CancelOrder").unwrap().len(), 1);
+        assert!(latency.histogram("AmendOrder").is_none());
+    }
+
+    #[test]
+    fn test_sequence_carries_on_after_replay() {
+        let path = journal_path("sequence");
+        let clock = ManualClock::new(1_700_000_000_000);
+
+        let (journal, _) = Journal::open(&path).unwrap();
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock.clone())
+            .with_journal(journal);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 10, 100),
+        );
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 2, "SOL_USDC", Side::Buy, 4, 100),
+        );
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let last_sequence = event_rx
+            .try_iter()
+            .map(|event| event.sequence())
+            .max()
+            .unwrap();
+
+        let (journal, entries) = Journal::open(&path).unwrap();
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock)
+            .with_journal(journal);
+        engine.replay(entries);
+        assert_eq!(engine.next_sequence(), last_sequence + 1);
+
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 4, 100),
+        );
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let first_sequence = event_rx
+            .try_iter()
+            .map(|event| event.sequence())
+            .min()
+            .unwrap();
+        assert_eq!(first_sequence, last_sequence + 1);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_journal_cuts_off_torn_last_entry() {
+        let path = journal_path("torn");
+        let clock = ManualClock::new(1_700_000_000_000);
+
+        let (journal, _) = Journal::open(&path).unwrap();
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock)
+            .with_journal(journal);
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        for order_id in 1..=2 {
+            let _ = send_order_and_get_response(
+                &order_tx,
+                limit_order(order_id, 1, "SOL_USDC", Side::Buy, 10, 100),
+            );
+        }
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let torn = format!("{}{{\"Command\":{{\"timest", written);
+        std::fs::write(&path, &torn).unwrap();
+
+        let (_, entries) = Journal::open(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+
+        // Damage anywhere but the end is not a crash mid-write
+        std::fs::write(&path, format!("{{oops}}\n{}", written)).unwrap();
+        assert!(matches!(
+            Journal::open(&path),
+            Err(JournalError::Corrupt { line: 1, .. })
+        ));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_snapshot_restore_replays_journal_tail() {
+        let journal = journal_path("snapshot");
+        let snapshot = journal.with_extension("snapshot");
+        let _ = std::fs::remove_file(&snapshot);
+        let clock = ManualClock::new(1_700_000_000_000);
+
+        let (opened, _) = Journal::open(&journal).unwrap();
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock.clone())
+            .with_journal(opened)
+            .with_snapshots(&snapshot, SnapshotInterval::Commands(3));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let mut iceberg = limit_order(1, 1, "SOL_USDC", Side::Sell, 30, 101);
+        iceberg.display_quantity = Some(10);
+        let orders = [
+            iceberg,
+            limit_order(2, 1, "SOL_USDC", Side::Sell, 10, 101),
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 12, 101),
+            // Journal tail after the snapshot
+            limit_order(4, 2, "SOL_USDC", Side::Buy, 5, 99),
+            limit_order(5, 3, "SOL_USDC", Side::Buy, 3, 101),
+        ];
+        for order in orders {
+            clock.advance(5);
+            let _ = send_order_and_get_response(&order_tx, order);
+        }
+
+        let depth_before = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let loaded = Snapshot::load(&snapshot).unwrap().unwrap();
+        assert_eq!(loaded.journal_entries(), 3);
+
+        let (opened, tail) = Journal::open_after(&journal, loaded.journal_entries()).unwrap();
+        assert_eq!(tail.len(), 2);
+
+        let mut engine = test_engine("SOL_USDC")
+            .with_clock(clock.clone())
+            .with_journal(opened);
+        engine.restore(loaded);
+        engine.replay(tail);
+        assert_eq!(engine.last_order_id(), 5);
+
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let depth_after = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        assert_eq!(format!("{:?}", depth_before), format!("{:?}", depth_after));
+
+        // Queue order and trade ids survive: the iceberg refilled behind order 2
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(6, 3, "SOL_USDC", Side::Buy, 10, 101),
+        );
+        let _ = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+
+        let trades: Vec<_> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Trade(trade) => Some((trade.trade_id, trade.maker_order_id, trade.quantity)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trades, vec![(4, 2, 5), (5, 1, 5)]);
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let _ = std::fs::remove_file(&journal);
+        let _ = std::fs::remove_file(&snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_checksum_is_verified() {
+        let journal = journal_path("checksum");
+        let snapshot = journal.with_extension("snapshot");
+        let _ = std::fs::remove_file(&snapshot);
+
+        assert!(Snapshot::load(&snapshot).unwrap().is_none());
+
+        let (opened, _) = Journal::open(&journal).unwrap();
+        let mut engine = test_engine("SOL_USDC")
+            .with_journal(opened)
+            .with_snapshots(&snapshot, SnapshotInterval::Commands(1));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 100),
+        );
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let written = std::fs::read_to_string(&snapshot).unwrap();
+        assert!(Snapshot::load(&snapshot).unwrap().is_some());
+
+        let tampered =
+            written.replacen("\"remaining_quantity\":10", "\"remaining_quantity\":90", 1);
+        assert_ne!(tampered, written);
+        std::fs::write(&snapshot, tampered).unwrap();
+        assert!(matches!(
+            Snapshot::load(&snapshot),
+            Err(SnapshotError::ChecksumMismatch { .. })
+        ));
+
+        let _ = std::fs::remove_file(&journal);
+        let _ = std::fs::remove_file(&snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_restore_keeps_configured_book() {
+        let journal = journal_path("config");
+        let snapshot = journal.with_extension("snapshot");
+        let _ = std::fs::remove_file(&snapshot);
+
+        let (opened, _) = Journal::open(&journal).unwrap();
+        let mut engine = test_engine("SOL_USDC")
+            .with_journal(opened)
+            .with_snapshots(&snapshot, SnapshotInterval::Commands(1));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(1, 1, "SOL_USDC", Side::Sell, 20, 50000),
+        );
+        drop(order_tx);
+        handle.join().unwrap();
+
+        // The snapshot was written with default rules and no fees
+        let mut engine = Engine::with_instruments([sol_usdc_spec()])
+            .with_fee_schedule("SOL_USDC", FeeSchedule::new(0, 10));
+        engine.restore(Snapshot::load(&snapshot).unwrap().unwrap());
+
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        match send_order_and_get_response(
+            &order_tx,
+            limit_order(2, 2, "SOL_USDC", Side::Buy, 5, 50000),
+        ) {
+            CommandResponse::PlaceOrder(OrderResponse::Reject { reason, .. }) => {
+                assert_eq!(
+                    reason.to_string(),
+                    RejectReason::InvalidQuantity.to_string()
+                );
+            }
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+
+        let _ = send_order_and_get_response(
+            &order_tx,
+            limit_order(3, 2, "SOL_USDC", Side::Buy, 10, 50000),
+        );
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let fills: Vec<(u64, i64)> = event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Fill(fill) => Some((fill.order_id, fill.fee)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills, vec![(1, 0), (3, 1)]);
+
+        let _ = std::fs::remove_file(&journal);
+        let _ = std::fs::remove_file(&snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_restores_price_ladder() {
+        let journal = journal_path("ladder");
+        let snapshot = journal.with_extension("snapshot");
+        let _ = std::fs::remove_file(&snapshot);
+
+        let ladder_engine =
+            || Engine::with_instruments([sol_usdc_spec()]).with_price_ladder("SOL_USDC");
+
+        let (opened, _) = Journal::open(&journal).unwrap();
+        let mut engine = ladder_engine()
+            .with_journal(opened)
+            .with_snapshots(&snapshot, SnapshotInterval::Commands(3));
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let orders = [
+            limit_order(1, 1, "SOL_USDC", Side::Buy, 10, 1000),
+            limit_order(2, 1, "SOL_USDC", Side::Buy, 20, 50000),
+            limit_order(3, 2, "SOL_USDC", Side::Sell, 10, 1_000_000),
+        ];
+        for order in orders {
+            let _ = send_order_and_get_response(&order_tx, order);
+        }
+        let depth_before = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        drop(order_tx);
+        handle.join().unwrap();
+
+        // Only the levels are written, not the empty slots between them
+        assert!(std::fs::metadata(&snapshot).unwrap().len() < 4096);
+
+        let mut engine = ladder_engine();
+        engine.restore(Snapshot::load(&snapshot).unwrap().unwrap());
+
+        let (order_tx, order_rx) =
+            crossbeam_channel::unbounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>();
+        let (event_tx, _event_rx) = crossbeam_channel::unbounded::<Event>();
+
+        let handle = std::thread::spawn(move || {
+            engine.run(order_rx, event_tx);
+        });
+
+        let depth_after = send_get_depth_and_get_response(&order_tx, "SOL_USDC", 5);
+        assert_eq!(format!("{:?}", depth_before), format!("{:?}", depth_after));
+
+        drop(order_tx);
+        handle.join().unwrap();
+
+        let _ = std::fs::remove_file(&journal);
+        let _ = std::fs::remove_file(&snapshot);
+    }
 }
@@ -1,18 +1,34 @@
 use crossbeam_channel;
 use engine_core::engine::Engine;
+use engine_core::journal::Journal;
+use engine_core::ledger::Ledger;
+use engine_core::snapshot::{Snapshot, SnapshotInterval};
 use market_data::{
     pipeline::MarketDataPipeline, publisher::publisher::Publisher, publisher::redis::RedisPublisher,
 };
-use net::http::app::HttpServerApp;
+use net::http::app::{AdminServerApp, HttpServerApp};
 use net::http::models::orders::CommandResponse;
 use net::ws::app::WsServerApp;
+use net::ws::types::RegisteredSymbols;
 use oneshot;
+use persistence::scylla_db::ScyllaDb;
 use persistence::writer::PersistenceWriter;
-use protocol::types::{Event, OrderCommand};
+use protocol::types::{Event, InstrumentSpec, OrderCommand};
 use runtime::RUNTIME;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::vec;
+
+/// Write-ahead journal the engine is rebuilt from on startup
+const JOURNAL_PATH: &str = "engine.journal";
+/// Latest engine snapshot, restored before the journal tail is replayed
+const SNAPSHOT_PATH: &str = "engine.snapshot";
+const SNAPSHOT_INTERVAL: SnapshotInterval = SnapshotInterval::Commands(10_000);
+const SCYLLA_HOST: &str = "127.0.0.1";
+/// Bearer token the admin listener requires; without it the listener is not started
+const OPERATOR_TOKEN_VAR: &str = "OPERATOR_TOKEN";
+const KEYSPACE: &str = "orderbook";
+
 fn main() {
     let (order_tx, order_rx) =
         crossbeam_channel::bounded::<(OrderCommand, oneshot::Sender<CommandResponse>)>(1000);
@@ -36,16 +52,88 @@ fn main() {
         }
     });
 
+    // A snapshot that fails its checksum is ignored and the whole journal
+    // is replayed instead
+    let snapshot = Snapshot::load(SNAPSHOT_PATH).unwrap_or_else(|e| {
+        eprintln!("Ignoring snapshot {}: {}", SNAPSHOT_PATH, e);
+        None
+    });
+    let replay_from = snapshot.as_ref().map_or(0, Snapshot::journal_entries);
+
+    let (journal, journal_entries) = Journal::open_after(JOURNAL_PATH, replay_from)
+        .unwrap_or_else(|e| panic!("Failed to open journal {}: {}", JOURNAL_PATH, e));
+
+    // Trading rules come from the markets table. A registered symbol without
+    // a valid market row gets no book, so its orders are rejected rather than
+    // accepted at any price and quantity.
+    let markets = RUNTIME
+        .block_on(async {
+            let db = ScyllaDb::new(SCYLLA_HOST, KEYSPACE).await?;
+            db.load_markets().await
+        })
+        .unwrap_or_else(|e| panic!("Failed to load markets: {}", e));
+
+    let registered = RegisteredSymbols::all();
+    let specs: Vec<InstrumentSpec> = markets
+        .into_iter()
+        .filter(|market| registered.contains(&market.symbol))
+        .filter_map(|market| {
+            InstrumentSpec::try_from(market)
+                .inspect_err(|e| eprintln!("Skipping market row: {}", e))
+                .ok()
+        })
+        .collect();
+
+    for symbol in &registered {
+        if !specs.iter().any(|spec| &spec.symbol == symbol) {
+            eprintln!(
+                "No valid market row for {}, its orders will be rejected",
+                symbol
+            );
+        }
+    }
+
+    // Rebuild the engine before taking new commands. Orders lock funds from
+    // the ledger, which only an operator can credit, through the admin
+    // listener. The ledger is part of every snapshot and deposits are
+    // journaled, so balances come back from the snapshot and the journal
+    // tail; it starts empty only when both are.
+    let mut engine = Engine::with_instruments(specs).with_ledger(Ledger::new());
+    if let Some(snapshot) = snapshot {
+        engine.restore(snapshot);
+    }
+    engine.replay(journal_entries);
+
+    // Order ids carry on after the last one placed before the restart
+    let first_order_id = engine.last_order_id() + 1;
+
     // Start engine
     let engine_handle = std::thread::spawn(move || {
-        let mut engine = Engine::new("SOL_USDC");
+        let mut engine = engine
+            .with_journal(journal)
+            .with_snapshots(SNAPSHOT_PATH, SNAPSHOT_INTERVAL);
         engine.run(order_rx, event_tx);
     });
 
     // Build and start HTTP server
-    let http_server = HttpServerApp::build("127.0.0.1", "8080", order_tx.clone())
+    let http_server = HttpServerApp::build("127.0.0.1", "8080", order_tx.clone(), first_order_id)
         .unwrap_or_else(|e| panic!("Failed to build HTTP server: {}", e));
 
+    // Build the operator-only admin server on loopback
+    let admin_server = match std::env::var(OPERATOR_TOKEN_VAR) {
+        Ok(token) if !token.is_empty() => Some(
+            AdminServerApp::build("127.0.0.1", "8082", order_tx.clone(), token)
+                .unwrap_or_else(|e| panic!("Failed to build admin server: {}", e)),
+        ),
+        _ => {
+            eprintln!(
+                "{} is not set, admin endpoints are disabled",
+                OPERATOR_TOKEN_VAR
+            );
+            None
+        }
+    };
+
     // Build and start WebSocket server
     let ws_server = RUNTIME.block_on(async {
         WsServerApp::build("127.0.0.1", "8081")
@@ -60,8 +148,13 @@ fn main() {
 
     let mut market_data_pipeline = MarketDataPipeline::new(publishers);
 
+    let http_latency = http_server.latency.clone();
+
     println!("Gateway starting...");
     println!("HTTP server: http://127.0.0.1:{}", http_server.port);
+    if let Some(admin_server) = &admin_server {
+        println!("Admin server: http://127.0.0.1:{}", admin_server.port);
+    }
     println!("Engine: Running");
 
     // Run HTTP server in async runtime
@@ -71,6 +164,15 @@ fn main() {
         }
     });
 
+    // Run admin server in async runtime
+    let admin_handle = admin_server.map(|admin_server| {
+        RUNTIME.spawn(async move {
+            if let Err(e) = admin_server.run_until_stopped().await {
+                eprintln!("Admin server error: {}", e);
+            }
+        })
+    });
+
     // Run WebSocket server in async runtime
     let ws_handle = RUNTIME.spawn(async move {
         if let Err(e) = ws_server.run_until_stopped().await {
@@ -85,7 +187,7 @@ fn main() {
 
     // Initialize and run persistence writer
     let persistence_handle = RUNTIME.spawn(async move {
-        let mut persistence_writer = PersistenceWriter::new("127.0.0.1", "orderbook")
+        let mut persistence_writer = PersistenceWriter::new(SCYLLA_HOST, KEYSPACE)
             .await
             .unwrap_or_else(|e| panic!("Failed to initialize persistence writer: {}", e));
         persistence_writer.run(persistence_rx).await;
@@ -112,7 +214,16 @@ fn main() {
     market_data_handle.join().unwrap();
     RUNTIME.block_on(persistence_handle).unwrap();
     RUNTIME.block_on(http_handle).unwrap();
+    if let Some(admin_handle) = admin_handle {
+        RUNTIME.block_on(admin_handle).unwrap();
+    }
     RUNTIME.block_on(ws_handle).unwrap();
 
+    if let Ok(latency) = http_latency.lock()
+        && !latency.is_empty()
+    {
+        print!("HTTP request latency:\n{}", latency);
+    }
+
     println!("Gateway stopped");
 }
@@ -58,6 +58,10 @@ impl Aggregator {
             Event::Ticker(_) => {
                 out.push(ev);
             }
+
+            Event::Status(_) | Event::Indicative(_) => {
+                out.push(ev);
+            }
         }
 
         out
@@ -117,6 +121,7 @@ impl Aggregator {
                 / state.open_24h.unwrap() as f64)
                 * 100.0,
             timestamp: Utc::now().timestamp_millis(),
+            sequence: t.sequence,
         })
     }
 }
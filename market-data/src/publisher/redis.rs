@@ -25,12 +25,17 @@ impl RedisPublisher {
             Event::Trade(trade) => format!("market:trade:{}", trade.symbol),
             Event::Depth(depth) => format!("market:depth:{}", depth.symbol),
             Event::Ticker(ticker) => format!("market:ticker:{}", ticker.symbol),
+            Event::Status(status) => format!("market:status:{}", status.symbol),
+            // Auction indications go to the UIs following the session status
+            Event::Indicative(indicative) => format!("market:status:{}", indicative.symbol),
             Event::OrderUpdate(update) => {
                 let user_id = match update {
                     UserOrderUpdateEvent::Fill { user_id, .. } => user_id,
                     UserOrderUpdateEvent::Ack { user_id, .. } => user_id,
                     UserOrderUpdateEvent::Reject { user_id, .. } => user_id,
                     UserOrderUpdateEvent::Cancelled { user_id, .. } => user_id,
+                    UserOrderUpdateEvent::Amended { user_id, .. } => user_id,
+                    UserOrderUpdateEvent::StopPriceUpdated { user_id, .. } => user_id,
                 };
 
                 format!("market:order:user:{}", user_id)
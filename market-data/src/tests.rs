@@ -8,8 +8,9 @@ use crate::{
 };
 use crossbeam_channel;
 use protocol::types::{
-    BookUpdate, CancelReason, Event, Fill, OrderAck, OrderCancelled, OrderReject, PriceLevel,
-    RejectReason, Side, Trade,
+    BookUpdate, CancelReason, Event, Fill, IndicativePrice, Liquidity, OrderAck, OrderCancelled,
+    OrderReject, PriceLevel, RejectReason, SessionState, SessionStateChanged, Side,
+    StopPriceUpdated, Trade,
 };
 use std::sync::{Arc, Mutex};
 
@@ -74,7 +75,12 @@ mod tests {
             symbol: "SOL_USDC".to_string(),
             quantity: 50,
             price: 50000,
+            maker_fee: 0,
+            maker_fee_asset: "USDC".to_string(),
+            taker_fee: 0,
+            taker_fee_asset: "SOL".to_string(),
             timestamp: 1234567890,
+            sequence: 42,
         };
         let event = Event::Trade(trade);
 
@@ -87,6 +93,7 @@ mod tests {
                 assert_eq!(t.price, 50000);
                 assert_eq!(t.symbol, "SOL_USDC");
                 assert_eq!(t.timestamp, 1234567890);
+                assert_eq!(t.sequence, 42);
             }
             _ => panic!("Expected Trade event, got {:?}", ws_event),
         }
@@ -103,6 +110,10 @@ mod tests {
             filled_quantity: 25,
             filled_price: 50000,
             remaining_quantity: 25,
+            liquidity: Liquidity::Taker,
+            fee: 1,
+            fee_asset: "SOL".to_string(),
+            sequence: 0,
         };
         let event = Event::Fill(fill);
 
@@ -116,6 +127,9 @@ mod tests {
                 filled_quantity,
                 filled_price,
                 remaining_quantity,
+                liquidity,
+                fee,
+                fee_asset,
                 ..
             }) => {
                 assert_eq!(order_id, 1);
@@ -124,6 +138,9 @@ mod tests {
                 assert_eq!(filled_quantity, 25);
                 assert_eq!(filled_price, 50000);
                 assert_eq!(remaining_quantity, 25);
+                assert_eq!(liquidity, Liquidity::Taker);
+                assert_eq!(fee, 1);
+                assert_eq!(fee_asset, "SOL");
             }
             _ => panic!("Expected Fill OrderUpdate event"),
         }
@@ -136,6 +153,7 @@ mod tests {
             order_id: 1,
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
+            sequence: 0,
         };
         let event = Event::OrderAck(ack);
 
@@ -165,6 +183,7 @@ mod tests {
             reason: RejectReason::InvalidQuantity,
             message: "Quantity must be greater than 0".to_string(),
             symbol: "SOL_USDC".to_string(),
+            sequence: 0,
         };
         let event = Event::OrderReject(reject);
 
@@ -197,6 +216,7 @@ mod tests {
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
             reason: CancelReason::UserRequested,
+            sequence: 0,
         };
         let event = Event::OrderCancelled(cancelled);
 
@@ -217,6 +237,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_transformer_stop_price_updated() {
+        let transformer = Transformer::new();
+        let updated = StopPriceUpdated {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            stop_price: 49900,
+            price: Some(49800),
+            sequence: 0,
+        };
+        let event = Event::StopPriceUpdated(updated);
+
+        let ws_event = transformer.transform(event);
+        assert_eq!(ws_event.user_id(), Some(100));
+        assert!(!ws_event.is_public());
+
+        match ws_event {
+            WSEvent::OrderUpdate(UserOrderUpdateEvent::StopPriceUpdated {
+                order_id,
+                stop_price,
+                price,
+                ..
+            }) => {
+                assert_eq!(order_id, 1);
+                assert_eq!(stop_price, 49900);
+                assert_eq!(price, Some(49800));
+            }
+            _ => panic!("Expected StopPriceUpdated OrderUpdate event"),
+        }
+    }
+
     #[test]
     fn test_transformer_book_update() {
         let transformer = Transformer::new();
@@ -231,6 +283,7 @@ mod tests {
                 quantity: 50,
             }],
             last_price: Some(50000),
+            sequence: 0,
         };
         let event = Event::BookUpdate(book_update);
 
@@ -251,6 +304,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_transformer_session_state_changed() {
+        let transformer = Transformer::new();
+        let event = Event::SessionStateChanged(SessionStateChanged {
+            symbol: "SOL_USDC".to_string(),
+            previous: SessionState::Continuous,
+            state: SessionState::Halted,
+            timestamp: 1000,
+            sequence: 0,
+        });
+
+        let ws_event = transformer.transform(event);
+        assert!(ws_event.is_public());
+        assert_eq!(ws_event.user_id(), None);
+
+        match ws_event {
+            WSEvent::Status(status) => {
+                assert_eq!(status.symbol, "SOL_USDC");
+                assert_eq!(status.previous, SessionState::Continuous);
+                assert_eq!(status.state, SessionState::Halted);
+                assert_eq!(status.timestamp, 1000);
+            }
+            _ => panic!("Expected Status event"),
+        }
+    }
+
+    #[test]
+    fn test_transformer_indicative_price() {
+        let transformer = Transformer::new();
+        let event = Event::IndicativePrice(IndicativePrice {
+            symbol: "SOL_USDC".to_string(),
+            price: Some(50000),
+            volume: 130,
+            imbalance: 20,
+            imbalance_side: Some(Side::Buy),
+            timestamp: 1000,
+            sequence: 0,
+        });
+
+        let ws_event = transformer.transform(event);
+        assert!(ws_event.is_public());
+
+        match ws_event {
+            WSEvent::Indicative(indicative) => {
+                assert_eq!(indicative.price, Some(50000));
+                assert_eq!(indicative.volume, 130);
+                assert_eq!(indicative.imbalance, 20);
+                assert_eq!(indicative.imbalance_side, Some(Side::Buy));
+            }
+            _ => panic!("Expected Indicative event"),
+        }
+    }
+
     #[test]
     fn test_transformer_all_event_types() {
         let transformer = Transformer::new();
@@ -266,7 +372,12 @@ mod tests {
                 symbol: "SOL_USDC".to_string(),
                 quantity: 50,
                 price: 50000,
+                maker_fee: 0,
+                maker_fee_asset: "USDC".to_string(),
+                taker_fee: 0,
+                taker_fee_asset: "SOL".to_string(),
                 timestamp: 1234567890,
+                sequence: 0,
             }),
             Event::Fill(Fill {
                 order_id: 1,
@@ -276,11 +387,16 @@ mod tests {
                 filled_quantity: 25,
                 filled_price: 50000,
                 remaining_quantity: 25,
+                liquidity: Liquidity::Taker,
+                fee: 0,
+                fee_asset: "SOL".to_string(),
+                sequence: 0,
             }),
             Event::OrderAck(OrderAck {
                 order_id: 1,
                 user_id: 100,
                 symbol: "SOL_USDC".to_string(),
+                sequence: 0,
             }),
         ];
 
@@ -306,6 +422,7 @@ mod tests {
             price: 50000,
             quantity: 10,
             timestamp: 1000,
+            sequence: 7,
         });
 
         let result = aggregator.process(trade);
@@ -316,6 +433,15 @@ mod tests {
 
         assert!(has_trade, "Should output trade event");
         assert!(has_ticker, "Should output ticker event");
+
+        // The ticker shares the sequence of the trade that moved it
+        for event in &result {
+            match event {
+                WSEvent::Trade(t) => assert_eq!(t.sequence, 7),
+                WSEvent::Ticker(t) => assert_eq!(t.sequence, 7),
+                _ => {}
+            }
+        }
     }
 
     #[test]
@@ -330,6 +456,7 @@ mod tests {
             price: 50000,
             quantity: 10,
             timestamp: 1000,
+            sequence: 0,
         });
         let result1 = aggregator.process(trade1);
         let ticker1 = result1
@@ -358,6 +485,7 @@ mod tests {
             price: 51000,
             quantity: 20,
             timestamp: 2000,
+            sequence: 0,
         });
         let result2 = aggregator.process(trade2);
         let ticker2 = result2
@@ -400,6 +528,7 @@ mod tests {
                 price,
                 quantity: qty,
                 timestamp: 1000,
+                sequence: 0,
             });
             aggregator.process(trade);
         }
@@ -411,6 +540,7 @@ mod tests {
             price: 50500,
             quantity: 1,
             timestamp: 5000,
+            sequence: 0,
         });
         let result = aggregator.process(final_trade);
         let ticker = result
@@ -442,6 +572,7 @@ mod tests {
             bids: vec![],
             asks: vec![],
             timestamp: 1000,
+            sequence: 0,
             last_price: None,
         });
 
@@ -450,6 +581,7 @@ mod tests {
             bids: vec![],
             asks: vec![],
             timestamp: 150, // Within 100ms throttle window
+            sequence: 0,
             last_price: None,
         });
 
@@ -473,6 +605,7 @@ mod tests {
             bids: vec![],
             asks: vec![],
             timestamp: 1000,
+            sequence: 0,
             last_price: None,
         });
 
@@ -490,6 +623,7 @@ mod tests {
             }],
             asks: vec![],
             timestamp: 1200, // 200ms later (outside throttle window)
+            sequence: 0,
             last_price: None,
         });
 
@@ -508,6 +642,7 @@ mod tests {
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
             timestamp: 1000,
+            sequence: 0,
         });
 
         let result = aggregator.process(order_update);
@@ -526,6 +661,7 @@ mod tests {
             price: 50000,
             quantity: 10,
             timestamp: 1000,
+            sequence: 0,
         });
 
         // Trade for BTC/USD
@@ -535,6 +671,7 @@ mod tests {
             price: 60000,
             quantity: 5,
             timestamp: 2000,
+            sequence: 0,
         });
 
         let result1 = aggregator.process(trade1);
@@ -597,7 +734,12 @@ mod tests {
             symbol: "SOL_USDC".to_string(),
             quantity: 50,
             price: 50000,
+            maker_fee: 0,
+            maker_fee_asset: "USDC".to_string(),
+            taker_fee: 0,
+            taker_fee_asset: "SOL".to_string(),
             timestamp: 1234567890,
+            sequence: 0,
         };
         event_tx.send(Event::Trade(trade)).unwrap();
 
@@ -639,7 +781,12 @@ mod tests {
                 symbol: "SOL_USDC".to_string(),
                 quantity: 50,
                 price: 50000 + i,
+                maker_fee: 0,
+                maker_fee_asset: "USDC".to_string(),
+                taker_fee: 0,
+                taker_fee_asset: "SOL".to_string(),
                 timestamp: 1234567890i64 + i as i64,
+                sequence: 0,
             };
             event_tx.send(Event::Trade(trade)).unwrap();
         }
@@ -679,7 +826,12 @@ mod tests {
             symbol: "SOL_USDC".to_string(),
             quantity: 50,
             price: 50000,
+            maker_fee: 0,
+            maker_fee_asset: "USDC".to_string(),
+            taker_fee: 0,
+            taker_fee_asset: "SOL".to_string(),
             timestamp: 1234567890,
+            sequence: 0,
         };
         event_tx.send(Event::Trade(trade)).unwrap();
 
@@ -716,7 +868,12 @@ mod tests {
                 symbol: "SOL_USDC".to_string(),
                 quantity: 50,
                 price: 50000,
+                maker_fee: 0,
+                maker_fee_asset: "USDC".to_string(),
+                taker_fee: 0,
+                taker_fee_asset: "SOL".to_string(),
                 timestamp: 1234567890,
+                sequence: 0,
             }))
             .unwrap();
 
@@ -729,6 +886,10 @@ mod tests {
                 filled_quantity: 25,
                 filled_price: 50000,
                 remaining_quantity: 25,
+                liquidity: Liquidity::Taker,
+                fee: 0,
+                fee_asset: "SOL".to_string(),
+                sequence: 0,
             }))
             .unwrap();
 
@@ -737,6 +898,7 @@ mod tests {
                 order_id: 1,
                 user_id: 100,
                 symbol: "SOL_USDC".to_string(),
+                sequence: 0,
             }))
             .unwrap();
 
@@ -746,6 +908,7 @@ mod tests {
                 bids: vec![],
                 asks: vec![],
                 last_price: Some(50000),
+                sequence: 0,
             }))
             .unwrap();
 
@@ -768,6 +931,7 @@ mod tests {
             price: 50000,
             quantity: 10,
             timestamp: 1000,
+            sequence: 0,
         });
         assert!(trade.is_public(), "Trade should be public");
 
@@ -776,6 +940,7 @@ mod tests {
             bids: vec![],
             asks: vec![],
             timestamp: 1000,
+            sequence: 0,
             last_price: None,
         });
         assert!(depth.is_public(), "Depth should be public");
@@ -790,6 +955,7 @@ mod tests {
             price_change: 0,
             price_change_percent: 0.0,
             timestamp: 1000,
+            sequence: 0,
         });
         assert!(ticker.is_public(), "Ticker should be public");
 
@@ -798,6 +964,7 @@ mod tests {
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
             timestamp: 1000,
+            sequence: 0,
         });
         assert!(!order_update.is_public(), "OrderUpdate should be private");
     }
@@ -810,6 +977,7 @@ mod tests {
             price: 50000,
             quantity: 10,
             timestamp: 1000,
+            sequence: 0,
         });
         assert_eq!(trade.user_id(), None, "Trade should not have user_id");
 
@@ -820,7 +988,11 @@ mod tests {
             filled_quantity: 10,
             filled_price: 50000,
             remaining_quantity: 0,
+            liquidity: Liquidity::Taker,
+            fee: 0,
+            fee_asset: "SOL".to_string(),
             timestamp: 1000,
+            sequence: 0,
         });
         assert_eq!(fill.user_id(), Some(100), "Fill should have user_id");
 
@@ -829,6 +1001,7 @@ mod tests {
             user_id: 200,
             symbol: "SOL_USDC".to_string(),
             timestamp: 1000,
+            sequence: 0,
         });
         assert_eq!(ack.user_id(), Some(200), "Ack should have user_id");
 
@@ -839,6 +1012,7 @@ mod tests {
             reason: "Invalid".to_string(),
             message: "Test".to_string(),
             timestamp: 1000,
+            sequence: 0,
         });
         assert_eq!(reject.user_id(), Some(300), "Reject should have user_id");
 
@@ -847,6 +1021,7 @@ mod tests {
             user_id: 400,
             symbol: "SOL_USDC".to_string(),
             timestamp: 1000,
+            sequence: 0,
         });
         assert_eq!(
             cancelled.user_id(),
@@ -863,6 +1038,7 @@ mod tests {
             price: 50000,
             quantity: 10,
             timestamp: 1000,
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&trade).unwrap();
@@ -888,6 +1064,7 @@ mod tests {
                 quantity: 50,
             }],
             timestamp: 1000,
+            sequence: 0,
             last_price: Some(50000),
         };
 
@@ -912,6 +1089,7 @@ mod tests {
             price_change: 0,
             price_change_percent: 0.0,
             timestamp: 1000,
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&ticker).unwrap();
@@ -934,7 +1112,11 @@ mod tests {
             filled_quantity: 10,
             filled_price: 50000,
             remaining_quantity: 0,
+            liquidity: Liquidity::Taker,
+            fee: 0,
+            fee_asset: "SOL".to_string(),
             timestamp: 1000,
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&fill).unwrap();
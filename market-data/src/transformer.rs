@@ -1,6 +1,10 @@
-use crate::types::{DepthEvent, Event as WsEvent, TradeEvent, UserOrderUpdateEvent};
+use crate::types::{
+    DepthEvent, Event as WsEvent, IndicativePriceEvent, StatusEvent, TradeEvent,
+    UserOrderUpdateEvent,
+};
 use protocol::types::{
-    BookUpdate, Event as EngineEvent, Fill, OrderAck, OrderCancelled, OrderReject, Trade,
+    BookUpdate, Event as EngineEvent, Fill, IndicativePrice, OrderAck, OrderAmended,
+    OrderCancelled, OrderReject, SessionStateChanged, StopPriceUpdated, Trade,
 };
 
 pub struct Transformer;
@@ -15,6 +19,8 @@ impl Transformer {
             // Public Events
             EngineEvent::Trade(trade) => self.transform_trade(trade),
             EngineEvent::BookUpdate(book_update) => self.transform_depth(book_update),
+            EngineEvent::SessionStateChanged(changed) => self.transform_session_state(changed),
+            EngineEvent::IndicativePrice(indicative) => self.transform_indicative_price(indicative),
 
             // Private Events
             EngineEvent::Fill(fill) => self.transform_fill(fill),
@@ -23,6 +29,10 @@ impl Transformer {
             EngineEvent::OrderCancelled(order_cancelled) => {
                 self.transform_order_cancelled(order_cancelled)
             }
+            EngineEvent::OrderAmended(order_amended) => self.transform_order_amended(order_amended),
+            EngineEvent::StopPriceUpdated(stop_price_updated) => {
+                self.transform_stop_price_updated(stop_price_updated)
+            }
         }
     }
 
@@ -33,6 +43,7 @@ impl Transformer {
             price: trade.price,
             quantity: trade.quantity,
             timestamp: trade.timestamp,
+            sequence: trade.sequence,
         })
     }
 
@@ -43,6 +54,29 @@ impl Transformer {
             asks: book_update.asks,
             last_price: book_update.last_price,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: book_update.sequence,
+        })
+    }
+
+    pub fn transform_session_state(&self, changed: SessionStateChanged) -> WsEvent {
+        WsEvent::Status(StatusEvent {
+            symbol: changed.symbol,
+            previous: changed.previous,
+            state: changed.state,
+            timestamp: changed.timestamp,
+            sequence: changed.sequence,
+        })
+    }
+
+    pub fn transform_indicative_price(&self, indicative: IndicativePrice) -> WsEvent {
+        WsEvent::Indicative(IndicativePriceEvent {
+            symbol: indicative.symbol,
+            price: indicative.price,
+            volume: indicative.volume,
+            imbalance: indicative.imbalance,
+            imbalance_side: indicative.imbalance_side,
+            timestamp: indicative.timestamp,
+            sequence: indicative.sequence,
         })
     }
 
@@ -54,7 +88,11 @@ impl Transformer {
             filled_quantity: fill.filled_quantity,
             filled_price: fill.filled_price,
             remaining_quantity: fill.remaining_quantity,
+            liquidity: fill.liquidity,
+            fee: fill.fee,
+            fee_asset: fill.fee_asset,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: fill.sequence,
         })
     }
 
@@ -64,6 +102,7 @@ impl Transformer {
             user_id: order_ack.user_id,
             symbol: order_ack.symbol,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: order_ack.sequence,
         })
     }
 
@@ -74,6 +113,7 @@ impl Transformer {
             reason: order_reject.reason.to_string(),
             message: order_reject.message,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: order_reject.sequence,
             symbol: order_reject.symbol,
         })
     }
@@ -84,6 +124,32 @@ impl Transformer {
             user_id: order_cancelled.user_id,
             symbol: order_cancelled.symbol,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: order_cancelled.sequence,
+        })
+    }
+
+    pub fn transform_order_amended(&self, order_amended: OrderAmended) -> WsEvent {
+        WsEvent::OrderUpdate(UserOrderUpdateEvent::Amended {
+            order_id: order_amended.order_id,
+            user_id: order_amended.user_id,
+            symbol: order_amended.symbol,
+            price: order_amended.price,
+            quantity: order_amended.quantity,
+            remaining_quantity: order_amended.remaining_quantity,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: order_amended.sequence,
+        })
+    }
+
+    pub fn transform_stop_price_updated(&self, stop_price_updated: StopPriceUpdated) -> WsEvent {
+        WsEvent::OrderUpdate(UserOrderUpdateEvent::StopPriceUpdated {
+            order_id: stop_price_updated.order_id,
+            user_id: stop_price_updated.user_id,
+            symbol: stop_price_updated.symbol,
+            stop_price: stop_price_updated.stop_price,
+            price: stop_price_updated.price,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            sequence: stop_price_updated.sequence,
         })
     }
 }
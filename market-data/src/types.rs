@@ -1,12 +1,18 @@
-use protocol::types::{OrderId, Price, PriceLevel, Quantity, UserId};
+use protocol::types::{
+    Liquidity, OrderId, Price, PriceLevel, Quantity, SessionState, Side, UserId,
+};
 use serde::{Deserialize, Serialize};
 
+// Every event carries the sequence of the engine event it came from; a ticker
+// shares the sequence of the trade that moved it
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
     Trade(TradeEvent),
     Depth(DepthEvent),
     Ticker(TickerEvent),
     OrderUpdate(UserOrderUpdateEvent),
+    Status(StatusEvent),
+    Indicative(IndicativePriceEvent),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +22,7 @@ pub struct TradeEvent {
     pub price: Price,
     pub quantity: Quantity,
     pub timestamp: i64,
+    pub sequence: u64,
     // pub side: Side,
 }
 
@@ -25,6 +32,7 @@ pub struct DepthEvent {
     pub bids: Vec<PriceLevel>,
     pub asks: Vec<PriceLevel>,
     pub timestamp: i64,
+    pub sequence: u64,
     pub last_price: Option<Price>,
 }
 
@@ -39,6 +47,29 @@ pub struct TickerEvent {
     pub price_change: i64,
     pub price_change_percent: f64,
     pub timestamp: i64,
+    pub sequence: u64,
+}
+
+// Trading session change of a symbol, e.g. a halt or a close
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusEvent {
+    pub symbol: String,
+    pub previous: SessionState,
+    pub state: SessionState,
+    pub timestamp: i64,
+    pub sequence: u64,
+}
+
+// Uncross price and volume of a symbol in its auction call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndicativePriceEvent {
+    pub symbol: String,
+    pub price: Option<Price>,
+    pub volume: Quantity,
+    pub imbalance: Quantity,
+    pub imbalance_side: Option<Side>,
+    pub timestamp: i64,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,13 +81,18 @@ pub enum UserOrderUpdateEvent {
         filled_quantity: Quantity,
         filled_price: Price,
         remaining_quantity: Quantity,
+        liquidity: Liquidity,
+        fee: i64,
+        fee_asset: String,
         timestamp: i64,
+        sequence: u64,
     },
     Ack {
         order_id: OrderId,
         user_id: UserId,
         symbol: String,
         timestamp: i64,
+        sequence: u64,
     },
     Reject {
         order_id: OrderId,
@@ -65,28 +101,62 @@ pub enum UserOrderUpdateEvent {
         reason: String,
         message: String,
         timestamp: i64,
+        sequence: u64,
     },
     Cancelled {
         order_id: OrderId,
         user_id: UserId,
         symbol: String,
         timestamp: i64,
+        sequence: u64,
+    },
+    Amended {
+        order_id: OrderId,
+        user_id: UserId,
+        symbol: String,
+        price: Price,
+        quantity: Quantity,
+        remaining_quantity: Quantity,
+        timestamp: i64,
+        sequence: u64,
+    },
+    StopPriceUpdated {
+        order_id: OrderId,
+        user_id: UserId,
+        symbol: String,
+        stop_price: Price,
+        price: Option<Price>,
+        timestamp: i64,
+        sequence: u64,
     },
 }
 
 impl Event {
     pub fn is_public(&self) -> bool {
-        matches!(self, Event::Trade(_) | Event::Depth(_) | Event::Ticker(_))
+        matches!(
+            self,
+            Event::Trade(_)
+                | Event::Depth(_)
+                | Event::Ticker(_)
+                | Event::Status(_)
+                | Event::Indicative(_)
+        )
     }
 
     pub fn user_id(&self) -> Option<UserId> {
         match self {
-            Event::Trade(_) | Event::Depth(_) | Event::Ticker(_) => None,
+            Event::Trade(_)
+            | Event::Depth(_)
+            | Event::Ticker(_)
+            | Event::Status(_)
+            | Event::Indicative(_) => None,
             Event::OrderUpdate(update) => match update {
                 UserOrderUpdateEvent::Fill { user_id, .. } => Some(*user_id),
                 UserOrderUpdateEvent::Ack { user_id, .. } => Some(*user_id),
                 UserOrderUpdateEvent::Reject { user_id, .. } => Some(*user_id),
                 UserOrderUpdateEvent::Cancelled { user_id, .. } => Some(*user_id),
+                UserOrderUpdateEvent::Amended { user_id, .. } => Some(*user_id),
+                UserOrderUpdateEvent::StopPriceUpdated { user_id, .. } => Some(*user_id),
             },
         }
     }
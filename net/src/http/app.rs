@@ -1,20 +1,52 @@
-use std::{net::TcpListener, sync::atomic::AtomicU64};
+use std::{
+    net::TcpListener,
+    sync::{Arc, Mutex, atomic::AtomicU64},
+    time::Duration,
+};
 
 use actix_web::{self, App, HttpServer, web};
 use crossbeam_channel::Sender;
 use oneshot;
 use protocol::types::OrderCommand;
+use runtime::latency::LatencyRecorder;
 
-use crate::http::{models::orders::CommandResponse, routes::config};
+use crate::http::{
+    models::orders::CommandResponse,
+    routes::{admin_config, config},
+};
 
 pub struct HttpServerApp {
     pub port: u16,
     pub server: actix_web::dev::Server,
+    // Round trip of each request through the engine, by command
+    pub latency: Arc<Mutex<LatencyRecorder>>,
 }
 
 pub struct HttpServerAppState {
     pub order_tx: Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
     pub order_id: AtomicU64,
+    pub latency: Arc<Mutex<LatencyRecorder>>,
+}
+
+/// Operator-only listener serving the admin routes, kept off the public
+/// address so a client of the trading API can't reach them
+pub struct AdminServerApp {
+    pub port: u16,
+    pub server: actix_web::dev::Server,
+}
+
+pub struct AdminServerAppState {
+    pub order_tx: Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+    // Bearer token every admin request must carry
+    pub operator_token: String,
+}
+
+impl HttpServerAppState {
+    pub fn record_latency(&self, command: &'static str, elapsed: Duration) {
+        if let Ok(mut latency) = self.latency.lock() {
+            latency.record(command, elapsed);
+        }
+    }
 }
 
 impl HttpServerApp {
@@ -22,14 +54,17 @@ impl HttpServerApp {
         host: &str,
         port: &str,
         order_tx: Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        first_order_id: u64,
     ) -> Result<Self, std::io::Error> {
         let address = format!("{}:{}", host, port);
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
 
+        let latency = Arc::new(Mutex::new(LatencyRecorder::new()));
         let app_state = web::Data::new(HttpServerAppState {
             order_tx,
-            order_id: AtomicU64::new(1),
+            order_id: AtomicU64::new(first_order_id),
+            latency: latency.clone(),
         });
 
         let server =
@@ -38,7 +73,11 @@ impl HttpServerApp {
                 .unwrap()
                 .run();
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            latency,
+        })
     }
 
     pub fn get_port(self) -> u16 {
@@ -49,3 +88,36 @@ impl HttpServerApp {
         self.server.await
     }
 }
+
+impl AdminServerApp {
+    pub fn build(
+        host: &str,
+        port: &str,
+        order_tx: Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+        operator_token: String,
+    ) -> Result<Self, std::io::Error> {
+        let address = format!("{}:{}", host, port);
+        let listener = TcpListener::bind(address)?;
+        let port = listener.local_addr().unwrap().port();
+
+        let app_state = web::Data::new(AdminServerAppState {
+            order_tx,
+            operator_token,
+        });
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .configure(admin_config)
+        })
+        .listen(listener)
+        .unwrap()
+        .run();
+
+        Ok(Self { port, server })
+    }
+
+    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
+        self.server.await
+    }
+}
@@ -0,0 +1,52 @@
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    web,
+};
+use serde_json::json;
+
+use crate::http::app::AdminServerAppState;
+
+/// Lets a request through to the admin routes only if it carries the
+/// operator token as `Authorization: Bearer <token>`.
+pub async fn require_operator(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let authorized = match req.app_data::<web::Data<AdminServerAppState>>() {
+        Some(app_state) => req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| token_matches(token, &app_state.operator_token)),
+        None => false,
+    };
+
+    if !authorized {
+        let response = HttpResponse::Unauthorized().json(json!({
+            "error": "Operator token required",
+        }));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+// Compares every byte so the time taken doesn't leak how much of the token
+// was right
+fn token_matches(token: &str, operator_token: &str) -> bool {
+    let (token, operator_token) = (token.as_bytes(), operator_token.as_bytes());
+
+    token.len() == operator_token.len()
+        && token
+            .iter()
+            .zip(operator_token)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
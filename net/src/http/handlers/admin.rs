@@ -0,0 +1,61 @@
+use crate::http::{
+    app::AdminServerAppState,
+    models::orders::{CommandResponse, DepositRequest, SessionStateRequest},
+};
+use actix_web::{HttpResponse, Responder, post, web};
+use protocol::types::{Deposit, OrderCommand, SetSessionState};
+use serde_json::json;
+
+#[post("/session")]
+pub async fn set_session_state(
+    req: web::Json<SessionStateRequest>,
+    app_state: web::Data<AdminServerAppState>,
+) -> impl Responder {
+    let body = req.into_inner();
+    let set_session_state = SetSessionState::new(body.symbol, body.state);
+    let (tx, rx) = oneshot::channel::<CommandResponse>();
+
+    if let Err(e) = app_state
+        .order_tx
+        .send((OrderCommand::SetSessionState(set_session_state), tx))
+    {
+        return HttpResponse::InternalServerError().json(json!({
+            "error": "Failed to send session state to engine",
+            "message": e.to_string()
+        }));
+    }
+
+    match rx.await {
+        Ok(response) => response.into_http_response(),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": e.to_string(),
+        })),
+    }
+}
+
+#[post("/deposit")]
+pub async fn deposit(
+    req: web::Json<DepositRequest>,
+    app_state: web::Data<AdminServerAppState>,
+) -> impl Responder {
+    let body = req.into_inner();
+    let deposit = Deposit::new(body.user_id, body.asset, body.amount);
+    let (tx, rx) = oneshot::channel::<CommandResponse>();
+
+    if let Err(e) = app_state
+        .order_tx
+        .send((OrderCommand::Deposit(deposit), tx))
+    {
+        return HttpResponse::InternalServerError().json(json!({
+            "error": "Failed to send deposit to engine",
+            "message": e.to_string()
+        }));
+    }
+
+    match rx.await {
+        Ok(response) => response.into_http_response(),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": e.to_string(),
+        })),
+    }
+}
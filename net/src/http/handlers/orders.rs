@@ -1,9 +1,12 @@
 use crate::http::{
     app::HttpServerAppState,
-    models::orders::{CancelOrderRequest, CommandResponse, DepthQuery, OrderRequest},
+    models::orders::{
+        AmendOrderRequest, CancelOrderRequest, CommandResponse, DepthQuery, MassCancelRequest,
+        OrderRequest,
+    },
 };
-use actix_web::{HttpResponse, Responder, delete, get, post, web};
-use protocol::types::{CancelOrder, Order, OrderCommand};
+use actix_web::{HttpResponse, Responder, delete, get, patch, post, web};
+use protocol::types::{AmendOrder, CancelOrder, GetDepth, MassCancel, Order, OrderCommand};
 use serde_json::json;
 use std::{sync::atomic::Ordering, time::Instant};
 
@@ -28,7 +31,16 @@ pub async fn place_order(
         body.order_type,
         body.quantity,
         body.price,
-    );
+    )
+    .with_quote_quantity(body.quote_quantity)
+    .with_display_quantity(body.display_quantity)
+    .with_stop_price(body.stop_price)
+    .with_trailing_offset(body.trailing_offset)
+    .with_time_in_force(body.time_in_force)
+    .with_expire_at(body.expire_at)
+    .with_post_only(body.post_only)
+    .with_self_trade_prevention(body.self_trade_prevention)
+    .with_max_slippage_bps(body.max_slippage_bps);
 
     let (tx, rx) = oneshot::channel::<CommandResponse>();
     if let Err(e) = app_state
@@ -41,19 +53,9 @@ pub async fn place_order(
         }));
     }
 
-    println!(
-        "placed order in {}ms",
-        order_place_time.elapsed().as_micros()
-    );
-
-    let received_time = Instant::now();
-
     match rx.await {
         Ok(response) => {
-            println!(
-                "order response received in {}ms",
-                received_time.elapsed().as_micros(),
-            );
+            app_state.record_latency("PlaceOrder", order_place_time.elapsed());
             response.into_http_response()
         }
         Err(e) => HttpResponse::InternalServerError().json(json!({
@@ -84,10 +86,75 @@ pub async fn cancel_order(
     let received_time = Instant::now();
     match rx.await {
         Ok(response) => {
-            println!(
-                "cancel order response received in {}ms",
-                received_time.elapsed().as_millis()
-            );
+            app_state.record_latency("CancelOrder", received_time.elapsed());
+            response.into_http_response()
+        }
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": e.to_string(),
+        })),
+    }
+}
+
+#[delete("/cancel-all")]
+pub async fn mass_cancel(
+    req: web::Json<MassCancelRequest>,
+    app_state: web::Data<HttpServerAppState>,
+) -> impl Responder {
+    let body = req.into_inner();
+    let mass_cancel = MassCancel::new(body.user_id, body.symbol, body.side);
+    let (tx, rx) = oneshot::channel::<CommandResponse>();
+
+    if let Err(e) = app_state
+        .order_tx
+        .send((OrderCommand::MassCancel(mass_cancel), tx))
+    {
+        return HttpResponse::InternalServerError().json(json!({
+            "error": "Failed to send mass cancel to engine",
+            "message": e.to_string()
+        }));
+    }
+
+    let received_time = Instant::now();
+    match rx.await {
+        Ok(response) => {
+            app_state.record_latency("MassCancel", received_time.elapsed());
+            response.into_http_response()
+        }
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": e.to_string(),
+        })),
+    }
+}
+
+#[patch("/amend")]
+pub async fn amend_order(
+    req: web::Json<AmendOrderRequest>,
+    app_state: web::Data<HttpServerAppState>,
+) -> impl Responder {
+    let body = req.into_inner();
+    let amend_order = AmendOrder::new(
+        body.order_id,
+        body.user_id,
+        body.symbol,
+        body.price,
+        body.quantity,
+    );
+    let (tx, rx) = oneshot::channel::<CommandResponse>();
+
+    if let Err(e) = app_state
+        .order_tx
+        .send((OrderCommand::AmendOrder(amend_order), tx))
+    {
+        return HttpResponse::InternalServerError().json(json!({
+            "error": "Failed to send amend order to engine",
+            "message": e.to_string()
+        }));
+    }
+
+    let received_time = Instant::now();
+    match rx.await {
+        Ok(response) => {
+            app_state.record_latency("AmendOrder", received_time.elapsed());
             response.into_http_response()
         }
         Err(e) => HttpResponse::InternalServerError().json(json!({
@@ -96,17 +163,39 @@ pub async fn cancel_order(
     }
 }
 
+#[get("/exchange-info")]
+pub async fn get_exchange_info(app_state: web::Data<HttpServerAppState>) -> impl Responder {
+    let (tx, rx) = oneshot::channel::<CommandResponse>();
+    if let Err(e) = app_state.order_tx.send((OrderCommand::GetExchangeInfo, tx)) {
+        return HttpResponse::InternalServerError().json(json!({
+            "error": "Failed to send get exchange info to engine",
+            "message": e.to_string()
+        }));
+    }
+
+    match rx.await {
+        Ok(response) => response.into_http_response(),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": e.to_string(),
+        })),
+    }
+}
+
 #[get("/depth/{symbol}")]
 pub async fn get_depth(
     path: web::Path<String>,
     query: web::Query<DepthQuery>,
     app_state: web::Data<HttpServerAppState>,
 ) -> impl Responder {
-    let _symbol = path.into_inner();
-    let _limit = query.into_inner().limit;
+    let symbol = path.into_inner();
+    let limit = query.into_inner().limit;
+    let get_depth = GetDepth::new(symbol, limit);
 
     let (tx, rx) = oneshot::channel::<CommandResponse>();
-    if let Err(e) = app_state.order_tx.send((OrderCommand::GetDepth, tx)) {
+    if let Err(e) = app_state
+        .order_tx
+        .send((OrderCommand::GetDepth(get_depth), tx))
+    {
         return HttpResponse::InternalServerError().json(json!({
             "error": "Failed to send get depth to engine",
             "message": e.to_string()
@@ -116,10 +205,7 @@ pub async fn get_depth(
     let received_time = Instant::now();
     match rx.await {
         Ok(response) => {
-            println!(
-                "get depth response received in {}ms",
-                received_time.elapsed().as_millis()
-            );
+            app_state.record_latency("GetDepth", received_time.elapsed());
             response.into_http_response()
         }
 
@@ -1,4 +1,5 @@
 pub mod app;
+pub mod auth;
 pub mod handlers;
 pub mod lib;
 pub mod models;
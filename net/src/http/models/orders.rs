@@ -1,12 +1,21 @@
 use actix_web::HttpResponse;
-use protocol::types::{OrderId, OrderType, Price, Quantity, RejectReason, Side, UserId};
+use protocol::types::{
+    InstrumentSpec, OrderId, OrderType, PostOnly, Price, Quantity, RejectReason,
+    SelfTradePrevention, SessionState, Side, TimeInForce, TrailingOffset, UserId,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CommandResponse {
     PlaceOrder(OrderResponse),
     CancelOrder(CancelOrderResponse),
+    AmendOrder(AmendOrderResponse),
+    MassCancel(MassCancelResponse),
     Depth(DepthResponse),
+    ExchangeInfo(ExchangeInfoResponse),
+    SessionState(SessionStateResponse),
+    Deposit(DepositResponse),
+    Reject(RejectResponse),
 }
 
 impl CommandResponse {
@@ -14,7 +23,13 @@ impl CommandResponse {
         match self {
             CommandResponse::PlaceOrder(resp) => resp.into_http_response(),
             CommandResponse::CancelOrder(resp) => resp.into_http_response(),
+            CommandResponse::AmendOrder(resp) => resp.into_http_response(),
+            CommandResponse::MassCancel(resp) => resp.into_http_response(),
             CommandResponse::Depth(resp) => HttpResponse::Ok().json(resp),
+            CommandResponse::ExchangeInfo(resp) => HttpResponse::Ok().json(resp),
+            CommandResponse::SessionState(resp) => resp.into_http_response(),
+            CommandResponse::Deposit(resp) => resp.into_http_response(),
+            CommandResponse::Reject(resp) => HttpResponse::BadRequest().json(resp),
         }
     }
 }
@@ -25,8 +40,27 @@ pub struct OrderRequest {
     pub symbol: String,
     pub side: Side,
     pub order_type: OrderType,
-    pub quantity: Quantity,
+    #[serde(default)]
+    pub quantity: Quantity, // may be omitted when `quote_quantity` is set
+    #[serde(default)]
+    pub quote_quantity: Option<Quantity>,
+    #[serde(default)]
+    pub display_quantity: Option<Quantity>,
     pub price: Option<Price>,
+    #[serde(default)]
+    pub stop_price: Option<Price>,
+    #[serde(default)]
+    pub trailing_offset: Option<TrailingOffset>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub expire_at: Option<i64>,
+    #[serde(default)]
+    pub post_only: Option<PostOnly>,
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    #[serde(default)]
+    pub max_slippage_bps: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +117,73 @@ impl CancelOrderResponse {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmendOrderRequest {
+    pub user_id: UserId,
+    pub symbol: String,
+    pub order_id: OrderId,
+    pub price: Option<Price>,
+    pub quantity: Option<Quantity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AmendOrderResponse {
+    Ack {
+        order_id: OrderId,
+        user_id: UserId,
+        symbol: String,
+        price: Price,
+        quantity: Quantity,
+    },
+    Reject {
+        order_id: OrderId,
+        reason: RejectReason,
+        message: String,
+    },
+}
+
+impl AmendOrderResponse {
+    pub fn into_http_response(self) -> HttpResponse {
+        match self {
+            AmendOrderResponse::Ack { .. } => HttpResponse::Ok().json(self),
+            AmendOrderResponse::Reject { .. } => HttpResponse::BadRequest().json(self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MassCancelRequest {
+    pub user_id: UserId,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub side: Option<Side>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MassCancelResponse {
+    Ack {
+        user_id: UserId,
+        symbol: Option<String>,
+        side: Option<Side>,
+        cancelled: usize,
+    },
+    Reject {
+        user_id: UserId,
+        reason: RejectReason,
+        message: String,
+    },
+}
+
+impl MassCancelResponse {
+    pub fn into_http_response(self) -> HttpResponse {
+        match self {
+            MassCancelResponse::Ack { .. } => HttpResponse::Ok().json(self),
+            MassCancelResponse::Reject { .. } => HttpResponse::BadRequest().json(self),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DepthQuery {
     pub limit: usize,
@@ -93,3 +194,74 @@ pub struct DepthResponse {
     pub bids: Vec<(Price, Quantity)>,
     pub asks: Vec<(Price, Quantity)>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeInfoResponse {
+    pub symbols: Vec<InstrumentSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStateRequest {
+    pub symbol: String,
+    pub state: SessionState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SessionStateResponse {
+    Ack {
+        symbol: String,
+        previous: SessionState,
+        state: SessionState,
+    },
+    Reject {
+        symbol: String,
+        reason: RejectReason,
+        message: String,
+    },
+}
+
+impl SessionStateResponse {
+    pub fn into_http_response(self) -> HttpResponse {
+        match self {
+            SessionStateResponse::Ack { .. } => HttpResponse::Ok().json(self),
+            SessionStateResponse::Reject { .. } => HttpResponse::BadRequest().json(self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositRequest {
+    pub user_id: UserId,
+    pub asset: String,
+    pub amount: Quantity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DepositResponse {
+    Ack {
+        user_id: UserId,
+        asset: String,
+        available: Quantity,
+    },
+    Reject {
+        user_id: UserId,
+        asset: String,
+        reason: RejectReason,
+        message: String,
+    },
+}
+
+impl DepositResponse {
+    pub fn into_http_response(self) -> HttpResponse {
+        match self {
+            DepositResponse::Ack { .. } => HttpResponse::Ok().json(self),
+            DepositResponse::Reject { .. } => HttpResponse::BadRequest().json(self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectResponse {
+    pub reason: RejectReason,
+    pub message: String,
+}
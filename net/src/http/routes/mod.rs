@@ -1,5 +1,9 @@
-use crate::http::handlers::orders::{cancel_order, get_depth, ping, place_order};
-use actix_web::web;
+use crate::http::auth::require_operator;
+use crate::http::handlers::admin::{deposit, set_session_state};
+use crate::http::handlers::orders::{
+    amend_order, cancel_order, get_depth, get_exchange_info, mass_cancel, ping, place_order,
+};
+use actix_web::{middleware::from_fn, web};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -8,8 +12,21 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(
                 web::scope("/orders")
                     .service(place_order)
-                    .service(cancel_order),
+                    .service(cancel_order)
+                    .service(mass_cancel)
+                    .service(amend_order),
             )
-            .service(get_depth),
+            .service(get_depth)
+            .service(get_exchange_info),
+    );
+}
+
+/// Routes served only on the operator listener, each behind the operator token
+pub fn admin_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/v1/admin")
+            .wrap(from_fn(require_operator))
+            .service(set_session_state)
+            .service(deposit),
     );
 }
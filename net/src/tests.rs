@@ -1,4 +1,7 @@
+use crate::http::app::AdminServerAppState;
 use crate::http::models::orders::*;
+use crate::http::routes::{admin_config, config};
+use actix_web::{App, test as actix_test, web};
 use protocol::types::*;
 use serde_json;
 
@@ -16,7 +19,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -38,7 +50,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Market,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: None, // Market orders don't have price
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -56,7 +77,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Limit,
             quantity: 100,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(60000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -264,6 +294,96 @@ mod tests {
         assert_eq!(http_resp.status().as_u16(), 400);
     }
 
+    // AmendOrder Tests
+
+    #[test]
+    fn test_amend_order_request_serialization() {
+        let json = r#"{"user_id":100,"symbol":"SOL_USDC","order_id":1,"quantity":20}"#;
+        let request: AmendOrderRequest = serde_json::from_str(json).unwrap();
+
+        assert_eq!(request.order_id, 1);
+        assert_eq!(request.price, None);
+        assert_eq!(request.quantity, Some(20));
+    }
+
+    #[test]
+    fn test_amend_order_response_into_http_response() {
+        let ack = CommandResponse::AmendOrder(AmendOrderResponse::Ack {
+            order_id: 1,
+            user_id: 100,
+            symbol: "SOL_USDC".to_string(),
+            price: 50000,
+            quantity: 20,
+        });
+        assert_eq!(ack.into_http_response().status().as_u16(), 200);
+
+        let reject = CommandResponse::AmendOrder(AmendOrderResponse::Reject {
+            order_id: 1,
+            reason: RejectReason::InvalidOrder,
+            message: "Order not found".to_string(),
+        });
+        assert_eq!(reject.into_http_response().status().as_u16(), 400);
+    }
+
+    // MassCancel Tests
+
+    #[test]
+    fn test_mass_cancel_response_into_http_response() {
+        let ack = CommandResponse::MassCancel(MassCancelResponse::Ack {
+            user_id: 100,
+            symbol: None,
+            side: Some(Side::Buy),
+            cancelled: 3,
+        });
+        assert_eq!(ack.into_http_response().status().as_u16(), 200);
+
+        let reject = CommandResponse::MassCancel(MassCancelResponse::Reject {
+            user_id: 100,
+            reason: RejectReason::SymbolNotFound,
+            message: "Symbol not found: ETH_USDC".to_string(),
+        });
+        assert_eq!(reject.into_http_response().status().as_u16(), 400);
+    }
+
+    // ExchangeInfo Tests
+
+    #[test]
+    fn test_exchange_info_response_into_http_response() {
+        let response = CommandResponse::ExchangeInfo(ExchangeInfoResponse {
+            symbols: vec![InstrumentSpec::new("SOL_USDC")],
+        });
+
+        assert_eq!(response.into_http_response().status().as_u16(), 200);
+    }
+
+    // SessionState Tests
+
+    #[test]
+    fn test_session_state_request_deserialization() {
+        let json = r#"{"symbol":"SOL_USDC","state":"Halted"}"#;
+        let request: SessionStateRequest = serde_json::from_str(json).unwrap();
+
+        assert_eq!(request.symbol, "SOL_USDC");
+        assert_eq!(request.state, SessionState::Halted);
+    }
+
+    #[test]
+    fn test_session_state_response_into_http_response() {
+        let ack = SessionStateResponse::Ack {
+            symbol: "SOL_USDC".to_string(),
+            previous: SessionState::Continuous,
+            state: SessionState::Halted,
+        };
+        assert_eq!(ack.into_http_response().status().as_u16(), 200);
+
+        let reject = SessionStateResponse::Reject {
+            symbol: "SOL_USDC".to_string(),
+            reason: RejectReason::InvalidOrder,
+            message: "Cannot move SOL_USDC from Halted to Halted".to_string(),
+        };
+        assert_eq!(reject.into_http_response().status().as_u16(), 400);
+    }
+
     // CommandResponse Tests
 
     #[test]
@@ -349,6 +469,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_command_response_reject() {
+        let reject = CommandResponse::Reject(RejectResponse {
+            reason: RejectReason::SymbolNotFound,
+            message: "Symbol not found: DOGE_USDC".to_string(),
+        });
+
+        let json = serde_json::to_string(&reject).unwrap();
+        let deserialized: CommandResponse = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            CommandResponse::Reject(r) => {
+                assert!(matches!(r.reason, RejectReason::SymbolNotFound));
+                assert_eq!(r.message, "Symbol not found: DOGE_USDC");
+            }
+            _ => panic!("Expected Reject variant"),
+        }
+    }
+
     #[test]
     fn test_command_response_into_http_response() {
         // Test PlaceOrder Ack
@@ -395,6 +534,14 @@ mod tests {
         });
         let http_resp = depth.into_http_response();
         assert_eq!(http_resp.status().as_u16(), 200);
+
+        // Test Reject
+        let reject = CommandResponse::Reject(RejectResponse {
+            reason: RejectReason::SymbolNotFound,
+            message: "Test".to_string(),
+        });
+        let http_resp = reject.into_http_response();
+        assert_eq!(http_resp.status().as_u16(), 400);
     }
 
     #[test]
@@ -502,7 +649,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Market,
             quantity: 999,
+            quote_quantity: None,
+            display_quantity: None,
             price: None,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -558,7 +714,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 0,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -574,7 +739,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: u64::MAX,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(u64::MAX),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -624,7 +798,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -640,11 +823,153 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
         let deserialized: OrderRequest = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.symbol, "BTC/₿");
     }
+
+    // Admin Tests
+
+    fn admin_app_state(
+        order_tx: crossbeam_channel::Sender<(OrderCommand, oneshot::Sender<CommandResponse>)>,
+    ) -> web::Data<AdminServerAppState> {
+        web::Data::new(AdminServerAppState {
+            order_tx,
+            operator_token: "operator-secret".to_string(),
+        })
+    }
+
+    fn deposit_request() -> DepositRequest {
+        DepositRequest {
+            user_id: 1,
+            asset: "USDC".to_string(),
+            amount: 100_000,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_admin_deposit_requires_operator_token() {
+        let (order_tx, order_rx) = crossbeam_channel::unbounded();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(admin_app_state(order_tx))
+                .configure(admin_config),
+        )
+        .await;
+
+        let anonymous = actix_test::TestRequest::post()
+            .uri("/api/v1/admin/deposit")
+            .set_json(deposit_request())
+            .to_request();
+        let response = actix_test::call_service(&app, anonymous).await;
+        assert_eq!(response.status().as_u16(), 401);
+
+        let wrong_token = actix_test::TestRequest::post()
+            .uri("/api/v1/admin/deposit")
+            .insert_header(("Authorization", "Bearer operator-guess"))
+            .set_json(deposit_request())
+            .to_request();
+        let response = actix_test::call_service(&app, wrong_token).await;
+        assert_eq!(response.status().as_u16(), 401);
+
+        assert!(order_rx.try_recv().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_admin_deposit_with_operator_token_reaches_engine() {
+        let (order_tx, order_rx) = crossbeam_channel::unbounded();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(admin_app_state(order_tx))
+                .configure(admin_config),
+        )
+        .await;
+
+        let engine = std::thread::spawn(move || {
+            let (command, reply_tx) = order_rx.recv().unwrap();
+            let OrderCommand::Deposit(deposit) = command else {
+                panic!("Expected a deposit, got {:?}", command);
+            };
+            reply_tx
+                .send(CommandResponse::Deposit(DepositResponse::Ack {
+                    user_id: deposit.user_id,
+                    asset: deposit.asset,
+                    available: deposit.amount,
+                }))
+                .unwrap();
+        });
+
+        let request = actix_test::TestRequest::post()
+            .uri("/api/v1/admin/deposit")
+            .insert_header(("Authorization", "Bearer operator-secret"))
+            .set_json(deposit_request())
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        engine.join().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_admin_session_requires_operator_token() {
+        let (order_tx, order_rx) = crossbeam_channel::unbounded();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(admin_app_state(order_tx))
+                .configure(admin_config),
+        )
+        .await;
+
+        let request = actix_test::TestRequest::post()
+            .uri("/api/v1/admin/session")
+            .set_json(SessionStateRequest {
+                symbol: "SOL_USDC".to_string(),
+                state: SessionState::Halted,
+            })
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+        assert!(order_rx.try_recv().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_public_routes_do_not_serve_session_state() {
+        let app = actix_test::init_service(App::new().configure(config)).await;
+
+        let request = actix_test::TestRequest::post()
+            .uri("/api/v1/admin/session")
+            .set_json(SessionStateRequest {
+                symbol: "SOL_USDC".to_string(),
+                state: SessionState::Halted,
+            })
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_public_routes_do_not_serve_deposit() {
+        let app = actix_test::init_service(App::new().configure(config)).await;
+
+        let request = actix_test::TestRequest::post()
+            .uri("/api/v1/admin/deposit")
+            .set_json(deposit_request())
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
 }
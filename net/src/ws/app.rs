@@ -4,7 +4,8 @@ use tokio::{net::TcpListener, sync::RwLock, task::JoinHandle};
 
 use crate::ws::broadcasters::{
     depth::broadcast_depth_events, order_update::broadcast_order_update_events,
-    ticker::broadcast_ticker_events, trade::broadcast_trade_events,
+    status::broadcast_status_events, ticker::broadcast_ticker_events,
+    trade::broadcast_trade_events,
 };
 use crate::ws::{client_manager::UserManager, lib::handle_connection};
 
@@ -34,6 +35,7 @@ impl WsServerApp {
         let depth_user_manager = user_manager.clone();
         let ticker_user_manager = user_manager.clone();
         let order_update_user_manager = user_manager.clone();
+        let status_user_manager = user_manager.clone();
 
         let mut broadcaster_handles = Vec::new();
 
@@ -57,6 +59,11 @@ impl WsServerApp {
             let _ = broadcast_order_update_events(order_update_user_manager, redis_order).await;
         }));
 
+        let redis_status = redis_client.clone();
+        broadcaster_handles.push(tokio::spawn(async move {
+            let _ = broadcast_status_events(status_user_manager, redis_status).await;
+        }));
+
         let handle = tokio::spawn(async move {
             loop {
                 match listener.accept().await {
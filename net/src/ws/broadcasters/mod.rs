@@ -1,4 +1,5 @@
 pub mod depth;
 pub mod order_update;
+pub mod status;
 pub mod ticker;
 pub mod trade;
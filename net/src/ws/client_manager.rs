@@ -9,6 +9,7 @@ pub struct UserInfo {
     pub subscribed_trades: HashSet<String>,
     pub subscribed_tickers: HashSet<String>,
     pub subscribed_depth: HashSet<String>,
+    pub subscribed_status: HashSet<String>,
 }
 
 pub struct UserManager {
@@ -37,6 +38,7 @@ impl UserManager {
                 subscribed_trades: HashSet::new(),
                 subscribed_tickers: HashSet::new(),
                 subscribed_depth: HashSet::new(),
+                subscribed_status: HashSet::new(),
             },
         );
 
@@ -226,3 +228,45 @@ impl UserManager {
         }
     }
 }
+
+impl UserManager {
+    pub fn subscribe_status(&mut self, user_addr: &str, symbol: &str) {
+        if let Some(user) = self.users.get_mut(user_addr) {
+            user.subscribed_status.insert(symbol.to_string());
+            println!(
+                "[UserManager] User subscribed to status: {} -> {}",
+                user_addr, symbol
+            );
+        } else {
+            println!("[UserManager] User not found: {}", user_addr);
+        }
+    }
+
+    pub fn unsubscribe_status(&mut self, user_addr: &str, symbol: &str) {
+        if let Some(user) = self.users.get_mut(user_addr) {
+            user.subscribed_status.remove(symbol);
+            println!(
+                "[UserManager] User unsubscribed from status: {} -> {}",
+                user_addr, symbol
+            );
+        } else {
+            println!("[UserManager] User not found: {}", user_addr);
+        }
+    }
+
+    pub async fn broadcast_status(&mut self, symbol: &str, status: &str) {
+        let mut dead = Vec::new();
+        for (addr, user) in self.users.iter_mut() {
+            if user.subscribed_status.contains(symbol) {
+                let message = Message::text(status);
+                if let Err(e) = user.writer.send(message).await {
+                    eprintln!("Could not send status to {}: {}", addr, e);
+                    dead.push(addr.clone());
+                }
+            }
+        }
+        for addr in dead {
+            self.remove_user(&addr);
+        }
+    }
+}
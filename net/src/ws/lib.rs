@@ -127,6 +127,20 @@ async fn handle_message(
                     .unsubscribe_ticker(&user_addr, &msg.symbol);
             }
         },
+        Event::STATUS => match msg.method {
+            Method::SUBSCRIBE => {
+                user_manager
+                    .write()
+                    .await
+                    .subscribe_status(user_addr, &msg.symbol);
+            }
+            Method::UNSUBSCRIBE => {
+                user_manager
+                    .write()
+                    .await
+                    .unsubscribe_status(user_addr, &msg.symbol);
+            }
+        },
         Event::ORDERUPDATE => match msg.method {
             Method::SUBSCRIBE => {
                 user_manager
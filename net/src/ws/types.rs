@@ -23,6 +23,7 @@ pub enum Event {
     DEPTH,
     TICKER,
     ORDERUPDATE,
+    STATUS,
 }
 
 #[allow(non_camel_case_types)]
@@ -42,4 +43,10 @@ impl RegisteredSymbols {
         }
         None
     }
+
+    pub fn all() -> Vec<String> {
+        RegisteredSymbols::iter()
+            .map(|symbol| symbol.to_string())
+            .collect()
+    }
 }
@@ -8,4 +8,6 @@ pub enum PersistenceError {
     Connection(String),
     #[error("Serialization error: {0}")]
     Serialization(String),
+    #[error("Invalid market: {0}")]
+    InvalidMarket(String),
 }
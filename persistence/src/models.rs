@@ -1,4 +1,7 @@
-use protocol::types::{OrderId, OrderType, Price, Quantity, Side, Symbol, TradeId, UserId};
+use crate::error::PersistenceError;
+use protocol::types::{
+    InstrumentSpec, Liquidity, OrderId, OrderType, Price, Quantity, Side, Symbol, TradeId, UserId,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -36,6 +39,11 @@ pub struct OrderRow {
     pub filled_quantity: Quantity,
     pub remaining_quantity: Quantity,
     pub order_status: String,
+    // Fees paid over all fills so far, negative for a net rebate, and
+    // whether the latest fill was maker or taker
+    pub fee: i64,
+    pub fee_asset: Option<String>,
+    pub liquidity: Option<Liquidity>,
     pub timestamp: i64,
 }
 
@@ -58,6 +66,10 @@ pub struct TradeRow {
     pub taker_user_id: Option<UserId>,
     pub price: Price,
     pub quantity: Quantity,
+    pub maker_fee: i64,
+    pub maker_fee_asset: String,
+    pub taker_fee: i64,
+    pub taker_fee_asset: String,
     pub timestamp: i64,
 }
 
@@ -136,6 +148,27 @@ impl MarketRow {
     }
 }
 
+impl TryFrom<MarketRow> for InstrumentSpec {
+    type Error = PersistenceError;
+
+    fn try_from(row: MarketRow) -> Result<Self, Self::Error> {
+        let spec = Self {
+            symbol: row.symbol,
+            base: row.base,
+            quote: row.quote,
+            tick_size: row.tick_size,
+            min_price: row.min_price,
+            max_price: row.max_price,
+            step_size: row.step_size,
+            min_quantity: row.min_quantity,
+            max_quantity: row.max_quantity,
+        };
+
+        spec.validate().map_err(PersistenceError::InvalidMarket)?;
+        Ok(spec)
+    }
+}
+
 impl TradeRow {
     pub fn new(
         trade_id: TradeId,
@@ -157,9 +190,27 @@ impl TradeRow {
             taker_user_id,
             price,
             quantity,
+            maker_fee: 0,
+            maker_fee_asset: String::new(),
+            taker_fee: 0,
+            taker_fee_asset: String::new(),
             timestamp,
         }
     }
+
+    pub fn with_fees(
+        mut self,
+        maker_fee: i64,
+        maker_fee_asset: String,
+        taker_fee: i64,
+        taker_fee_asset: String,
+    ) -> Self {
+        self.maker_fee = maker_fee;
+        self.maker_fee_asset = maker_fee_asset;
+        self.taker_fee = taker_fee;
+        self.taker_fee_asset = taker_fee_asset;
+        self
+    }
 }
 
 impl CancelOrderRow {
@@ -194,6 +245,9 @@ impl OrderRow {
             filled_quantity: 0,
             remaining_quantity: 0,
             order_status: "Pending".to_string(),
+            fee: 0,
+            fee_asset: None,
+            liquidity: None,
             timestamp,
         }
     }
@@ -217,6 +271,9 @@ impl OrderRow {
             filled_quantity: 0,
             remaining_quantity: 0,
             order_status: reason,
+            fee: 0,
+            fee_asset: None,
+            liquidity: None,
             timestamp,
         }
     }
@@ -245,7 +302,17 @@ impl OrderRow {
             filled_quantity,
             remaining_quantity,
             order_status: status,
+            fee: 0,
+            fee_asset: None,
+            liquidity: None,
             timestamp,
         }
     }
+
+    pub fn with_fee(mut self, fee: i64, fee_asset: String, liquidity: Liquidity) -> Self {
+        self.fee = fee;
+        self.fee_asset = Some(fee_asset);
+        self.liquidity = Some(liquidity);
+        self
+    }
 }
@@ -1,9 +1,23 @@
 use scylla::client::{session::Session, session_builder::SessionBuilder};
 
-use crate::error::PersistenceError;
+use crate::{error::PersistenceError, models::MarketRow};
 
 pub type Result<T> = std::result::Result<T, PersistenceError>;
 
+// symbol, base, quote, max_price, min_price, tick_size, max_quantity,
+// min_quantity, step_size
+type MarketColumns = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+);
+
 pub struct ScyllaDb {
     session: Session,
     keyspace: String,
@@ -33,6 +47,7 @@ impl ScyllaDb {
         self.create_order_table().await?;
         self.create_cancel_order_table().await?;
         self.create_trade_table().await?;
+        self.add_fee_columns().await?;
         self.create_market_table().await?;
         self.create_ticker_table().await?;
         Ok(())
@@ -46,6 +61,59 @@ impl ScyllaDb {
         &self.keyspace
     }
 
+    /// Every market and its trading rules, which the engine validates
+    /// orders against.
+    pub async fn load_markets(&self) -> Result<Vec<MarketRow>> {
+        let query = format!(
+            r#"
+            SELECT symbol, base, quote, max_price, min_price, tick_size,
+                max_quantity, min_quantity, step_size
+            FROM {}.markets
+            "#,
+            self.keyspace
+        );
+
+        let rows = self
+            .session
+            .query_unpaged(query, &[])
+            .await
+            .map_err(|e| PersistenceError::Scylla(e.to_string()))?
+            .into_rows_result()
+            .map_err(|e| PersistenceError::Scylla(e.to_string()))?;
+
+        let mut markets = Vec::new();
+        for row in rows
+            .rows::<MarketColumns>()
+            .map_err(|e| PersistenceError::Serialization(e.to_string()))?
+        {
+            let (
+                symbol,
+                base,
+                quote,
+                max_price,
+                min_price,
+                tick_size,
+                max_quantity,
+                min_quantity,
+                step_size,
+            ) = row.map_err(|e| PersistenceError::Serialization(e.to_string()))?;
+
+            markets.push(MarketRow::new(
+                symbol,
+                base,
+                quote,
+                parse_column("max_price", &max_price)?,
+                parse_column("min_price", &min_price)?,
+                parse_column("tick_size", &tick_size)?,
+                parse_column("max_quantity", &max_quantity)?,
+                parse_column("min_quantity", &min_quantity)?,
+                parse_column("step_size", &step_size)?,
+            ));
+        }
+
+        Ok(markets)
+    }
+
     async fn create_keyspace(&self) -> Result<()> {
         let create_keyspace_query = format!(
             r#"
@@ -100,6 +168,9 @@ impl ScyllaDb {
                 filled_quantity bigint,
                 remaining_quantity bigint,
                 order_status text,
+                fee bigint,
+                fee_asset text,
+                liquidity text,
                 timestamp bigint,
                 PRIMARY KEY ((symbol), order_id, timestamp)
             ) WITH CLUSTERING ORDER BY (order_id DESC, timestamp DESC)
@@ -150,6 +221,10 @@ impl ScyllaDb {
                 taker_user_id bigint,
                 price bigint,
                 quantity bigint,
+                maker_fee bigint,
+                maker_fee_asset text,
+                taker_fee bigint,
+                taker_fee_asset text,
                 timestamp bigint,
                 PRIMARY KEY ((symbol), trade_id, timestamp)
             ) WITH CLUSTERING ORDER BY (trade_id DESC, timestamp DESC)
@@ -165,6 +240,40 @@ impl ScyllaDb {
         Ok(())
     }
 
+    /// Fee columns of the orders and trades tables, which `CREATE TABLE IF
+    /// NOT EXISTS` leaves out of tables created before they were added.
+    async fn add_fee_columns(&self) -> Result<()> {
+        let columns = [
+            ("orders", "fee", "bigint"),
+            ("orders", "fee_asset", "text"),
+            ("orders", "liquidity", "text"),
+            ("trades", "maker_fee", "bigint"),
+            ("trades", "maker_fee_asset", "text"),
+            ("trades", "taker_fee", "bigint"),
+            ("trades", "taker_fee_asset", "text"),
+        ];
+
+        for (table, column, column_type) in columns {
+            self.add_column(table, column, column_type).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds `column` to `table`, doing nothing if the table already has it.
+    async fn add_column(&self, table: &str, column: &str, column_type: &str) -> Result<()> {
+        let add_column_query = format!(
+            "ALTER TABLE {}.{} ADD {} {}",
+            self.keyspace, table, column, column_type
+        );
+
+        match self.session.query_unpaged(add_column_query, &[]).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_existing_column(&e.to_string()) => Ok(()),
+            Err(e) => Err(PersistenceError::Scylla(e.to_string())),
+        }
+    }
+
     async fn create_market_table(&self) -> Result<()> {
         let create_market_table_query = format!(
             r#"
@@ -217,3 +326,16 @@ impl ScyllaDb {
         Ok(())
     }
 }
+
+/// Whether an `ALTER TABLE ... ADD` failed only because the column is
+/// already there, as Scylla and Cassandra word it.
+fn is_existing_column(error: &str) -> bool {
+    error.contains("conflicts with an existing column") || error.contains("already exists")
+}
+
+/// Numeric columns of the markets table are stored as text.
+fn parse_column(column: &str, value: &str) -> Result<u64> {
+    value.parse().map_err(|e| {
+        PersistenceError::Serialization(format!("Invalid {} {:?}: {}", column, value, e))
+    })
+}
@@ -3,7 +3,9 @@ use std::time::{Duration, Instant};
 
 use chrono::Utc;
 use crossbeam_channel::{Receiver, RecvTimeoutError};
-use protocol::types::{Event, Fill, OrderAck, OrderCancelled, OrderId, OrderReject, Side, Trade};
+use protocol::types::{
+    Event, Fill, OrderAck, OrderAmended, OrderCancelled, OrderId, OrderReject, Side, Trade,
+};
 
 use crate::{
     error::PersistenceError,
@@ -26,6 +28,7 @@ struct OrderState {
     side: Option<Side>,
     initial_quantity: u64,
     filled_quantity: u64,
+    fee: i64,
 }
 
 impl PersistenceWriter {
@@ -117,7 +120,14 @@ impl PersistenceWriter {
             Event::OrderCancelled(order_cancelled) => {
                 self.persist_order_cancelled(order_cancelled).await
             }
+            Event::OrderAmended(order_amended) => {
+                self.apply_order_amended(order_amended);
+                Ok(())
+            }
+            Event::StopPriceUpdated(_) => Ok(()),
             Event::BookUpdate(_) => Ok(()), // optional
+            Event::SessionStateChanged(_) => Ok(()),
+            Event::IndicativePrice(_) => Ok(()),
         }
     }
 
@@ -130,6 +140,7 @@ impl PersistenceWriter {
                 side: None,
                 initial_quantity: 0,
                 filled_quantity: 0,
+                fee: 0,
             },
         );
 
@@ -180,6 +191,13 @@ impl PersistenceWriter {
         Ok(())
     }
 
+    // Later fills derive the order's quantities from this state
+    fn apply_order_amended(&mut self, order_amended: OrderAmended) {
+        if let Some(state) = self.order_state.get_mut(&order_amended.order_id) {
+            state.initial_quantity = order_amended.quantity;
+        }
+    }
+
     async fn persist_order_reject(&mut self, order_reject: OrderReject) -> Result<()> {
         let timestamp = Utc::now().timestamp_millis();
 
@@ -245,6 +263,7 @@ impl PersistenceWriter {
                 side: Some(fill.side.clone()),
                 initial_quantity: fill.filled_quantity + fill.remaining_quantity,
                 filled_quantity: 0,
+                fee: 0,
             });
 
         // If we didn't have initial quantity, set it now
@@ -254,6 +273,7 @@ impl PersistenceWriter {
 
         state.side = Some(fill.side.clone());
         state.filled_quantity += fill.filled_quantity;
+        state.fee = state.fee.saturating_add(fill.fee);
 
         let remaining = fill.remaining_quantity;
         let order_status = if remaining == 0 {
@@ -273,14 +293,16 @@ impl PersistenceWriter {
             remaining,
             order_status.to_string(),
             timestamp,
-        );
+        )
+        .with_fee(state.fee, fill.fee_asset, fill.liquidity);
 
         let query = format!(
             r#"
             INSERT INTO {}.orders (
                 order_id, user_id, symbol, side, order_type, price, initial_quantity,
-                filled_quantity, remaining_quantity, order_status, timestamp
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                filled_quantity, remaining_quantity, order_status, fee, fee_asset, liquidity,
+                timestamp
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             self.db.keyspace()
         );
@@ -306,6 +328,9 @@ impl PersistenceWriter {
                     row.filled_quantity as i64,
                     row.remaining_quantity as i64,
                     row.order_status,
+                    row.fee,
+                    row.fee_asset,
+                    row.liquidity.map(|l| format!("{:?}", l)),
                     row.timestamp,
                 ),
             )
@@ -326,14 +351,21 @@ impl PersistenceWriter {
             trade.price,
             trade.quantity,
             trade.timestamp,
+        )
+        .with_fees(
+            trade.maker_fee,
+            trade.maker_fee_asset,
+            trade.taker_fee,
+            trade.taker_fee_asset,
         );
 
         let query = format!(
             r#"
             INSERT INTO {}.trades (
                 trade_id, symbol, maker_order_id, maker_user_id, taker_order_id,
-                taker_user_id, price, quantity, timestamp
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                taker_user_id, price, quantity, maker_fee, maker_fee_asset, taker_fee,
+                taker_fee_asset, timestamp
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             self.db.keyspace()
         );
@@ -351,6 +383,10 @@ impl PersistenceWriter {
                     row.taker_user_id.unwrap_or(0) as i64,
                     row.price as i64,
                     row.quantity as i64,
+                    row.maker_fee,
+                    row.maker_fee_asset,
+                    row.taker_fee,
+                    row.taker_fee_asset,
                     row.timestamp,
                 ),
             )
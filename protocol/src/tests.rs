@@ -53,7 +53,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&order).unwrap();
@@ -124,7 +133,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 50,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let command = OrderCommand::PlaceOrder(order);
@@ -163,14 +181,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_order_command_amend_order_serialization() {
+        let amend = AmendOrder::new(1, 100, "SOL_USDC".to_string(), Some(49000), None);
+
+        let command = OrderCommand::AmendOrder(amend);
+        let json = serde_json::to_string(&command).unwrap();
+        let deserialized: OrderCommand = serde_json::from_str(&json).unwrap();
+
+        match deserialized {
+            OrderCommand::AmendOrder(a) => {
+                assert_eq!(a.order_id, 1);
+                assert_eq!(a.user_id, 100);
+                assert_eq!(a.price, Some(49000));
+                assert_eq!(a.quantity, None);
+            }
+            _ => panic!("Commands don't match"),
+        }
+    }
+
+    #[test]
+    fn test_order_command_mass_cancel_serialization() {
+        let json = r#"{"MassCancel":{"user_id":100}}"#;
+        let deserialized: OrderCommand = serde_json::from_str(json).unwrap();
+
+        match deserialized {
+            OrderCommand::MassCancel(m) => {
+                assert_eq!(m.user_id, 100);
+                assert_eq!(m.symbol, None);
+                assert_eq!(m.side, None);
+            }
+            _ => panic!("Commands don't match"),
+        }
+
+        let command = OrderCommand::MassCancel(MassCancel::new(
+            100,
+            Some("SOL_USDC".to_string()),
+            Some(Side::Sell),
+        ));
+        let json = serde_json::to_string(&command).unwrap();
+        assert!(json.contains(r#""side":"Sell""#));
+    }
+
     #[test]
     fn test_order_command_get_depth_serialization() {
-        let command = OrderCommand::GetDepth;
+        let command = OrderCommand::GetDepth(GetDepth::new("SOL_USDC".to_string(), 20));
         let json = serde_json::to_string(&command).unwrap();
         let deserialized: OrderCommand = serde_json::from_str(&json).unwrap();
 
         match (command, deserialized) {
-            (OrderCommand::GetDepth, OrderCommand::GetDepth) => {}
+            (OrderCommand::GetDepth(d1), OrderCommand::GetDepth(d2)) => {
+                assert_eq!(d1.symbol, d2.symbol);
+                assert_eq!(d1.limit, d2.limit);
+            }
             _ => panic!("Commands don't match"),
         }
     }
@@ -188,7 +251,12 @@ mod tests {
             symbol: "SOL_USDC".to_string(),
             quantity: 50,
             price: 50000,
+            maker_fee: 0,
+            maker_fee_asset: "USDC".to_string(),
+            taker_fee: 0,
+            taker_fee_asset: "SOL".to_string(),
             timestamp: 1234567890,
+            sequence: 0,
         };
 
         let event = Event::Trade(trade);
@@ -216,6 +284,10 @@ mod tests {
             filled_quantity: 25,
             filled_price: 50000,
             remaining_quantity: 25,
+            liquidity: Liquidity::Taker,
+            fee: 0,
+            fee_asset: "SOL".to_string(),
+            sequence: 0,
         };
 
         let event = Event::Fill(fill);
@@ -239,6 +311,7 @@ mod tests {
             order_id: 1,
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
+            sequence: 0,
         };
 
         let event = Event::OrderAck(ack);
@@ -263,6 +336,7 @@ mod tests {
             reason: RejectReason::InvalidQuantity,
             message: "Quantity must be greater than 0".to_string(),
             symbol: "SOL_USDC".to_string(),
+            sequence: 0,
         };
 
         let event = Event::OrderReject(reject);
@@ -287,6 +361,7 @@ mod tests {
             user_id: 100,
             symbol: "SOL_USDC".to_string(),
             reason: CancelReason::UserRequested,
+            sequence: 0,
         };
 
         let event = Event::OrderCancelled(cancelled);
@@ -316,6 +391,7 @@ mod tests {
                 quantity: 50,
             }],
             last_price: Some(50000),
+            sequence: 0,
         };
 
         let event = Event::BookUpdate(book_update);
@@ -360,6 +436,63 @@ mod tests {
         assert_eq!(sell, sell_deserialized);
     }
 
+    // TimeInForce Tests
+
+    #[test]
+    fn test_time_in_force_serialization() {
+        for tif in [
+            TimeInForce::GTC,
+            TimeInForce::IOC,
+            TimeInForce::FOK,
+            TimeInForce::GTD,
+            TimeInForce::DAY,
+        ] {
+            let json = serde_json::to_string(&tif).unwrap();
+            let deserialized: TimeInForce = serde_json::from_str(&json).unwrap();
+            assert_eq!(tif, deserialized);
+        }
+
+        assert_eq!(serde_json::to_string(&TimeInForce::IOC).unwrap(), "\"IOC\"");
+    }
+
+    #[test]
+    fn test_order_time_in_force_defaults_to_gtc() {
+        let json = r#"{
+            "order_id": 1,
+            "user_id": 100,
+            "symbol": "SOL_USDC",
+            "side": "Buy",
+            "order_type": "Limit",
+            "quantity": 50,
+            "price": 50000
+        }"#;
+
+        let order: Order = serde_json::from_str(json).unwrap();
+        assert_eq!(order.time_in_force, TimeInForce::GTC);
+
+        let order = order.with_time_in_force(TimeInForce::FOK);
+        assert_eq!(order.time_in_force, TimeInForce::FOK);
+    }
+
+    #[test]
+    fn test_order_post_only_serialization() {
+        let order = Order::new(
+            1,
+            100,
+            "SOL_USDC".to_string(),
+            Side::Sell,
+            OrderType::Limit,
+            10,
+            Some(50000),
+        );
+        assert_eq!(order.post_only, None);
+
+        let order = order.with_post_only(Some(PostOnly::Reprice));
+        let json = serde_json::to_string(&order).unwrap();
+        let deserialized: Order = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.post_only, Some(PostOnly::Reprice));
+    }
+
     // OrderType Tests
 
     #[test]
@@ -387,6 +520,109 @@ mod tests {
         assert_eq!(market, market_deserialized);
     }
 
+    // InstrumentSpec Tests
+
+    #[test]
+    fn test_instrument_spec_defaults() {
+        let spec = InstrumentSpec::new("SOL_USDC");
+
+        assert_eq!(spec.base, "SOL");
+        assert_eq!(spec.quote, "USDC");
+        assert_eq!(spec.tick_size, 1);
+        assert_eq!(spec.step_size, 1);
+        assert_eq!(spec.min_price, 1);
+        assert_eq!(spec.max_quantity, Quantity::MAX);
+    }
+
+    #[test]
+    fn test_instrument_spec_serialization() {
+        let spec = InstrumentSpec::new("BTC_USDC")
+            .with_price_rules(10, 100, 10_000_000)
+            .with_quantity_rules(1, 1, 1000);
+
+        let json = serde_json::to_string(&spec).unwrap();
+        let deserialized: InstrumentSpec = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(spec, deserialized);
+    }
+
+    #[test]
+    fn test_instrument_spec_validate() {
+        assert!(InstrumentSpec::new("SOL_USDC").validate().is_ok());
+
+        let invalid = [
+            InstrumentSpec::new("SOL_USDC").with_price_rules(0, 1, 100),
+            InstrumentSpec::new("SOL_USDC").with_quantity_rules(0, 1, 100),
+            InstrumentSpec::new("SOL_USDC").with_price_rules(1, 200, 100),
+            InstrumentSpec::new("SOL_USDC").with_quantity_rules(1, 200, 100),
+        ];
+
+        for spec in invalid {
+            assert!(spec.validate().is_err(), "{:?}", spec);
+        }
+    }
+
+    // SessionState Tests
+
+    #[test]
+    fn test_session_state_defaults_to_continuous() {
+        assert_eq!(SessionState::default(), SessionState::Continuous);
+        assert_eq!(SessionState::Halted.to_string(), "Halted");
+    }
+
+    #[test]
+    fn test_session_state_transitions() {
+        use SessionState::*;
+
+        let states = [PreOpen, Auction, Continuous, Halted, Closed];
+        let allowed = [
+            (PreOpen, Auction),
+            (PreOpen, Halted),
+            (PreOpen, Closed),
+            (Auction, Continuous),
+            (Auction, Halted),
+            (Auction, Closed),
+            (Continuous, Halted),
+            (Continuous, Closed),
+            (Halted, PreOpen),
+            (Halted, Auction),
+            (Halted, Closed),
+            (Closed, PreOpen),
+        ];
+
+        for from in states {
+            for to in states {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_session_state_serialization() {
+        let command = OrderCommand::SetSessionState(SetSessionState::new(
+            "SOL_USDC".to_string(),
+            SessionState::Halted,
+        ));
+
+        let json = serde_json::to_string(&command).unwrap();
+        assert!(json.contains("\"Halted\""));
+
+        let deserialized: OrderCommand = serde_json::from_str(&json).unwrap();
+        match deserialized {
+            OrderCommand::SetSessionState(set) => {
+                assert_eq!(set.symbol, "SOL_USDC");
+                assert_eq!(set.state, SessionState::Halted);
+            }
+            _ => panic!("Expected SetSessionState"),
+        }
+    }
+
     // RejectReason Tests
 
     #[test]
@@ -398,6 +634,11 @@ mod tests {
             (RejectReason::InsufficientBalance, "InsufficientBalance"),
             (RejectReason::SymbolNotFound, "SymbolNotFound"),
             (RejectReason::MarketClosed, "MarketClosed"),
+            (RejectReason::InsufficientLiquidity, "InsufficientLiquidity"),
+            (RejectReason::PostOnlyWouldCross, "PostOnlyWouldCross"),
+            (RejectReason::OrderNotFound, "OrderNotFound"),
+            (RejectReason::NotOrderOwner, "NotOrderOwner"),
+            (RejectReason::PriceOutsideBand, "PriceOutsideBand"),
             (RejectReason::InternalError, "InternalError"),
         ];
 
@@ -424,6 +665,12 @@ mod tests {
             CancelReason::SystemCancelled,
             CancelReason::Expired,
             CancelReason::Liquidation,
+            CancelReason::ImmediateOrCancel,
+            CancelReason::SelfTradePrevention,
+            CancelReason::SlippageExceeded,
+            CancelReason::VolatilityInterruption,
+            CancelReason::StopRejected,
+            CancelReason::InsufficientBalance,
         ];
 
         for reason in reasons {
@@ -479,7 +726,16 @@ mod tests {
             side: Side::Sell,
             order_type: OrderType::Market,
             quantity: 999,
+            quote_quantity: None,
+            display_quantity: None,
             price: None,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -505,7 +761,12 @@ mod tests {
             symbol: "ETH/USD".to_string(),
             quantity: 500,
             price: 60000,
+            maker_fee: 0,
+            maker_fee_asset: "USDC".to_string(),
+            taker_fee: 0,
+            taker_fee_asset: "SOL".to_string(),
             timestamp: 1234567890123,
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -519,6 +780,23 @@ mod tests {
         assert_eq!(original.timestamp, deserialized.timestamp);
     }
 
+    #[test]
+    fn test_event_sequence() {
+        let mut event = Event::OrderAck(OrderAck {
+            order_id: 1,
+            user_id: 2,
+            symbol: "SOL_USDC".to_string(),
+            sequence: 0,
+        });
+
+        event.set_sequence(17);
+        assert_eq!(event.sequence(), 17);
+
+        let json = serde_json::to_string(&event).unwrap();
+        let deserialized: Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.sequence(), 17);
+    }
+
     #[test]
     fn test_complete_book_update_round_trip() {
         let original = BookUpdate {
@@ -544,6 +822,7 @@ mod tests {
                 },
             ],
             last_price: Some(50000),
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -571,7 +850,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: 0,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(50000),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&order).unwrap();
@@ -588,7 +876,16 @@ mod tests {
             side: Side::Buy,
             order_type: OrderType::Limit,
             quantity: u64::MAX,
+            quote_quantity: None,
+            display_quantity: None,
             price: Some(u64::MAX),
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         };
 
         let json = serde_json::to_string(&order).unwrap();
@@ -606,6 +903,7 @@ mod tests {
             bids: vec![],
             asks: vec![],
             last_price: None,
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&book_update).unwrap();
@@ -637,6 +935,7 @@ mod tests {
             bids,
             asks,
             last_price: Some(50000),
+            sequence: 0,
         };
 
         let json = serde_json::to_string(&book_update).unwrap();
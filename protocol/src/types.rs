@@ -13,7 +13,12 @@ pub type Symbol = String;
 pub enum OrderCommand {
     PlaceOrder(Order),
     CancelOrder(CancelOrder),
-    GetDepth,
+    AmendOrder(AmendOrder),
+    MassCancel(MassCancel),
+    GetDepth(GetDepth),
+    GetExchangeInfo,
+    SetSessionState(SetSessionState),
+    Deposit(Deposit),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,7 +29,25 @@ pub struct Order {
     pub side: Side,
     pub order_type: OrderType,
     pub quantity: Quantity,
+    #[serde(default)]
+    pub quote_quantity: Option<Quantity>, // market buys only, amount of quote to spend instead of `quantity`
+    #[serde(default)]
+    pub display_quantity: Option<Quantity>, // iceberg peak, None shows everything
     pub price: Option<Price>, // None if market order
+    #[serde(default)]
+    pub stop_price: Option<Price>, // required for stop orders unless trailing
+    #[serde(default)]
+    pub trailing_offset: Option<TrailingOffset>, // makes a stop order trailing
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub expire_at: Option<i64>, // unix millis, required for GTD orders
+    #[serde(default)]
+    pub post_only: Option<PostOnly>, // None if the order may take liquidity
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePrevention>, // None uses the symbol default
+    #[serde(default)]
+    pub max_slippage_bps: Option<u64>, // market orders only, None uses the symbol default
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,19 +57,141 @@ pub struct CancelOrder {
     pub symbol: String,
 }
 
+// Changes a resting order in place. `quantity` is the new total order size,
+// including anything already filled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmendOrder {
+    pub order_id: OrderId,
+    pub user_id: UserId,
+    pub symbol: String,
+    pub price: Option<Price>,
+    pub quantity: Option<Quantity>,
+}
+
+// Cancels every open order of a user, optionally only on one symbol and/or side
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MassCancel {
+    pub user_id: UserId,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub side: Option<Side>,
+}
+
+// Admin command moving a symbol to another trading session state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetSessionState {
+    pub symbol: String,
+    pub state: SessionState,
+}
+
+// Admin command crediting a user's available balance of `asset`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deposit {
+    pub user_id: UserId,
+    pub asset: String,
+    pub amount: Quantity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetDepth {
+    pub symbol: String,
+    pub limit: usize,
+}
+
+// Trading rules of a symbol. Prices must be multiples of `tick_size` within
+// [min_price, max_price] and quantities multiples of `step_size` within
+// [min_quantity, max_quantity].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstrumentSpec {
+    pub symbol: Symbol,
+    pub base: String,
+    pub quote: String,
+    pub tick_size: Price,
+    pub min_price: Price,
+    pub max_price: Price,
+    pub step_size: Quantity,
+    pub min_quantity: Quantity,
+    pub max_quantity: Quantity,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Side {
     Buy,
     Sell,
 }
 
+// Stop orders wait off-book until the last trade price reaches `stop_price`,
+// then enter as a market or limit order.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OrderType {
     Limit,
     Market,
+    StopMarket,
+    StopLimit,
+}
+
+// Distance a trailing stop keeps from the highest best bid (sells) or lowest
+// best ask (buys) since placement.
+// Percentage offsets are in basis points (100 = 1%).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TrailingOffset {
+    Absolute(Price),
+    Percentage(u64),
+}
+
+// GTC rests until filled or cancelled, IOC cancels whatever does not fill
+// immediately, FOK either fills completely on arrival or is rejected.
+// GTD rests until `expire_at`, DAY until the engine's configured session end.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum TimeInForce {
+    #[default]
+    GTC,
+    IOC,
+    FOK,
+    GTD,
+    DAY,
 }
 
-// Outbound events (engine -> client)
+// What to do with a post-only order that would cross the spread on arrival:
+// reject it, or move its price one tick behind the opposite best.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PostOnly {
+    Reject,
+    Reprice,
+}
+
+// How to resolve an incoming order matching a resting order of the same user.
+// Newest is the incoming order, oldest the resting one. DecrementAndCancel
+// reduces both by the smaller quantity and cancels whichever reaches zero;
+// one that is only reduced is reported as OrderAmended.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum SelfTradePrevention {
+    #[default]
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    DecrementAndCancel,
+}
+
+// Trading phase of a symbol. New orders and amends are only matched while
+// Continuous; cancels are accepted in every state. During an Auction call
+// resting limit orders are collected without matching and uncrossed at a
+// single price once the symbol turns Continuous. PreOpen takes no orders,
+// collection only starts when the call opens.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, EnumStringify)]
+pub enum SessionState {
+    PreOpen,
+    Auction,
+    #[default]
+    Continuous,
+    Halted,
+    Closed,
+}
+
+// Outbound events (engine -> client). Every event carries a `sequence` that
+// the engine sets as it sends it, one higher than the event before, so
+// consumers can tell when they missed or reordered events.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
     OrderAck(OrderAck),
@@ -54,7 +199,11 @@ pub enum Event {
     Fill(Fill),
     Trade(Trade),
     OrderCancelled(OrderCancelled),
+    OrderAmended(OrderAmended),
+    StopPriceUpdated(StopPriceUpdated),
     BookUpdate(BookUpdate),
+    SessionStateChanged(SessionStateChanged),
+    IndicativePrice(IndicativePrice),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, EnumStringify)]
@@ -71,6 +220,7 @@ pub struct OrderAck {
     pub order_id: OrderId,
     pub user_id: UserId,
     pub symbol: String,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,9 +230,10 @@ pub struct OrderReject {
     pub reason: RejectReason,
     pub message: String,
     pub symbol: String,
+    pub sequence: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, EnumStringify)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, EnumStringify)]
 pub enum RejectReason {
     InvalidPrice,
     InvalidOrder,
@@ -90,9 +241,25 @@ pub enum RejectReason {
     InsufficientBalance,
     SymbolNotFound,
     MarketClosed,
+    InsufficientLiquidity,
+    PostOnlyWouldCross,
+    OrderNotFound,
+    NotOrderOwner,
+    PriceOutsideBand,
     InternalError,
 }
 
+// Whether a fill added liquidity from the book or took it. In an auction
+// uncross the order of each matched pair that joined its queue first is the
+// maker.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+// `fee` is charged in `fee_asset`, the asset the order receives: base for
+// buys, quote for sells. A negative fee is a rebate.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Fill {
     pub order_id: OrderId,
@@ -102,6 +269,10 @@ pub struct Fill {
     pub filled_quantity: Quantity,
     pub filled_price: Price,
     pub remaining_quantity: Quantity,
+    pub liquidity: Liquidity,
+    pub fee: i64,
+    pub fee_asset: String,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -114,7 +285,13 @@ pub struct Trade {
     pub symbol: String,
     pub quantity: Quantity,
     pub price: Price,
+    // Fees of the maker and taker fills of this trade, as on their `Fill`s
+    pub maker_fee: i64,
+    pub maker_fee_asset: String,
+    pub taker_fee: i64,
+    pub taker_fee_asset: String,
     pub timestamp: i64,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -123,14 +300,67 @@ pub struct OrderCancelled {
     pub user_id: UserId,
     pub symbol: String,
     pub reason: CancelReason,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderAmended {
+    pub order_id: OrderId,
+    pub user_id: UserId,
+    pub symbol: String,
+    pub price: Price,
+    pub quantity: Quantity,
+    pub remaining_quantity: Quantity,
+    pub sequence: u64,
+}
+
+// A trailing stop moved its trigger (and limit price for stop-limit orders)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopPriceUpdated {
+    pub order_id: OrderId,
+    pub user_id: UserId,
+    pub symbol: String,
+    pub stop_price: Price,
+    pub price: Option<Price>,
+    pub sequence: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum CancelReason {
     UserRequested,
     SystemCancelled,
     Expired,
     Liquidation,
+    ImmediateOrCancel,
+    SelfTradePrevention,
+    SlippageExceeded,
+    VolatilityInterruption,
+    // A triggered stop order failed the checks of the order it became
+    StopRejected,
+    // A market buy spent the funds it locked before it was filled
+    InsufficientBalance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStateChanged {
+    pub symbol: String,
+    pub previous: SessionState,
+    pub state: SessionState,
+    pub timestamp: i64,
+    pub sequence: u64,
+}
+
+// Where a symbol in its auction call would uncross now and how much would
+// trade. `price` is None while the book does not cross.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndicativePrice {
+    pub symbol: String,
+    pub price: Option<Price>,
+    pub volume: Quantity,
+    pub imbalance: Quantity,
+    pub imbalance_side: Option<Side>,
+    pub timestamp: i64,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,6 +369,7 @@ pub struct BookUpdate {
     pub bids: Vec<PriceLevel>,
     pub asks: Vec<PriceLevel>,
     pub last_price: Option<Price>,
+    pub sequence: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -147,6 +378,38 @@ pub struct PriceLevel {
     pub quantity: Quantity,
 }
 
+impl Event {
+    pub fn sequence(&self) -> u64 {
+        match self {
+            Event::OrderAck(event) => event.sequence,
+            Event::OrderReject(event) => event.sequence,
+            Event::Fill(event) => event.sequence,
+            Event::Trade(event) => event.sequence,
+            Event::OrderCancelled(event) => event.sequence,
+            Event::OrderAmended(event) => event.sequence,
+            Event::StopPriceUpdated(event) => event.sequence,
+            Event::BookUpdate(event) => event.sequence,
+            Event::SessionStateChanged(event) => event.sequence,
+            Event::IndicativePrice(event) => event.sequence,
+        }
+    }
+
+    pub fn set_sequence(&mut self, sequence: u64) {
+        match self {
+            Event::OrderAck(event) => event.sequence = sequence,
+            Event::OrderReject(event) => event.sequence = sequence,
+            Event::Fill(event) => event.sequence = sequence,
+            Event::Trade(event) => event.sequence = sequence,
+            Event::OrderCancelled(event) => event.sequence = sequence,
+            Event::OrderAmended(event) => event.sequence = sequence,
+            Event::StopPriceUpdated(event) => event.sequence = sequence,
+            Event::BookUpdate(event) => event.sequence = sequence,
+            Event::SessionStateChanged(event) => event.sequence = sequence,
+            Event::IndicativePrice(event) => event.sequence = sequence,
+        }
+    }
+}
+
 impl Order {
     pub fn new(
         order_id: OrderId,
@@ -164,9 +427,66 @@ impl Order {
             side,
             order_type,
             quantity,
+            quote_quantity: None,
+            display_quantity: None,
             price,
+            stop_price: None,
+            trailing_offset: None,
+            time_in_force: TimeInForce::default(),
+            expire_at: None,
+            post_only: None,
+            self_trade_prevention: None,
+            max_slippage_bps: None,
         }
     }
+
+    pub fn with_quote_quantity(mut self, quote_quantity: Option<Quantity>) -> Self {
+        self.quote_quantity = quote_quantity;
+        self
+    }
+
+    pub fn with_display_quantity(mut self, display_quantity: Option<Quantity>) -> Self {
+        self.display_quantity = display_quantity;
+        self
+    }
+
+    pub fn with_stop_price(mut self, stop_price: Option<Price>) -> Self {
+        self.stop_price = stop_price;
+        self
+    }
+
+    pub fn with_trailing_offset(mut self, trailing_offset: Option<TrailingOffset>) -> Self {
+        self.trailing_offset = trailing_offset;
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_expire_at(mut self, expire_at: Option<i64>) -> Self {
+        self.expire_at = expire_at;
+        self
+    }
+
+    pub fn with_post_only(mut self, post_only: Option<PostOnly>) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    pub fn with_self_trade_prevention(
+        mut self,
+        self_trade_prevention: Option<SelfTradePrevention>,
+    ) -> Self {
+        self.self_trade_prevention = self_trade_prevention;
+        self
+    }
+
+    pub fn with_max_slippage_bps(mut self, max_slippage_bps: Option<u64>) -> Self {
+        self.max_slippage_bps = max_slippage_bps;
+        self
+    }
 }
 
 impl CancelOrder {
@@ -178,3 +498,142 @@ impl CancelOrder {
         }
     }
 }
+
+impl AmendOrder {
+    pub fn new(
+        order_id: OrderId,
+        user_id: UserId,
+        symbol: String,
+        price: Option<Price>,
+        quantity: Option<Quantity>,
+    ) -> Self {
+        Self {
+            order_id,
+            user_id,
+            symbol,
+            price,
+            quantity,
+        }
+    }
+}
+
+impl MassCancel {
+    pub fn new(user_id: UserId, symbol: Option<String>, side: Option<Side>) -> Self {
+        Self {
+            user_id,
+            symbol,
+            side,
+        }
+    }
+}
+
+impl InstrumentSpec {
+    /// Spec that accepts any positive price and quantity, with base and quote
+    /// taken from a `BASE_QUOTE` symbol.
+    pub fn new(symbol: &str) -> Self {
+        let (base, quote) = symbol.split_once('_').unwrap_or((symbol, ""));
+
+        Self {
+            symbol: symbol.to_string(),
+            base: base.to_string(),
+            quote: quote.to_string(),
+            tick_size: 1,
+            min_price: 1,
+            max_price: Price::MAX,
+            step_size: 1,
+            min_quantity: 1,
+            max_quantity: Quantity::MAX,
+        }
+    }
+
+    pub fn with_price_rules(
+        mut self,
+        tick_size: Price,
+        min_price: Price,
+        max_price: Price,
+    ) -> Self {
+        self.tick_size = tick_size;
+        self.min_price = min_price;
+        self.max_price = max_price;
+        self
+    }
+
+    pub fn with_quantity_rules(
+        mut self,
+        step_size: Quantity,
+        min_quantity: Quantity,
+        max_quantity: Quantity,
+    ) -> Self {
+        self.step_size = step_size;
+        self.min_quantity = min_quantity;
+        self.max_quantity = max_quantity;
+        self
+    }
+
+    /// Checks the rules can be traded on: non-zero tick and step sizes and
+    /// ranges whose minimum is not above their maximum.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tick_size == 0 {
+            return Err(format!("{}: tick size must be greater than 0", self.symbol));
+        }
+        if self.step_size == 0 {
+            return Err(format!("{}: step size must be greater than 0", self.symbol));
+        }
+        if self.min_price > self.max_price {
+            return Err(format!(
+                "{}: min price {} is above max price {}",
+                self.symbol, self.min_price, self.max_price
+            ));
+        }
+        if self.min_quantity > self.max_quantity {
+            return Err(format!(
+                "{}: min quantity {} is above max quantity {}",
+                self.symbol, self.min_quantity, self.max_quantity
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl SetSessionState {
+    pub fn new(symbol: String, state: SessionState) -> Self {
+        Self { symbol, state }
+    }
+}
+
+impl Deposit {
+    pub fn new(user_id: UserId, asset: String, amount: Quantity) -> Self {
+        Self {
+            user_id,
+            asset,
+            amount,
+        }
+    }
+}
+
+impl SessionState {
+    /// Whether a symbol in this state may be moved to `next`. Continuous
+    /// trading is only ever entered by uncrossing an auction call, so a
+    /// closed symbol reopens through pre-open and a halt is lifted through a
+    /// call. A halt can only be lifted, not entered, from outside trading
+    /// hours.
+    pub fn can_transition_to(&self, next: SessionState) -> bool {
+        use SessionState::*;
+
+        matches!(
+            (self, next),
+            (PreOpen, Auction | Halted | Closed)
+                | (Auction, Continuous | Halted | Closed)
+                | (Continuous, Halted | Closed)
+                | (Halted, PreOpen | Auction | Closed)
+                | (Closed, PreOpen)
+        )
+    }
+}
+
+impl GetDepth {
+    pub fn new(symbol: String, limit: usize) -> Self {
+        Self { symbol, limit }
+    }
+}
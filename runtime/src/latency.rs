@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+
+/// Longest latency told apart from others; anything slower is counted here
+const MAX_LATENCY_NANOS: u64 = 60_000_000_000;
+/// Significant digits kept for every recorded latency
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+/// Latency histograms keyed by what was timed, e.g. a command name.
+///
+/// Each histogram keeps every sample in nanoseconds to three significant
+/// digits in fixed memory, so recording is cheap enough for a hot path and
+/// percentiles stay exact to that precision however many samples come in.
+/// `Display` prints a line of percentiles per key, meant to be dumped at
+/// shutdown.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyRecorder {
+    histograms: BTreeMap<&'static str, Histogram<u64>>,
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, key: &'static str, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+
+        self.histograms
+            .entry(key)
+            .or_insert_with(|| {
+                Histogram::new_with_bounds(1, MAX_LATENCY_NANOS, SIGNIFICANT_DIGITS)
+                    .expect("latency histogram bounds are valid")
+            })
+            .saturating_record(nanos.max(1));
+    }
+
+    /// Samples recorded under `key`, in nanoseconds.
+    pub fn histogram(&self, key: &str) -> Option<&Histogram<u64>> {
+        self.histograms.get(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.histograms.is_empty()
+    }
+}
+
+impl fmt::Display for LatencyRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, histogram) in &self.histograms {
+            let at = |quantile: f64| Duration::from_nanos(histogram.value_at_quantile(quantile));
+
+            writeln!(
+                f,
+                "{}: count={} min={:?} p50={:?} p90={:?} p99={:?} p99.9={:?} max={:?}",
+                key,
+                histogram.len(),
+                Duration::from_nanos(histogram.min()),
+                at(0.5),
+                at(0.9),
+                at(0.99),
+                at(0.999),
+                Duration::from_nanos(histogram.max()),
+            )?;
+        }
+
+        Ok(())
+    }
+}
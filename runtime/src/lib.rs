@@ -1,3 +1,5 @@
+pub mod latency;
+
 use once_cell::sync::Lazy;
 use tokio::runtime::{Builder, Runtime};
 
@@ -12,7 +14,10 @@ pub static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::RUNTIME;
+    use super::latency::LatencyRecorder;
 
     #[test]
     fn runtime_spawns_and_runs_tasks() {
@@ -22,4 +27,30 @@ mod tests {
             assert_eq!(result, 2);
         });
     }
+
+    #[test]
+    fn latency_recorder_keeps_percentiles_per_key() {
+        let mut recorder = LatencyRecorder::new();
+        assert!(recorder.is_empty());
+
+        for micros in 1..=100 {
+            recorder.record("PlaceOrder", Duration::from_micros(micros));
+        }
+        recorder.record("CancelOrder", Duration::from_secs(3600));
+
+        let place = recorder.histogram("PlaceOrder").unwrap();
+        assert_eq!(place.len(), 100);
+        assert!(place.equivalent(place.value_at_quantile(0.5), 50_000));
+        assert!(place.equivalent(place.max(), 100_000));
+
+        // Samples past the histogram's range are kept at its top
+        let cancel = recorder.histogram("CancelOrder").unwrap();
+        assert_eq!(cancel.len(), 1);
+        assert!(recorder.histogram("GetDepth").is_none());
+
+        let report = recorder.to_string();
+        assert_eq!(report.lines().count(), 2);
+        assert!(report.starts_with("CancelOrder: count=1"));
+        assert!(report.contains("PlaceOrder: count=100"));
+    }
 }